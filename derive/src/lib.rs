@@ -0,0 +1,143 @@
+// #[derive(VertexLayout)], see src/vertex.rs in the main crate
+//
+// attributes get consecutive shader locations in field order, with offsets
+// summed from the field sizes (bytemuck::Pod already rules out padding)
+//
+// struct attributes:
+//   #[vertex(instance)]      - step once per instance instead of once per vertex
+//   #[vertex(location = 2)]  - shader location of the first field
+//   #[vertex(crate = "gfx")] - path of the main crate, when it's renamed or re-exported;
+//                              ::wgpu_tiny_graphics otherwise
+
+use proc_macro::TokenStream;
+use quote::quote;
+
+#[proc_macro_derive(VertexLayout, attributes(vertex))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match vertex_layout(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn vertex_layout(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "VertexLayout can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut instance = false;
+    let mut location = 0u32;
+    let mut krate: syn::Path = syn::parse_quote!(::wgpu_tiny_graphics);
+    for attribute in input.attrs.iter().filter(|a| a.path.is_ident("vertex")) {
+        let list = match attribute.parse_meta()? {
+            syn::Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected #[vertex(...)]")),
+        };
+        for nested in list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("instance") => {
+                    instance = true;
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Int(lit),
+                    ..
+                })) if path.is_ident("location") => {
+                    location = lit.base10_parse()?;
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if path.is_ident("crate") => {
+                    krate = lit.parse()?;
+                }
+                nested => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "expected `instance`, `location = N` or `crate = \"path\"`",
+                    ))
+                }
+            }
+        }
+    }
+
+    let mut attributes = vec![];
+    let mut offset = quote! { 0 };
+    for field in fields {
+        let ty = &field.ty;
+
+        // a matrix takes up one location per column
+        let columns = match matrix_columns(ty) {
+            Some(columns) => columns,
+            None => {
+                attributes.push(quote! {
+                    wgpu::VertexAttribute {
+                        offset: (#offset) as wgpu::BufferAddress,
+                        shader_location: #location,
+                        format: <#ty as #krate::vertex::VertexAttributeFormat>::FORMAT,
+                    }
+                });
+                location += 1;
+                offset = quote! { #offset + std::mem::size_of::<#ty>() };
+                continue;
+            }
+        };
+
+        for column in 0..columns {
+            attributes.push(quote! {
+                wgpu::VertexAttribute {
+                    offset: (#offset + #column * std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress,
+                    shader_location: #location,
+                    format: wgpu::VertexFormat::Float4,
+                }
+            });
+            location += 1;
+        }
+        offset = quote! { #offset + std::mem::size_of::<#ty>() };
+    }
+
+    let step_mode = if instance {
+        quote! { wgpu::InputStepMode::Instance }
+    } else {
+        quote! { wgpu::InputStepMode::Vertex }
+    };
+
+    Ok(quote! {
+        impl #krate::vertex::VertexLayout for #name {
+            const STEP_MODE: wgpu::InputStepMode = #step_mode;
+            const ATTRIBUTES: &'static [wgpu::VertexAttribute] = &[#(#attributes),*];
+        }
+    })
+}
+
+// [[f32; 4]; N] is a matrix with N columns
+fn matrix_columns(ty: &syn::Type) -> Option<usize> {
+    let outer = match ty {
+        syn::Type::Array(array) => array,
+        _ => return None,
+    };
+    let is_column = match &*outer.elem {
+        syn::Type::Array(inner) => quote!(#inner).to_string().replace(' ', "") == "[f32;4]",
+        _ => false,
+    };
+    if !is_column {
+        return None;
+    }
+    match &outer.len {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(lit),
+            ..
+        }) => lit.base10_parse().ok(),
+        _ => None,
+    }
+}
@@ -0,0 +1,82 @@
+// the derive used from outside of the main crate, like a user of the library would
+use wgpu_tiny_graphics::vertex::{Uchar4Norm, VertexLayout};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+struct Vertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: Uchar4Norm,
+    layer: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+#[vertex(instance, location = 3)]
+struct Instance {
+    transform: [[f32; 4]; 4],
+    tint: [f32; 4],
+}
+
+mod renamed {
+    use wgpu_tiny_graphics as gfx;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, gfx::vertex::VertexLayout)]
+    #[vertex(crate = "gfx")]
+    pub struct Vertex {
+        pub position: [f32; 3],
+    }
+}
+
+fn attributes<T: VertexLayout>() -> Vec<(wgpu::BufferAddress, u32, wgpu::VertexFormat)> {
+    T::ATTRIBUTES
+        .iter()
+        .map(|attribute| {
+            (
+                attribute.offset,
+                attribute.shader_location,
+                attribute.format,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn lays_out_vertex_fields() {
+    assert_eq!(Vertex::STEP_MODE, wgpu::InputStepMode::Vertex);
+    assert_eq!(
+        attributes::<Vertex>(),
+        vec![
+            (0, 0, wgpu::VertexFormat::Float2),
+            (8, 1, wgpu::VertexFormat::Float2),
+            (16, 2, wgpu::VertexFormat::Uchar4Norm),
+            (20, 3, wgpu::VertexFormat::Uint),
+        ]
+    );
+    let descriptor = Vertex::descriptor();
+    assert_eq!(descriptor.array_stride, 24);
+}
+
+#[test]
+fn splits_matrices_into_columns() {
+    assert_eq!(Instance::STEP_MODE, wgpu::InputStepMode::Instance);
+    assert_eq!(
+        attributes::<Instance>(),
+        vec![
+            (0, 3, wgpu::VertexFormat::Float4),
+            (16, 4, wgpu::VertexFormat::Float4),
+            (32, 5, wgpu::VertexFormat::Float4),
+            (48, 6, wgpu::VertexFormat::Float4),
+            (64, 7, wgpu::VertexFormat::Float4),
+        ]
+    );
+}
+
+#[test]
+fn uses_the_crate_path_override() {
+    assert_eq!(
+        attributes::<renamed::Vertex>(),
+        vec![(0, 0, wgpu::VertexFormat::Float3)]
+    );
+}
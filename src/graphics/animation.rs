@@ -0,0 +1,480 @@
+// sprite-sheet animation: a clip is a list of texture rects with durations,
+// an AnimatedSprite plays one back on a Sprite
+use crate::frame_counter::FrameCounter;
+use crate::graphics::{Atlas, GraphicsState, Rect, Sprite, Texture, TextureOptions};
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum AnimationError {
+    // a frame name that isn't in the atlas
+    MissingFrame(String),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnimationError::MissingFrame(name) => write!(f, "no frame named {}", name),
+            AnimationError::Io(e) => write!(f, "{}", e),
+            AnimationError::Json(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {}
+
+impl From<std::io::Error> for AnimationError {
+    fn from(e: std::io::Error) -> Self {
+        AnimationError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for AnimationError {
+    fn from(e: serde_json::Error) -> Self {
+        AnimationError::Json(e)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PlayMode {
+    Loop,
+    // forwards, then backwards, without repeating the end frames
+    PingPong,
+    // stops on the last frame
+    Once,
+}
+
+impl Default for PlayMode {
+    fn default() -> Self {
+        PlayMode::Loop
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AnimationEvent {
+    FrameChanged(usize),
+    // back at the first frame, after a full cycle
+    Looped,
+    // a PlayMode::Once clip reached its end
+    Finished,
+}
+
+#[derive(Clone)]
+pub struct AnimationFrame {
+    pub texture: Rc<Texture>,
+    // in pixels
+    pub rect: Rect,
+    pub duration: Duration,
+}
+
+#[derive(Clone)]
+pub struct AnimationClip {
+    pub frames: Vec<AnimationFrame>,
+    pub mode: PlayMode,
+}
+
+// new
+impl AnimationClip {
+    pub fn new(frames: Vec<AnimationFrame>, mode: PlayMode) -> Self {
+        AnimationClip { frames, mode }
+    }
+
+    // frames are indices into a grid of `frame_size` cells, left to right then top to bottom;
+    // panics when the cells are empty
+    pub fn from_grid<I: IntoIterator<Item = usize>>(
+        texture: &Rc<Texture>,
+        frame_size: (u32, u32),
+        frames: I,
+        duration: Duration,
+        mode: PlayMode,
+    ) -> Self {
+        assert!(
+            frame_size.0 > 0 && frame_size.1 > 0,
+            "animation frames need a size, got {:?}",
+            frame_size
+        );
+        let columns = (texture.size().0 / frame_size.0).max(1) as usize;
+        let frames = frames
+            .into_iter()
+            .map(|index| AnimationFrame {
+                texture: texture.clone(),
+                rect: Rect::new(
+                    ((index % columns) as u32 * frame_size.0) as f32,
+                    ((index / columns) as u32 * frame_size.1) as f32,
+                    frame_size.0 as f32,
+                    frame_size.1 as f32,
+                ),
+                duration,
+            })
+            .collect();
+        AnimationClip { frames, mode }
+    }
+
+    pub fn from_atlas(
+        atlas: &Atlas,
+        names: &[&str],
+        duration: Duration,
+        mode: PlayMode,
+    ) -> Result<Self, AnimationError> {
+        let mut frames = vec![];
+        for &name in names {
+            let region = atlas
+                .region(name)
+                .ok_or_else(|| AnimationError::MissingFrame(name.to_owned()))?;
+            frames.push(AnimationFrame {
+                texture: atlas.texture(region.page).clone(),
+                rect: region.rect,
+                duration,
+            });
+        }
+        Ok(AnimationClip { frames, mode })
+    }
+
+    // one clip per tag of an Aseprite sprite sheet export (json "hash" or "array"),
+    // or a single looping clip named "default" when there are no tags;
+    // the sheet image is loaded relative to the json, and is the placeholder texture when
+    // that fails
+    pub fn load_aseprite<P: AsRef<Path>>(
+        graphics_state: &GraphicsState,
+        json_path: P,
+        texture_options: TextureOptions,
+    ) -> Result<HashMap<String, AnimationClip>, AnimationError> {
+        let json_path = json_path.as_ref();
+        let file = std::fs::File::open(json_path)?;
+        let sheet: aseprite::Sheet = serde_json::from_reader(std::io::BufReader::new(file))?;
+
+        let texture = graphics_state.load_texture_or_placeholder(
+            json_path.with_file_name(&sheet.meta.image),
+            texture_options,
+        );
+        Ok(sheet.clips(&texture))
+    }
+}
+
+// accessors
+impl AnimationClip {
+    pub fn total_duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    // until a looping clip is back at the same frame, None for Once clips and overflowing cycles
+    pub fn cycle_duration(&self) -> Option<Duration> {
+        let total = self.total_duration();
+        match self.mode {
+            PlayMode::Loop => Some(total),
+            PlayMode::Once => None,
+            PlayMode::PingPong if self.frames.len() == 1 => Some(total),
+            // the first and last frames are shown once per cycle, the others twice
+            PlayMode::PingPong => total.checked_mul(2).map(|twice| {
+                twice - self.frames[0].duration - self.frames[self.frames.len() - 1].duration
+            }),
+        }
+    }
+}
+
+pub struct AnimatedSprite {
+    // its texture and texture rect follow the current frame
+    pub sprite: Sprite,
+    // multiplies the frame delta, 0 freezes the animation
+    pub speed: f32,
+    clip: Rc<AnimationClip>,
+    frame: usize,
+    // time spent on the current frame
+    elapsed: Duration,
+    backwards: bool,
+    playing: bool,
+}
+
+// new
+impl AnimatedSprite {
+    pub fn new(clip: Rc<AnimationClip>) -> Self {
+        assert!(!clip.frames.is_empty(), "animation clip has no frames");
+        let mut animated_sprite = AnimatedSprite {
+            sprite: Sprite::new(clip.frames[0].texture.clone()),
+            speed: 1.0,
+            clip,
+            frame: 0,
+            elapsed: Duration::default(),
+            backwards: false,
+            playing: true,
+        };
+        animated_sprite.show_frame(0);
+        animated_sprite
+    }
+}
+
+// playback
+impl AnimatedSprite {
+    // restarts from the first frame, unless `clip` is already playing
+    pub fn set_clip(&mut self, clip: &Rc<AnimationClip>) {
+        if Rc::ptr_eq(&self.clip, clip) {
+            return;
+        }
+        assert!(!clip.frames.is_empty(), "animation clip has no frames");
+        self.clip = clip.clone();
+        self.stop();
+        self.playing = true;
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    // pauses and rewinds to the first frame
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.elapsed = Duration::default();
+        self.backwards = false;
+        self.show_frame(0);
+    }
+
+    // advances by the duration of the last presented frame
+    pub fn advance(&mut self, frame_counter: &FrameCounter) -> Vec<AnimationEvent> {
+        self.update(frame_counter.last_frame_time())
+    }
+
+    pub fn update(&mut self, delta: Duration) -> Vec<AnimationEvent> {
+        let mut events = vec![];
+        if !self.playing || self.speed.is_nan() || self.speed <= 0.0 {
+            return events;
+        }
+        // a clip made only of zero length frames would never leave the loop below
+        if self.clip.total_duration() == Duration::default() {
+            return events;
+        }
+
+        // saturates instead of overflowing for huge or infinite speeds
+        let scaled = delta.as_secs_f64() * self.speed as f64;
+        let delta = if scaled < u64::MAX as f64 {
+            Duration::from_secs_f64(scaled)
+        } else {
+            Duration::from_secs(u64::MAX)
+        };
+        self.elapsed = self.elapsed.saturating_add(delta);
+        // whole cycles end on the frame they started, only the last one is stepped through
+        if let Some(cycle) = self.clip.cycle_duration() {
+            if cycle
+                .checked_mul(2)
+                .map_or(false, |two| self.elapsed >= two)
+            {
+                let rest = self.elapsed.as_nanos() % cycle.as_nanos();
+                self.elapsed = cycle
+                    + Duration::new((rest / 1_000_000_000) as u64, (rest % 1_000_000_000) as u32);
+            }
+        }
+        let frame_before = self.frame;
+        let mut frame = self.frame;
+        while self.elapsed >= self.clip.frames[frame].duration {
+            self.elapsed -= self.clip.frames[frame].duration;
+            match self.next_frame(frame) {
+                Some((next, looped)) => {
+                    frame = next;
+                    if looped {
+                        events.push(AnimationEvent::Looped);
+                    }
+                }
+                None => {
+                    self.playing = false;
+                    self.elapsed = Duration::default();
+                    events.push(AnimationEvent::Finished);
+                    break;
+                }
+            }
+        }
+
+        if frame != frame_before {
+            self.show_frame(frame);
+            events.insert(0, AnimationEvent::FrameChanged(frame));
+        }
+        events
+    }
+
+    // the frame after `frame` and whether that completed a cycle, None when a Once clip ends
+    fn next_frame(&mut self, frame: usize) -> Option<(usize, bool)> {
+        let last = self.clip.frames.len() - 1;
+        match self.clip.mode {
+            PlayMode::Loop if frame >= last => Some((0, true)),
+            PlayMode::Loop => Some((frame + 1, false)),
+            PlayMode::Once if frame >= last => None,
+            PlayMode::Once => Some((frame + 1, false)),
+            PlayMode::PingPong if last == 0 => Some((0, true)),
+            PlayMode::PingPong => {
+                if self.backwards {
+                    let next = frame - 1;
+                    if next == 0 {
+                        self.backwards = false;
+                    }
+                    Some((next, next == 0))
+                } else {
+                    let next = frame + 1;
+                    if next == last {
+                        self.backwards = true;
+                    }
+                    Some((next, false))
+                }
+            }
+        }
+    }
+
+    fn show_frame(&mut self, frame: usize) {
+        self.frame = frame;
+        let frame = &self.clip.frames[frame];
+        if !Rc::ptr_eq(&self.sprite.texture, &frame.texture) {
+            self.sprite.texture = frame.texture.clone();
+        }
+        self.sprite.texture_rect = Some(frame.rect);
+    }
+}
+
+// accessors
+impl AnimatedSprite {
+    pub fn clip(&self) -> &Rc<AnimationClip> {
+        &self.clip
+    }
+
+    pub fn current_frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+}
+
+// the parts of Aseprite's sprite sheet json that matter for animation
+mod aseprite {
+    use super::{AnimationClip, AnimationFrame, PlayMode};
+    use crate::graphics::{Rect, Texture};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    #[derive(serde::Deserialize)]
+    pub(super) struct Sheet {
+        frames: Frames,
+        pub(super) meta: Meta,
+    }
+
+    // "json hash" exports key frames by file name, their order is the frame order
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Frames {
+        Array(Vec<Frame>),
+        Hash(OrderedFrames),
+    }
+
+    struct OrderedFrames(Vec<Frame>);
+
+    #[derive(serde::Deserialize)]
+    struct Frame {
+        frame: FrameRect,
+        // in milliseconds
+        duration: u64,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct FrameRect {
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+    }
+
+    #[derive(serde::Deserialize)]
+    pub(super) struct Meta {
+        pub(super) image: String,
+        #[serde(default, rename = "frameTags")]
+        frame_tags: Vec<FrameTag>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct FrameTag {
+        name: String,
+        from: usize,
+        to: usize,
+        #[serde(default)]
+        direction: String,
+    }
+
+    impl<'de> serde::Deserialize<'de> for OrderedFrames {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct Visitor;
+
+            impl<'de> serde::de::Visitor<'de> for Visitor {
+                type Value = OrderedFrames;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    write!(f, "a map of frame names to frames")
+                }
+
+                fn visit_map<A: serde::de::MapAccess<'de>>(
+                    self,
+                    mut map: A,
+                ) -> Result<Self::Value, A::Error> {
+                    let mut frames = vec![];
+                    while let Some((_, frame)) = map.next_entry::<String, Frame>()? {
+                        frames.push(frame);
+                    }
+                    Ok(OrderedFrames(frames))
+                }
+            }
+
+            deserializer.deserialize_map(Visitor)
+        }
+    }
+
+    impl Sheet {
+        pub(super) fn clips(&self, texture: &Rc<Texture>) -> HashMap<String, AnimationClip> {
+            let frames: Vec<AnimationFrame> = match &self.frames {
+                Frames::Array(frames) | Frames::Hash(OrderedFrames(frames)) => frames
+                    .iter()
+                    .map(|frame| AnimationFrame {
+                        texture: texture.clone(),
+                        rect: Rect::new(
+                            frame.frame.x as f32,
+                            frame.frame.y as f32,
+                            frame.frame.w as f32,
+                            frame.frame.h as f32,
+                        ),
+                        duration: Duration::from_millis(frame.duration),
+                    })
+                    .collect(),
+            };
+
+            let mut clips = HashMap::new();
+            if self.meta.frame_tags.is_empty() {
+                clips.insert(
+                    "default".to_owned(),
+                    AnimationClip::new(frames, PlayMode::Loop),
+                );
+                return clips;
+            }
+
+            for tag in &self.meta.frame_tags {
+                if tag.from > tag.to || tag.to >= frames.len() {
+                    log::warn!("aseprite tag {} is out of the frame range", tag.name);
+                    continue;
+                }
+                let mut tag_frames = frames[tag.from..=tag.to].to_vec();
+                let mode = match tag.direction.as_str() {
+                    "pingpong" => PlayMode::PingPong,
+                    "reverse" => {
+                        tag_frames.reverse();
+                        PlayMode::Loop
+                    }
+                    _ => PlayMode::Loop,
+                };
+                clips.insert(tag.name.clone(), AnimationClip::new(tag_frames, mode));
+            }
+            clips
+        }
+    }
+}
@@ -0,0 +1,618 @@
+// packs many small images into a few big textures, so sprites using them can share batches
+use crate::graphics::texture::placeholder_rgba;
+use crate::graphics::{GraphicsState, Rect, Sprite, Texture, TextureOptions};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    // bigger than a page, even without any padding around it
+    ImageTooLarge(String),
+    // a loaded region on a page the atlas doesn't have
+    MissingPage(String, usize),
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtlasError::ImageTooLarge(name) => write!(f, "image {} doesn't fit in a page", name),
+            AtlasError::MissingPage(name, page) => {
+                write!(
+                    f,
+                    "region {} is on page {}, which doesn't exist",
+                    name, page
+                )
+            }
+            AtlasError::Io(e) => write!(f, "{}", e),
+            AtlasError::Image(e) => write!(f, "{}", e),
+            AtlasError::Json(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl From<std::io::Error> for AtlasError {
+    fn from(e: std::io::Error) -> Self {
+        AtlasError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for AtlasError {
+    fn from(e: image::ImageError) -> Self {
+        AtlasError::Image(e)
+    }
+}
+
+impl From<serde_json::Error> for AtlasError {
+    fn from(e: serde_json::Error) -> Self {
+        AtlasError::Json(e)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct AtlasOptions {
+    pub page_size: (u32, u32),
+    // empty pixels between neighbouring images
+    pub padding: u32,
+    // how many times the edge pixels are repeated around each image,
+    // so linear filtering doesn't pull in the neighbours
+    pub extrude: u32,
+    pub texture_options: TextureOptions,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        AtlasOptions {
+            page_size: (1024, 1024),
+            padding: 2,
+            extrude: 1,
+            texture_options: TextureOptions::default(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AtlasRegion {
+    pub page: usize,
+    // in pixels, without padding and extrusion
+    pub rect: Rect,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    // where the next image on this shelf goes
+    x: u32,
+}
+
+// shelf packing: images are placed left to right in rows ("shelves"),
+// a new shelf is opened below the last one when none of them has room
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub(super) struct ShelfPacker {
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    pub(super) fn pack(&mut self, page_size: (u32, u32), size: (u32, u32)) -> Option<(u32, u32)> {
+        let (width, height) = size;
+
+        // the lowest shelf that fits, to waste as little height as possible
+        let best = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && shelf.x + width <= page_size.0)
+            .min_by_key(|shelf| shelf.height - height);
+        if let Some(shelf) = best {
+            let position = (shelf.x, shelf.y);
+            shelf.x += width;
+            return Some(position);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        if y + height > page_size.1 || width > page_size.0 {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+        Some((0, y))
+    }
+}
+
+// x, y, width, height
+type PixelRect = (u32, u32, u32, u32);
+
+struct AtlasPage {
+    // cpu copy, for runtime additions and for saving
+    image: image::RgbaImage,
+    // none until the page is first uploaded
+    texture: Option<Rc<Texture>>,
+    packer: ShelfPacker,
+}
+
+pub struct Atlas {
+    options: AtlasOptions,
+    pages: Vec<AtlasPage>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+// the json written next to the page pngs
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AtlasLayout {
+    page_size: (u32, u32),
+    padding: u32,
+    extrude: u32,
+    pages: Vec<AtlasLayoutPage>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AtlasLayoutPage {
+    // relative to the json file
+    image: String,
+    packer: ShelfPacker,
+}
+
+pub struct AtlasBuilder {
+    options: AtlasOptions,
+    images: Vec<(String, image::RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    pub fn new(options: AtlasOptions) -> Self {
+        AtlasBuilder {
+            options,
+            images: vec![],
+        }
+    }
+
+    pub fn add(&mut self, name: &str, image: &image::DynamicImage) -> &mut Self {
+        self.images.push((name.to_owned(), image.to_rgba8()));
+        self
+    }
+
+    pub fn add_path<P: AsRef<Path>>(
+        &mut self,
+        name: &str,
+        path: P,
+    ) -> Result<&mut Self, AtlasError> {
+        let image = image::open(path)?;
+        Ok(self.add(name, &image))
+    }
+
+    pub fn build(&mut self, graphics_state: &GraphicsState) -> Result<Atlas, AtlasError> {
+        let mut atlas = Atlas {
+            options: self.options,
+            pages: vec![],
+            regions: HashMap::new(),
+        };
+
+        // tallest first packs shelves a lot tighter
+        let mut images = std::mem::take(&mut self.images);
+        images.sort_by_key(|(_, image)| std::cmp::Reverse(image.height()));
+
+        // everything is blitted on the cpu first, so each page is uploaded once
+        for (name, image) in &images {
+            atlas.place(name, image)?;
+        }
+        for page in &mut atlas.pages {
+            page.texture = Some(Rc::new(page_texture(
+                graphics_state,
+                &page.image,
+                &self.options,
+            )?));
+        }
+
+        Ok(atlas)
+    }
+}
+
+// other
+impl Atlas {
+    // packs another image at runtime, reusing free space in the existing pages when possible
+    pub fn add(
+        &mut self,
+        graphics_state: &GraphicsState,
+        name: &str,
+        image: &image::DynamicImage,
+    ) -> Result<AtlasRegion, AtlasError> {
+        let image = image.to_rgba8();
+        let pages_before = self.pages.len();
+        let (region, cell) = self.place(name, &image)?;
+
+        let page = &mut self.pages[region.page];
+        match &page.texture {
+            Some(texture) if region.page < pages_before => {
+                let pixels = image::imageops::crop_imm(&page.image, cell.0, cell.1, cell.2, cell.3)
+                    .to_image();
+                texture.update_region(graphics_state, cell.0, cell.1, cell.2, cell.3, &pixels)?;
+            }
+            _ => {
+                page.texture = Some(Rc::new(page_texture(
+                    graphics_state,
+                    &page.image,
+                    &self.options,
+                )?));
+            }
+        }
+
+        Ok(region)
+    }
+
+    // packs and blits on the cpu only, returns the region and the touched pixels
+    fn place(
+        &mut self,
+        name: &str,
+        image: &image::RgbaImage,
+    ) -> Result<(AtlasRegion, PixelRect), AtlasError> {
+        let options = self.options;
+        let extrude = options.extrude;
+        let cell_size = (image.width() + 2 * extrude, image.height() + 2 * extrude);
+        // padding is only needed between cells, not against the page edges
+        let padded_size = (cell_size.0 + options.padding, cell_size.1 + options.padding);
+
+        let mut placement = None;
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(position) = page.packer.pack(options.page_size, padded_size) {
+                placement = Some((index, position));
+                break;
+            }
+        }
+        let (page_index, position) = match placement {
+            Some(placement) => placement,
+            None => {
+                let mut packer = ShelfPacker::default();
+                let position = packer
+                    .pack(options.page_size, padded_size)
+                    .or_else(|| packer.pack(options.page_size, cell_size))
+                    .ok_or_else(|| AtlasError::ImageTooLarge(name.to_owned()))?;
+                self.pages.push(AtlasPage {
+                    image: image::RgbaImage::new(options.page_size.0, options.page_size.1),
+                    texture: None,
+                    packer,
+                });
+                (self.pages.len() - 1, position)
+            }
+        };
+
+        let page = &mut self.pages[page_index];
+        let (x, y) = (position.0 + extrude, position.1 + extrude);
+        image::imageops::replace(&mut page.image, image, x, y);
+        extrude_edges(&mut page.image, (x, y), image.dimensions(), extrude);
+
+        let region = AtlasRegion {
+            page: page_index,
+            rect: Rect::new(
+                x as f32,
+                y as f32,
+                image.width() as f32,
+                image.height() as f32,
+            ),
+        };
+        if self.regions.insert(name.to_owned(), region).is_some() {
+            log::warn!("atlas region {} was replaced", name);
+        }
+
+        Ok((region, (position.0, position.1, cell_size.0, cell_size.1)))
+    }
+}
+
+// accessors
+impl Atlas {
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = (&str, AtlasRegion)> {
+        self.regions
+            .iter()
+            .map(|(name, region)| (name.as_str(), *region))
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn texture(&self, page: usize) -> &Rc<Texture> {
+        self.pages[page]
+            .texture
+            .as_ref()
+            .expect("atlas page wasn't uploaded")
+    }
+
+    pub fn sprite(&self, name: &str) -> Option<Sprite> {
+        let region = self.region(name)?;
+        let mut sprite = Sprite::new(self.texture(region.page).clone());
+        sprite.texture_rect = Some(region.rect);
+        Some(sprite)
+    }
+}
+
+// saving and loading
+impl Atlas {
+    // writes the layout to `json_path` and each page to `<json stem>_<page>.png` next to it
+    pub fn save<P: AsRef<Path>>(&self, json_path: P) -> Result<(), AtlasError> {
+        let json_path = json_path.as_ref();
+        let stem = json_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "atlas".to_owned());
+
+        let mut pages = vec![];
+        for (index, page) in self.pages.iter().enumerate() {
+            let file_name = format!("{}_{}.png", stem, index);
+            page.image.save(sibling(json_path, &file_name))?;
+            pages.push(AtlasLayoutPage {
+                image: file_name,
+                packer: page.packer.clone(),
+            });
+        }
+
+        let layout = AtlasLayout {
+            page_size: self.options.page_size,
+            padding: self.options.padding,
+            extrude: self.options.extrude,
+            pages,
+            regions: self.regions.clone(),
+        };
+        let file = std::fs::File::create(json_path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), &layout)?;
+
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(
+        graphics_state: &GraphicsState,
+        json_path: P,
+        texture_options: TextureOptions,
+    ) -> Result<Atlas, AtlasError> {
+        let mut atlas = Atlas::read(json_path.as_ref(), texture_options)?;
+        for page in &mut atlas.pages {
+            page.texture = Some(Rc::new(page_texture(
+                graphics_state,
+                &page.image,
+                &atlas.options,
+            )?));
+        }
+        Ok(atlas)
+    }
+
+    // the layout and the page images, without uploading them
+    fn read(json_path: &Path, texture_options: TextureOptions) -> Result<Atlas, AtlasError> {
+        let file = std::fs::File::open(json_path)?;
+        let layout: AtlasLayout = serde_json::from_reader(std::io::BufReader::new(file))?;
+        if let Some((name, region)) = layout
+            .regions
+            .iter()
+            .find(|(_, region)| region.page >= layout.pages.len())
+        {
+            return Err(AtlasError::MissingPage(name.clone(), region.page));
+        }
+
+        let options = AtlasOptions {
+            page_size: layout.page_size,
+            padding: layout.padding,
+            extrude: layout.extrude,
+            texture_options,
+        };
+
+        let mut pages = vec![];
+        for page in layout.pages {
+            // a page that fails to load is filled with the placeholder's squares
+            let image_path = sibling(json_path, &page.image);
+            let image = match image::open(&image_path) {
+                Ok(image) => image.to_rgba8(),
+                Err(e) => {
+                    log::error!("couldn't load {}: {}", image_path.display(), e);
+                    let (width, height) = options.page_size;
+                    image::RgbaImage::from_raw(width, height, placeholder_rgba((width, height)))
+                        .unwrap()
+                }
+            };
+            pages.push(AtlasPage {
+                image,
+                texture: None,
+                packer: page.packer,
+            });
+        }
+
+        Ok(Atlas {
+            options,
+            pages,
+            regions: layout.regions,
+        })
+    }
+}
+
+fn sibling(path: &Path, file_name: &str) -> PathBuf {
+    path.with_file_name(file_name)
+}
+
+fn page_texture(
+    graphics_state: &GraphicsState,
+    image: &image::RgbaImage,
+    options: &AtlasOptions,
+) -> Result<Texture, image::ImageError> {
+    Texture::new(
+        graphics_state,
+        "atlas page",
+        image.dimensions(),
+        image,
+        options.texture_options,
+    )
+}
+
+// repeats the outermost pixels of the image at (x, y) `extrude` times in every direction
+fn extrude_edges(
+    page: &mut image::RgbaImage,
+    position: (u32, u32),
+    size: (u32, u32),
+    extrude: u32,
+) {
+    if extrude == 0 || size.0 == 0 || size.1 == 0 {
+        return;
+    }
+    let (x, y) = position;
+    let (width, height) = size;
+
+    for row in 0..height {
+        let left = *page.get_pixel(x, y + row);
+        let right = *page.get_pixel(x + width - 1, y + row);
+        for i in 1..=extrude {
+            page.put_pixel(x - i, y + row, left);
+            page.put_pixel(x + width - 1 + i, y + row, right);
+        }
+    }
+
+    // whole rows, including the corners extruded above
+    for column in x - extrude..x + width + extrude {
+        let top = *page.get_pixel(column, y);
+        let bottom = *page.get_pixel(column, y + height - 1);
+        for i in 1..=extrude {
+            page.put_pixel(column, y - i, top);
+            page.put_pixel(column, y + height - 1 + i, bottom);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atlas(page_size: (u32, u32), padding: u32, extrude: u32) -> Atlas {
+        Atlas {
+            options: AtlasOptions {
+                page_size,
+                padding,
+                extrude,
+                texture_options: TextureOptions::default(),
+            },
+            pages: vec![],
+            regions: HashMap::new(),
+        }
+    }
+
+    // a different color per pixel
+    fn gradient(width: u32, height: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([(x * 16) as u8, (y * 16) as u8, 100, 255])
+        })
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn packs_shelves() {
+        let mut packer = ShelfPacker::default();
+        let page_size = (10, 10);
+        assert_eq!(packer.pack(page_size, (4, 4)), Some((0, 0)));
+        assert_eq!(packer.pack(page_size, (4, 2)), Some((4, 0)));
+        // no room left on the first shelf
+        assert_eq!(packer.pack(page_size, (4, 4)), Some((0, 4)));
+        // the lowest shelf that fits
+        assert_eq!(packer.pack(page_size, (1, 3)), Some((8, 0)));
+        assert_eq!(packer.pack(page_size, (3, 3)), Some((4, 4)));
+        assert_eq!(packer.pack(page_size, (11, 1)), None);
+        // no room below the last shelf
+        assert_eq!(packer.pack(page_size, (5, 5)), None);
+        assert_eq!(packer.pack(page_size, (5, 2)), Some((0, 8)));
+    }
+
+    #[test]
+    fn extrudes_edges() {
+        let mut page = image::RgbaImage::new(4, 4);
+        let image = gradient(2, 2);
+        image::imageops::replace(&mut page, &image, 1, 1);
+        extrude_edges(&mut page, (1, 1), (2, 2), 1);
+        for y in 0..4 {
+            for x in 0..4 {
+                let inside = (x.clamp(1, 2) - 1, y.clamp(1, 2) - 1);
+                assert_eq!(
+                    page.get_pixel(x, y),
+                    image.get_pixel(inside.0, inside.1),
+                    "{}, {}",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn places_images_with_padding_and_extrusion() {
+        let mut atlas = atlas((16, 16), 2, 1);
+        let (region, cell) = atlas.place("a", &gradient(4, 4)).unwrap();
+        assert_eq!(region.page, 0);
+        assert_eq!(region.rect, Rect::new(1.0, 1.0, 4.0, 4.0));
+        assert_eq!(cell, (0, 0, 6, 6));
+        let (region, _) = atlas.place("b", &gradient(4, 4)).unwrap();
+        assert_eq!(region.rect, Rect::new(9.0, 1.0, 4.0, 4.0));
+        // a page of its own, without padding against the page's edges
+        let (region, _) = atlas.place("c", &gradient(14, 14)).unwrap();
+        assert_eq!((region.page, region.rect.left), (1, 1.0));
+        assert!(matches!(
+            atlas.place("d", &gradient(15, 15)),
+            Err(AtlasError::ImageTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn saves_and_reads_back() {
+        let mut atlas = atlas((16, 16), 2, 1);
+        atlas.place("a", &gradient(4, 4)).unwrap();
+        atlas.place("b", &gradient(14, 14)).unwrap();
+        let dir = temp_dir("atlas_round_trip");
+        let json_path = dir.join("atlas.json");
+        atlas.save(&json_path).unwrap();
+
+        let mut read = Atlas::read(&json_path, TextureOptions::default()).unwrap();
+        assert_eq!(read.regions, atlas.regions);
+        assert_eq!(read.options.page_size, (16, 16));
+        assert_eq!((read.options.padding, read.options.extrude), (2, 1));
+        assert_eq!(read.page_count(), 2);
+        for (read, page) in read.pages.iter().zip(&atlas.pages) {
+            assert_eq!(read.image, page.image);
+        }
+        // packing goes on where it stopped
+        assert_eq!(
+            read.place("c", &gradient(2, 2)).unwrap(),
+            atlas.place("c", &gradient(2, 2)).unwrap()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_regions_on_missing_pages() {
+        let dir = temp_dir("atlas_missing_page");
+        let json_path = dir.join("atlas.json");
+        std::fs::write(
+            &json_path,
+            r#"{
+                "page_size": [16, 16],
+                "padding": 0,
+                "extrude": 0,
+                "pages": [],
+                "regions": { "a": { "page": 1, "rect": { "left": 0, "top": 0, "width": 4, "height": 4 } } }
+            }"#,
+        )
+        .unwrap();
+        let result = Atlas::read(&json_path, TextureOptions::default());
+        assert!(matches!(result, Err(AtlasError::MissingPage(name, 1)) if name == "a"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,597 @@
+// AngelCode BMFont fonts, <https://www.angelcode.com/products/bmfont/doc/file_format.html>,
+// in the text or the binary format; drawn by RendererSprite::push_text
+use crate::graphics::sprite::quad_vertices;
+use crate::graphics::text::Text;
+use crate::graphics::{GraphicsState, Rect, Texture, TextureOptions};
+use crate::vertex::TexturedVertex;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::Path;
+use std::rc::Rc;
+use wgpu_glyph::{HorizontalAlign, VerticalAlign};
+
+#[derive(Debug)]
+pub enum BitmapFontError {
+    Invalid(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for BitmapFontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitmapFontError::Invalid(e) => write!(f, "invalid bitmap font: {}", e),
+            BitmapFontError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BitmapFontError {}
+
+impl From<std::io::Error> for BitmapFontError {
+    fn from(e: std::io::Error) -> Self {
+        BitmapFontError::Io(e)
+    }
+}
+
+fn invalid<T>(message: &str) -> Result<T, BitmapFontError> {
+    Err(BitmapFontError::Invalid(message.to_owned()))
+}
+
+// in pixels of its page
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BitmapChar {
+    pub rect: Rect,
+    // from the pen position on the top of the line to the top left of the rect
+    pub offset: (f32, f32),
+    pub advance: f32,
+    pub page: usize,
+}
+
+// what the .fnt file says, before the pages are loaded
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Description {
+    size: f32,
+    line_height: f32,
+    base: f32,
+    pages: Vec<String>,
+    chars: HashMap<char, BitmapChar>,
+    kernings: HashMap<(char, char), f32>,
+}
+
+pub struct BitmapFont {
+    // the size the font was exported at, in pixels; texts are scaled by Text::size / size
+    pub size: f32,
+    pub line_height: f32,
+    // from the top of a line to the baseline
+    pub base: f32,
+    pages: Vec<Rc<Texture>>,
+    chars: HashMap<char, BitmapChar>,
+    kernings: HashMap<(char, char), f32>,
+}
+
+// new
+impl BitmapFont {
+    // the page textures are loaded from next to the .fnt file, pages that fail to load are the
+    // placeholder texture; pixel fonts want TextureOptions::filter set to Nearest
+    pub fn load<P: AsRef<Path>>(
+        graphics_state: &GraphicsState,
+        path: P,
+        texture_options: TextureOptions,
+    ) -> Result<Self, BitmapFontError> {
+        let path = path.as_ref();
+        let description = parse(&std::fs::read(path)?)?;
+        let pages = description
+            .pages
+            .iter()
+            .map(|page| {
+                graphics_state
+                    .load_texture_or_placeholder(path.with_file_name(page), texture_options)
+            })
+            .collect();
+        BitmapFont::from_description(description, pages)
+    }
+
+    // `pages` in the order of the file's page ids
+    pub fn from_bytes(fnt: &[u8], pages: Vec<Rc<Texture>>) -> Result<Self, BitmapFontError> {
+        BitmapFont::from_description(parse(fnt)?, pages)
+    }
+
+    fn from_description(
+        description: Description,
+        pages: Vec<Rc<Texture>>,
+    ) -> Result<Self, BitmapFontError> {
+        if pages.len() < description.pages.len() {
+            return invalid("missing page textures");
+        }
+        if description.chars.values().any(|c| c.page >= pages.len()) {
+            return invalid("a char is on a page that doesn't exist");
+        }
+        Ok(BitmapFont {
+            size: description.size,
+            line_height: description.line_height,
+            base: description.base,
+            pages,
+            chars: description.chars,
+            kernings: description.kernings,
+        })
+    }
+}
+
+// accessors
+impl BitmapFont {
+    pub fn page(&self, page: usize) -> &Rc<Texture> {
+        &self.pages[page]
+    }
+
+    pub fn char(&self, c: char) -> Option<&BitmapChar> {
+        self.chars.get(&c)
+    }
+
+    // added to the advance of `first` when `second` follows it
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.kernings.get(&(first, second)).copied().unwrap_or(0.0)
+    }
+}
+
+// a char of a text, in place on its line
+struct PlacedChar {
+    c: char,
+    bitmap_char: BitmapChar,
+    // from the start of the line
+    x: f32,
+    scale: f32,
+    color: [f32; 4],
+}
+
+struct Line {
+    chars: Vec<PlacedChar>,
+    // without trailing whitespace
+    width: f32,
+    // of its biggest char, which sets the line's height
+    scale: f32,
+}
+
+// layout
+impl BitmapFont {
+    // quads for RendererSprite, and the page each one samples; laid out with the text's
+    // alignment, wrapping, line spacing and styles, in the sprite renderer's world units.
+    // Text::font, space and rendering don't apply to bitmap fonts
+    pub fn vertices(&self, text: &Text) -> Vec<(usize, [TexturedVertex; 6])> {
+        let lines = self.break_lines(text);
+        let baselines = self.baselines(text, &lines);
+
+        let mut vertices = Vec::new();
+        for (line, &baseline) in lines.iter().zip(baselines.iter()) {
+            let left = match text.horizontal_align {
+                HorizontalAlign::Left => text.position.0,
+                HorizontalAlign::Center => text.position.0 - line.width / 2.0,
+                HorizontalAlign::Right => text.position.0 - line.width,
+            };
+
+            for placed in &line.chars {
+                let bitmap_char = &placed.bitmap_char;
+                if bitmap_char.rect.width == 0.0 || bitmap_char.rect.height == 0.0 {
+                    continue;
+                }
+                // bigger or smaller spans share the baseline; whole units keep pixel fonts sharp
+                let x = (left + placed.x + bitmap_char.offset.0 * placed.scale).round();
+                let y = (baseline + (bitmap_char.offset.1 - self.base) * placed.scale).round();
+                let local = Rect::new(
+                    x,
+                    y,
+                    bitmap_char.rect.width * placed.scale,
+                    bitmap_char.rect.height * placed.scale,
+                );
+                vertices.push((
+                    bitmap_char.page,
+                    quad_vertices(
+                        &self.pages[bitmap_char.page],
+                        &text.transform,
+                        local,
+                        bitmap_char.rect,
+                        placed.color,
+                    ),
+                ));
+            }
+        }
+        vertices
+    }
+
+    // lines are as high as their biggest char, and spaced out around the one that stays put,
+    // like TextLayout does it
+    fn baselines(&self, text: &Text, lines: &[Line]) -> Vec<f32> {
+        let height: f32 = lines.iter().map(|line| self.line_height * line.scale).sum();
+        let mut top = match text.vertical_align {
+            VerticalAlign::Top => text.position.1,
+            VerticalAlign::Center => text.position.1 - height / 2.0,
+            VerticalAlign::Bottom => text.position.1 - height,
+        };
+        let mut tops = Vec::with_capacity(lines.len());
+        for line in lines {
+            tops.push(top);
+            top += self.line_height * line.scale;
+        }
+
+        let first_top = tops[0];
+        let last_top = tops[tops.len() - 1];
+        let anchor = match text.vertical_align {
+            VerticalAlign::Top => first_top,
+            VerticalAlign::Center => (first_top + last_top) / 2.0,
+            VerticalAlign::Bottom => last_top,
+        };
+        lines
+            .iter()
+            .zip(tops)
+            .map(|(line, top)| anchor + (top - anchor) * text.line_spacing + self.base * line.scale)
+            .collect()
+    }
+
+    // at '\n', and between words when a line gets longer than the wrap width
+    fn break_lines(&self, text: &Text) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut line = Vec::new();
+        let mut pen = 0.0;
+        let mut previous = None;
+        // where the line can be broken, after whitespace
+        let mut break_at = None;
+
+        for (range, style) in text.spans() {
+            let scale = style.and_then(|style| style.size).unwrap_or(text.size) / self.size;
+            let color = style.and_then(|style| style.color).unwrap_or(text.color);
+
+            for c in text.string[range].chars() {
+                if c == '\n' {
+                    lines.push(self.finish_line(text, std::mem::take(&mut line)));
+                    pen = 0.0;
+                    previous = None;
+                    break_at = None;
+                    continue;
+                }
+                let bitmap_char = match self.chars.get(&c).or_else(|| self.chars.get(&'?')) {
+                    Some(bitmap_char) => *bitmap_char,
+                    None => continue,
+                };
+
+                let kerning = previous.map_or(0.0, |previous| self.kerning(previous, c) * scale);
+                let mut x = pen + kerning;
+                let advance = bitmap_char.advance * scale;
+                let overflows = text
+                    .wrap_width
+                    .map_or(false, |wrap_width| x + advance > wrap_width);
+                if overflows && !c.is_whitespace() {
+                    if let Some(break_at) = break_at.take() {
+                        let rest: Vec<PlacedChar> = line.split_off(break_at);
+                        let shift = rest.first().map_or(x, |placed| placed.x);
+                        lines.push(self.finish_line(text, std::mem::replace(&mut line, rest)));
+                        for placed in &mut line {
+                            placed.x -= shift;
+                        }
+                        x -= shift;
+                    }
+                }
+
+                line.push(PlacedChar {
+                    c,
+                    bitmap_char,
+                    x,
+                    scale,
+                    color,
+                });
+                pen = x + advance;
+                previous = Some(c);
+                if c.is_whitespace() {
+                    break_at = Some(line.len());
+                }
+            }
+        }
+        lines.push(self.finish_line(text, line));
+        lines
+    }
+
+    // empty lines keep the text's size
+    fn finish_line(&self, text: &Text, chars: Vec<PlacedChar>) -> Line {
+        let width = chars
+            .iter()
+            .rev()
+            .find(|placed| !placed.c.is_whitespace())
+            .map_or(0.0, |placed| {
+                placed.x + placed.bitmap_char.advance * placed.scale
+            });
+        let scale = if chars.is_empty() {
+            text.size / self.size
+        } else {
+            chars.iter().map(|placed| placed.scale).fold(0.0, f32::max)
+        };
+        Line {
+            chars,
+            width,
+            scale,
+        }
+    }
+}
+
+// parsing
+fn parse(bytes: &[u8]) -> Result<Description, BitmapFontError> {
+    if bytes.starts_with(b"BMF") {
+        parse_binary(bytes)
+    } else {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => parse_text(text),
+            Err(_) => invalid("neither the text nor the binary format"),
+        }
+    }
+}
+
+fn parse_text(text: &str) -> Result<Description, BitmapFontError> {
+    let mut description = Description::default();
+    for line in text.lines() {
+        let mut tokens = tokenize(line);
+        let tag = match tokens.next() {
+            Some((tag, None)) => tag,
+            _ => continue,
+        };
+        let values: HashMap<&str, &str> = tokens
+            .filter_map(|(key, value)| value.map(|value| (key, value)))
+            .collect();
+        let number = |key: &str| -> Result<f32, BitmapFontError> {
+            match values.get(key).map(|value| value.parse::<f32>()) {
+                Some(Ok(value)) => Ok(value),
+                _ => Err(BitmapFontError::Invalid(format!(
+                    "{} has no number {}",
+                    tag, key
+                ))),
+            }
+        };
+
+        match tag {
+            // negative sizes are in "character height" rather than "cell height"
+            "info" => description.size = number("size")?.abs(),
+            "common" => {
+                description.line_height = number("lineHeight")?;
+                description.base = number("base")?;
+            }
+            "page" => {
+                let id = number("id")? as usize;
+                let file = values.get("file").copied().unwrap_or_default();
+                if description.pages.len() <= id {
+                    description.pages.resize(id + 1, String::new());
+                }
+                description.pages[id] = file.to_owned();
+            }
+            "char" => {
+                let c = char_from_id(number("id")? as i64)?;
+                let bitmap_char = BitmapChar {
+                    rect: Rect::new(
+                        number("x")?,
+                        number("y")?,
+                        number("width")?,
+                        number("height")?,
+                    ),
+                    offset: (number("xoffset")?, number("yoffset")?),
+                    advance: number("xadvance")?,
+                    page: number("page")? as usize,
+                };
+                if let Some(c) = c {
+                    description.chars.insert(c, bitmap_char);
+                }
+            }
+            "kerning" => {
+                let first = char_from_id(number("first")? as i64)?;
+                let second = char_from_id(number("second")? as i64)?;
+                if let (Some(first), Some(second)) = (first, second) {
+                    description
+                        .kernings
+                        .insert((first, second), number("amount")?);
+                }
+            }
+            _ => {}
+        }
+    }
+    check(description)
+}
+
+// `key=value` pairs, values can be quoted; the first token is the tag without a value
+fn tokenize(line: &str) -> impl Iterator<Item = (&str, Option<&str>)> {
+    let mut rest = line.trim();
+    std::iter::from_fn(move || {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = &rest[key_end..];
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let (value, after) = match after.strip_prefix('"') {
+                    Some(quoted) => {
+                        let end = quoted.find('"').unwrap_or(quoted.len());
+                        (&quoted[..end], quoted.get(end + 1..).unwrap_or(""))
+                    }
+                    None => {
+                        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+                rest = after;
+                Some(value)
+            }
+            None => None,
+        };
+        Some((key, value))
+    })
+}
+
+// -1 is the "invalid char" glyph some exporters add, it's skipped
+fn char_from_id(id: i64) -> Result<Option<char>, BitmapFontError> {
+    if id < 0 {
+        return Ok(None);
+    }
+    match std::char::from_u32(id as u32) {
+        Some(c) => Ok(Some(c)),
+        None => invalid("a char id isn't a unicode scalar value"),
+    }
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Description, BitmapFontError> {
+    if bytes.get(3) != Some(&3) {
+        return invalid("only version 3 of the binary format is supported");
+    }
+    let mut description = Description::default();
+    let mut rest = &bytes[4..];
+    while !rest.is_empty() {
+        if rest.len() < 5 {
+            return invalid("truncated block header");
+        }
+        let block_type = rest[0];
+        let size = u32::from_le_bytes(rest[1..5].try_into().unwrap()) as usize;
+        let block = match rest.get(5..5 + size) {
+            Some(block) => block,
+            None => return invalid("truncated block"),
+        };
+        rest = &rest[5 + size..];
+
+        let u16_at = |offset: usize| u16::from_le_bytes([block[offset], block[offset + 1]]);
+        let i16_at = |offset: usize| i16::from_le_bytes([block[offset], block[offset + 1]]);
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap());
+        match block_type {
+            1 if size >= 2 => description.size = (i16_at(0) as f32).abs(),
+            2 if size >= 4 => {
+                description.line_height = u16_at(0) as f32;
+                description.base = u16_at(2) as f32;
+            }
+            // null terminated file names
+            3 => {
+                description.pages = block
+                    .split(|&byte| byte == 0)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| String::from_utf8_lossy(name).into_owned())
+                    .collect();
+            }
+            4 => {
+                for offset in (0..size - size % 20).step_by(20) {
+                    let c = char_from_id(u32_at(offset) as i32 as i64)?;
+                    let bitmap_char = BitmapChar {
+                        rect: Rect::new(
+                            u16_at(offset + 4) as f32,
+                            u16_at(offset + 6) as f32,
+                            u16_at(offset + 8) as f32,
+                            u16_at(offset + 10) as f32,
+                        ),
+                        offset: (i16_at(offset + 12) as f32, i16_at(offset + 14) as f32),
+                        advance: i16_at(offset + 16) as f32,
+                        page: block[offset + 18] as usize,
+                    };
+                    if let Some(c) = c {
+                        description.chars.insert(c, bitmap_char);
+                    }
+                }
+            }
+            5 => {
+                for offset in (0..size - size % 10).step_by(10) {
+                    let first = char_from_id(u32_at(offset) as i32 as i64)?;
+                    let second = char_from_id(u32_at(offset + 4) as i32 as i64)?;
+                    if let (Some(first), Some(second)) = (first, second) {
+                        description
+                            .kernings
+                            .insert((first, second), i16_at(offset + 8) as f32);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    check(description)
+}
+
+fn check(description: Description) -> Result<Description, BitmapFontError> {
+    if description.size <= 0.0 || description.line_height <= 0.0 {
+        return invalid("missing info or common block");
+    }
+    Ok(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT_FNT: &[u8] = include_bytes!("../test_font_text.fnt");
+    const BINARY_FNT: &[u8] = include_bytes!("../test_font_binary.fnt");
+
+    fn font() -> BitmapFont {
+        let description = parse(TEXT_FNT).unwrap();
+        BitmapFont {
+            size: description.size,
+            line_height: description.line_height,
+            base: description.base,
+            pages: vec![],
+            chars: description.chars,
+            kernings: description.kernings,
+        }
+    }
+
+    #[test]
+    fn parses_the_text_format() {
+        let description = parse(TEXT_FNT).unwrap();
+        assert_eq!(description.size, 16.0);
+        assert_eq!(description.line_height, 20.0);
+        assert_eq!(description.base, 16.0);
+        assert_eq!(description.pages, vec!["test font.png".to_owned()]);
+        // the -1 char is skipped
+        assert_eq!(description.chars.len(), 2);
+        assert_eq!(
+            description.chars[&'A'],
+            BitmapChar {
+                rect: Rect::new(0.0, 0.0, 8.0, 12.0),
+                offset: (1.0, 4.0),
+                advance: 10.0,
+                page: 0,
+            }
+        );
+        assert_eq!(description.chars[&' '].advance, 5.0);
+        assert_eq!(description.kernings[&('A', 'A')], -2.0);
+    }
+
+    #[test]
+    fn parses_the_binary_format_like_the_text_format() {
+        assert_eq!(parse(BINARY_FNT).unwrap(), parse(TEXT_FNT).unwrap());
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(parse(b"BMF\x02").is_err());
+        assert!(parse(&BINARY_FNT[..BINARY_FNT.len() - 1]).is_err());
+        assert!(parse(b"info size=16\n").is_err());
+        assert!(parse(&[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn kerning_narrows_lines() {
+        let lines = font().break_lines(&Text::new("AA A"));
+        assert_eq!(lines.len(), 1);
+        // 10 - 2 kerning, then 10, 5 for the space and 10
+        assert_eq!(lines[0].width, 33.0);
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let mut text = Text::new("AA AA");
+        text.wrap_width = Some(20.0);
+        let lines = font().break_lines(&text);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].chars[0].x, 0.0);
+        assert_eq!(lines[1].width, 18.0);
+    }
+
+    #[test]
+    fn lines_are_as_high_as_their_biggest_span() {
+        let font = font();
+        let text = Text::from_markup("A[size=32]A[/size]\nA\n");
+        let lines = font.break_lines(&text);
+        // twice the line height and base for the first line, the empty one keeps the text's
+        assert_eq!(font.baselines(&text, &lines), vec![32.0, 56.0, 76.0]);
+    }
+}
@@ -0,0 +1,64 @@
+// reimplementation of <https://www.sfml-dev.org/documentation/2.5.1/structsf_1_1BlendMode.php>
+// as a closed set of presets, so it can key the renderers' pipeline caches
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    // overwrites the destination
+    None,
+    Alpha,
+    // for colors that were already multiplied by their alpha
+    PremultipliedAlpha,
+    Add,
+    Multiply,
+    Min,
+    Max,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Alpha
+    }
+}
+
+impl BlendMode {
+    // https://github.com/SFML/SFML/blob/master/src/SFML/Graphics/BlendMode.cpp#L35
+    pub fn color_blend(self) -> wgpu::BlendState {
+        use wgpu::BlendFactor::*;
+        use wgpu::BlendOperation::*;
+
+        let (src_factor, dst_factor, operation) = match self {
+            BlendMode::None => (One, Zero, Add),
+            BlendMode::Alpha => (SrcAlpha, OneMinusSrcAlpha, Add),
+            BlendMode::PremultipliedAlpha => (One, OneMinusSrcAlpha, Add),
+            BlendMode::Add => (SrcAlpha, One, Add),
+            BlendMode::Multiply => (DstColor, Zero, Add),
+            BlendMode::Min => (One, One, Min),
+            BlendMode::Max => (One, One, Max),
+        };
+
+        wgpu::BlendState {
+            src_factor,
+            dst_factor,
+            operation,
+        }
+    }
+
+    pub fn alpha_blend(self) -> wgpu::BlendState {
+        use wgpu::BlendFactor::*;
+        use wgpu::BlendOperation::*;
+
+        let (src_factor, dst_factor, operation) = match self {
+            BlendMode::None => (One, Zero, Add),
+            BlendMode::Alpha | BlendMode::PremultipliedAlpha => (One, OneMinusSrcAlpha, Add),
+            BlendMode::Add => (One, One, Add),
+            BlendMode::Multiply => (DstAlpha, Zero, Add),
+            BlendMode::Min => (One, One, Min),
+            BlendMode::Max => (One, One, Max),
+        };
+
+        wgpu::BlendState {
+            src_factor,
+            dst_factor,
+            operation,
+        }
+    }
+}
@@ -0,0 +1,1104 @@
+// cpu decoders for block compressed formats, for devices that can't sample them directly
+// covers BC1-7, ETC2, EAC and ASTC LDR; BC6H and ASTC come from texture2ddecoder, with BC6H's hdr
+// colors clamped to 0-1
+use std::convert::TryInto;
+
+type Block = [[u8; 4]; 16];
+
+// tightly packed rgba8 pixels, None when the format can't be decoded
+pub fn decode(format: wgpu::TextureFormat, size: (u32, u32), data: &[u8]) -> Option<Vec<u8>> {
+    use wgpu::TextureFormat::*;
+
+    let info = format.describe();
+    // every astc block size is decoded the same way
+    if info.required_features == wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR {
+        let (block_width, block_height) = info.block_dimensions;
+        return decode_blocks(format, size, data, |block, pixels| {
+            astc(block, (block_width as usize, block_height as usize), pixels)
+        });
+    }
+
+    let decode_block: fn(&[u8]) -> Block = match format {
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb => bc1,
+        Bc2RgbaUnorm | Bc2RgbaUnormSrgb => bc2,
+        Bc3RgbaUnorm | Bc3RgbaUnormSrgb => bc3,
+        Bc4RUnorm => |block| bc4(block, false),
+        Bc4RSnorm => |block| bc4(block, true),
+        Bc5RgUnorm => |block| bc5(block, false),
+        Bc5RgSnorm => |block| bc5(block, true),
+        Bc6hRgbUfloat => |block| bc6h(block, false),
+        Bc6hRgbSfloat => |block| bc6h(block, true),
+        Bc7RgbaUnorm | Bc7RgbaUnormSrgb => bc7,
+        Etc2RgbUnorm | Etc2RgbUnormSrgb => |block| etc2(block, false),
+        Etc2RgbA1Unorm | Etc2RgbA1UnormSrgb => |block| etc2(block, true),
+        Etc2RgbA8Unorm | Etc2RgbA8UnormSrgb => etc2_eac,
+        EacRUnorm => |block| eac_r(block, false),
+        EacRSnorm => |block| eac_r(block, true),
+        EtcRgUnorm => |block| eac_rg(block, false),
+        EtcRgSnorm => |block| eac_rg(block, true),
+        _ => return None,
+    };
+    decode_blocks(format, size, data, |block, pixels| {
+        pixels.copy_from_slice(&decode_block(block))
+    })
+}
+
+// `decode_block` gets one block's bytes and writes its pixels, row by row
+fn decode_blocks(
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+    data: &[u8],
+    decode_block: impl Fn(&[u8], &mut [[u8; 4]]),
+) -> Option<Vec<u8>> {
+    let info = format.describe();
+    let block_size = info.block_size as usize;
+    let block_width = info.block_dimensions.0 as usize;
+    let block_height = info.block_dimensions.1 as usize;
+
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    let blocks_wide = (width + block_width - 1) / block_width;
+    let blocks_high = (height + block_height - 1) / block_height;
+    if data.len() < blocks_wide * blocks_high * block_size {
+        return None;
+    }
+
+    let mut rgba = vec![0; width * height * 4];
+    let mut pixels = vec![[0; 4]; block_width * block_height];
+    for block_y in 0..blocks_high {
+        for block_x in 0..blocks_wide {
+            let offset = (block_y * blocks_wide + block_x) * block_size;
+            decode_block(&data[offset..offset + block_size], &mut pixels);
+            for (i, pixel) in pixels.iter().enumerate() {
+                let x = block_x * block_width + i % block_width;
+                let y = block_y * block_height + i / block_width;
+                if x < width && y < height {
+                    let index = (y * width + x) * 4;
+                    rgba[index..index + 4].copy_from_slice(pixel);
+                }
+            }
+        }
+    }
+    Some(rgba)
+}
+
+// bc1-3
+
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = ((color >> 11) & 31) as u8;
+    let g = ((color >> 5) & 63) as u8;
+    let b = (color & 31) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+// bc2 and bc3 always use the four color mode
+fn bc1_colors(block: &[u8], punch_through: bool) -> Block {
+    let color_0 = u16::from_le_bytes([block[0], block[1]]);
+    let color_1 = u16::from_le_bytes([block[2], block[3]]);
+    let c0 = rgb565(color_0);
+    let c1 = rgb565(color_1);
+    let mix = |a: u8, b: u8, wa: u32, wb: u32| ((a as u32 * wa + b as u32 * wb) / (wa + wb)) as u8;
+
+    let mut palette = [
+        [c0[0], c0[1], c0[2], 255],
+        [c1[0], c1[1], c1[2], 255],
+        [0; 4],
+        [0; 4],
+    ];
+    if color_0 > color_1 || !punch_through {
+        for channel in 0..3 {
+            palette[2][channel] = mix(c0[channel], c1[channel], 2, 1);
+            palette[3][channel] = mix(c0[channel], c1[channel], 1, 2);
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for channel in 0..3 {
+            palette[2][channel] = mix(c0[channel], c1[channel], 1, 1);
+        }
+        palette[2][3] = 255;
+        // palette[3] stays transparent black
+    }
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut pixels = [[0; 4]; 16];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+    pixels
+}
+
+// whether any block of bc1 data is in its three color mode and uses the transparent index
+pub fn bc1_has_transparent_pixels(data: &[u8]) -> bool {
+    data.chunks_exact(8).any(|block| {
+        let color_0 = u16::from_le_bytes([block[0], block[1]]);
+        let color_1 = u16::from_le_bytes([block[2], block[3]]);
+        let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+        color_0 <= color_1 && (0..16).any(|i| (indices >> (2 * i)) & 3 == 3)
+    })
+}
+
+fn bc1(block: &[u8]) -> Block {
+    bc1_colors(block, true)
+}
+
+fn bc2(block: &[u8]) -> Block {
+    let mut pixels = bc1_colors(&block[8..16], false);
+    let alpha = u64::from_le_bytes(block[0..8].try_into().unwrap());
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let a = ((alpha >> (4 * i)) & 15) as u8;
+        pixel[3] = (a << 4) | a;
+    }
+    pixels
+}
+
+fn bc3(block: &[u8]) -> Block {
+    let mut pixels = bc1_colors(&block[8..16], false);
+    let alpha = bc4_values(&block[0..8], false);
+    for (pixel, a) in pixels.iter_mut().zip(alpha.iter()) {
+        pixel[3] = *a;
+    }
+    pixels
+}
+
+// bc4-5, also the alpha of bc3
+
+fn bc4_values(block: &[u8], signed: bool) -> [u8; 16] {
+    // snorm values are mapped from -1..1 to 0..255, -128 is -1 too
+    let (e0, e1) = if signed {
+        (
+            ((block[0] as i8).max(-127) as i32 + 127) * 255 / 254,
+            ((block[1] as i8).max(-127) as i32 + 127) * 255 / 254,
+        )
+    } else {
+        (block[0] as i32, block[1] as i32)
+    };
+    let four_or_six = if signed {
+        (block[0] as i8) > (block[1] as i8)
+    } else {
+        block[0] > block[1]
+    };
+
+    let mut palette = [e0, e1, 0, 0, 0, 0, 0, 0];
+    if four_or_six {
+        for i in 1..7 {
+            palette[i + 1] = (e0 * (7 - i as i32) + e1 * i as i32) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (e0 * (5 - i as i32) + e1 * i as i32) / 5;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits = 0u64;
+    for (i, byte) in block[2..8].iter().enumerate() {
+        bits |= (*byte as u64) << (8 * i);
+    }
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((bits >> (3 * i)) & 7) as usize].clamp(0, 255) as u8;
+    }
+    values
+}
+
+fn bc4(block: &[u8], signed: bool) -> Block {
+    let mut pixels = [[0, 0, 0, 255]; 16];
+    for (pixel, r) in pixels.iter_mut().zip(bc4_values(block, signed).iter()) {
+        pixel[0] = *r;
+    }
+    pixels
+}
+
+fn bc5(block: &[u8], signed: bool) -> Block {
+    let mut pixels = [[0, 0, 0, 255]; 16];
+    let red = bc4_values(&block[0..8], signed);
+    let green = bc4_values(&block[8..16], signed);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[0] = red[i];
+        pixel[1] = green[i];
+    }
+    pixels
+}
+
+// bc6h, rgb16 half floats
+
+fn bc6h(block: &[u8], signed: bool) -> Block {
+    let mut pixels = [0; 16];
+    texture2ddecoder::decode_bc6_block(block, &mut pixels, signed);
+    let mut rgba = [[0; 4]; 16];
+    from_bgra(&pixels, &mut rgba);
+    rgba
+}
+
+// texture2ddecoder's pixels are little endian bgra u32s
+fn from_bgra(pixels: &[u32], rgba: &mut [[u8; 4]]) {
+    for (pixel, rgba) in pixels.iter().zip(rgba) {
+        let [b, g, r, a] = pixel.to_le_bytes();
+        *rgba = [r, g, b, a];
+    }
+}
+
+// bc7
+
+struct Bits<'a> {
+    block: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    // least significant bit first
+    fn read(&mut self, count: usize) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let bit = (self.block[self.position / 8] >> (self.position % 8)) & 1;
+            value |= (bit as u32) << i;
+            self.position += 1;
+        }
+        value
+    }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: usize,
+    secondary_index_bits: usize,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+// bit i is the subset of pixel i
+#[rustfmt::skip]
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+    0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+    0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+// bits 2i..2i+1 are the subset of pixel i
+#[rustfmt::skip]
+const BC7_PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+// the pixel of subset 1 (and 2) whose index has one bit less
+#[rustfmt::skip]
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+#[rustfmt::skip]
+const BC7_ANCHORS_3_1: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+
+#[rustfmt::skip]
+const BC7_ANCHORS_3_2: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn bc7_weight(index_bits: usize, index: u32) -> u32 {
+    match index_bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    }
+}
+
+fn bc7(block: &[u8]) -> Block {
+    let mode_index = block[0].trailing_zeros() as usize;
+    if mode_index >= 8 {
+        // reserved, decodes to transparent black
+        return [[0; 4]; 16];
+    }
+    let mode = &BC7_MODES[mode_index];
+    let mut bits = Bits {
+        block,
+        position: mode_index + 1,
+    };
+
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    // [subset * 2 + endpoint][channel], still at their stored precision
+    let mut endpoints = [[0u32; 4]; 6];
+    let endpoint_count = mode.subsets * 2;
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = bits.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = if mode.alpha_bits > 0 {
+            bits.read(mode.alpha_bits)
+        } else {
+            255
+        };
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_p_bits || mode.shared_p_bits {
+        let mut p_bits = [0u32; 6];
+        if mode.endpoint_p_bits {
+            for p_bit in p_bits.iter_mut().take(endpoint_count) {
+                *p_bit = bits.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let p_bit = bits.read(1);
+                p_bits[subset * 2] = p_bit;
+                p_bits[subset * 2 + 1] = p_bit;
+            }
+        }
+        for (endpoint, p_bit) in endpoints.iter_mut().zip(p_bits.iter()).take(endpoint_count) {
+            for value in endpoint.iter_mut().take(3) {
+                *value = (*value << 1) | p_bit;
+            }
+            if mode.alpha_bits > 0 {
+                endpoint[3] = (endpoint[3] << 1) | p_bit;
+            }
+        }
+        color_bits += 1;
+        if mode.alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    let expand = |value: u32, bits: usize| {
+        let value = value << (8 - bits);
+        value | (value >> bits)
+    };
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut().take(3) {
+            *value = expand(*value, color_bits);
+        }
+        if mode.alpha_bits > 0 {
+            endpoint[3] = expand(endpoint[3], alpha_bits);
+        }
+    }
+
+    let subset_of = |pixel: usize| -> usize {
+        match mode.subsets {
+            2 => ((BC7_PARTITIONS_2[partition] >> pixel) & 1) as usize,
+            3 => ((BC7_PARTITIONS_3[partition] >> (2 * pixel)) & 3) as usize,
+            _ => 0,
+        }
+    };
+    let is_anchor = |pixel: usize| -> bool {
+        pixel == 0
+            || match mode.subsets {
+                2 => pixel == BC7_ANCHORS_2[partition] as usize,
+                3 => {
+                    pixel == BC7_ANCHORS_3_1[partition] as usize
+                        || pixel == BC7_ANCHORS_3_2[partition] as usize
+                }
+                _ => false,
+            }
+    };
+
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let count = mode.index_bits - is_anchor(pixel) as usize;
+        *index = bits.read(count);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            let count = mode.secondary_index_bits - (pixel == 0) as usize;
+            *index = bits.read(count);
+        }
+    }
+
+    let mut pixels = [[0; 4]; 16];
+    for (pixel, out) in pixels.iter_mut().enumerate() {
+        let subset = subset_of(pixel);
+        let e0 = endpoints[subset * 2];
+        let e1 = endpoints[subset * 2 + 1];
+
+        // (color weight, alpha weight)
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = bc7_weight(mode.index_bits, indices[pixel]);
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                bc7_weight(mode.index_bits, indices[pixel]),
+                bc7_weight(mode.secondary_index_bits, secondary_indices[pixel]),
+            )
+        } else {
+            (
+                bc7_weight(mode.secondary_index_bits, secondary_indices[pixel]),
+                bc7_weight(mode.index_bits, indices[pixel]),
+            )
+        };
+
+        let interpolate = |a: u32, b: u32, weight: u32| ((64 - weight) * a + weight * b + 32) >> 6;
+        let mut color = [
+            interpolate(e0[0], e1[0], color_weight) as u8,
+            interpolate(e0[1], e1[1], color_weight) as u8,
+            interpolate(e0[2], e1[2], color_weight) as u8,
+            interpolate(e0[3], e1[3], alpha_weight) as u8,
+        ];
+        match rotation {
+            1 => color.swap(0, 3),
+            2 => color.swap(1, 3),
+            3 => color.swap(2, 3),
+            _ => {}
+        }
+        *out = color;
+    }
+    pixels
+}
+
+// etc2 and eac, pixels are stored column by column: i = x * 4 + y
+
+const ETC1_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const ETC2_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+#[rustfmt::skip]
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn clamp_u8(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn bits_of(value: u64, high: u32, count: u32) -> i32 {
+    ((value >> (high + 1 - count)) & ((1 << count) - 1)) as i32
+}
+
+fn extend_4(value: i32) -> i32 {
+    (value << 4) | value
+}
+
+fn extend_5(value: i32) -> i32 {
+    (value << 3) | (value >> 2)
+}
+
+fn extend_6(value: i32) -> i32 {
+    (value << 2) | (value >> 4)
+}
+
+fn extend_7(value: i32) -> i32 {
+    (value << 1) | (value >> 6)
+}
+
+// `punch_through` is Etc2RgbA1, where the differential bit says whether the block is opaque
+fn etc2(block: &[u8], punch_through: bool) -> Block {
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let differential = bits_of(bits, 33, 1) == 1;
+    let opaque = !punch_through || differential;
+    let flip = bits_of(bits, 32, 1) == 1;
+
+    // 2-bit selector of pixel i, in the order the block stores them
+    let selector = |i: usize| -> usize {
+        let msb = (bits >> (16 + i)) & 1;
+        let lsb = (bits >> i) & 1;
+        ((msb << 1) | lsb) as usize
+    };
+
+    let mut pixels = [[0; 4]; 16];
+    let mut set = |i: usize, color: [i32; 3], transparent: bool| {
+        let (x, y) = (i / 4, i % 4);
+        pixels[y * 4 + x] = if transparent {
+            [0; 4]
+        } else {
+            [
+                clamp_u8(color[0]),
+                clamp_u8(color[1]),
+                clamp_u8(color[2]),
+                255,
+            ]
+        };
+    };
+
+    if !differential && !punch_through {
+        // individual mode: two 4-bit colors
+        let base = [
+            [
+                extend_4(bits_of(bits, 63, 4)),
+                extend_4(bits_of(bits, 55, 4)),
+                extend_4(bits_of(bits, 47, 4)),
+            ],
+            [
+                extend_4(bits_of(bits, 59, 4)),
+                extend_4(bits_of(bits, 51, 4)),
+                extend_4(bits_of(bits, 43, 4)),
+            ],
+        ];
+        etc1_subblocks(bits, base, flip, true, &mut set, &selector);
+        return pixels;
+    }
+
+    let r = bits_of(bits, 63, 5);
+    let g = bits_of(bits, 55, 5);
+    let b = bits_of(bits, 47, 5);
+    // 3-bit two's complement deltas
+    let delta = |high: u32| {
+        let d = bits_of(bits, high, 3);
+        if d >= 4 {
+            d - 8
+        } else {
+            d
+        }
+    };
+    let (r2, g2, b2) = (r + delta(58), g + delta(50), b + delta(42));
+
+    if !(0..32).contains(&r2) {
+        // t mode
+        let r1 = (bits_of(bits, 60, 2) << 2) | bits_of(bits, 57, 2);
+        let base_1 = [
+            extend_4(r1),
+            extend_4(bits_of(bits, 55, 4)),
+            extend_4(bits_of(bits, 51, 4)),
+        ];
+        let base_2 = [
+            extend_4(bits_of(bits, 47, 4)),
+            extend_4(bits_of(bits, 43, 4)),
+            extend_4(bits_of(bits, 39, 4)),
+        ];
+        let distance =
+            ETC2_DISTANCES[((bits_of(bits, 35, 2) << 1) | bits_of(bits, 32, 1)) as usize];
+        let paint = [
+            base_1,
+            add(base_2, distance),
+            base_2,
+            add(base_2, -distance),
+        ];
+        for i in 0..16 {
+            let s = selector(i);
+            set(i, paint[s], !opaque && s == 2);
+        }
+    } else if !(0..32).contains(&g2) {
+        // h mode
+        let r1 = bits_of(bits, 62, 4);
+        let g1 = (bits_of(bits, 58, 3) << 1) | bits_of(bits, 52, 1);
+        let b1 = (bits_of(bits, 51, 1) << 3) | bits_of(bits, 49, 3);
+        let r2 = bits_of(bits, 46, 4);
+        let g2 = bits_of(bits, 42, 4);
+        let b2 = bits_of(bits, 38, 4);
+        let value_1 = (r1 << 8) | (g1 << 4) | b1;
+        let value_2 = (r2 << 8) | (g2 << 4) | b2;
+        let distance_index =
+            (bits_of(bits, 34, 1) << 2) | (bits_of(bits, 32, 1) << 1) | (value_1 >= value_2) as i32;
+        let distance = ETC2_DISTANCES[distance_index as usize];
+
+        let base_1 = [extend_4(r1), extend_4(g1), extend_4(b1)];
+        let base_2 = [extend_4(r2), extend_4(g2), extend_4(b2)];
+        let paint = [
+            add(base_1, distance),
+            add(base_1, -distance),
+            add(base_2, distance),
+            add(base_2, -distance),
+        ];
+        for i in 0..16 {
+            let s = selector(i);
+            set(i, paint[s], !opaque && s == 2);
+        }
+    } else if !(0..32).contains(&b2) {
+        // planar mode: a color gradient, never transparent
+        let origin = [
+            extend_6(bits_of(bits, 62, 6)),
+            extend_7((bits_of(bits, 56, 1) << 6) | bits_of(bits, 54, 6)),
+            extend_6(
+                (bits_of(bits, 48, 1) << 5) | (bits_of(bits, 44, 2) << 3) | bits_of(bits, 41, 3),
+            ),
+        ];
+        let horizontal = [
+            extend_6((bits_of(bits, 38, 5) << 1) | bits_of(bits, 32, 1)),
+            extend_7(bits_of(bits, 31, 7)),
+            extend_6(bits_of(bits, 24, 6)),
+        ];
+        let vertical = [
+            extend_6(bits_of(bits, 18, 6)),
+            extend_7(bits_of(bits, 12, 7)),
+            extend_6(bits_of(bits, 5, 6)),
+        ];
+        for i in 0..16 {
+            let (x, y) = ((i / 4) as i32, (i % 4) as i32);
+            let mut color = [0; 3];
+            for channel in 0..3 {
+                color[channel] = (x * (horizontal[channel] - origin[channel])
+                    + y * (vertical[channel] - origin[channel])
+                    + 4 * origin[channel]
+                    + 2)
+                    >> 2;
+            }
+            set(i, color, false);
+        }
+    } else {
+        let base = [
+            [extend_5(r), extend_5(g), extend_5(b)],
+            [extend_5(r2), extend_5(g2), extend_5(b2)],
+        ];
+        etc1_subblocks(bits, base, flip, opaque, &mut set, &selector);
+    }
+
+    pixels
+}
+
+fn add(color: [i32; 3], value: i32) -> [i32; 3] {
+    [color[0] + value, color[1] + value, color[2] + value]
+}
+
+// the individual and differential modes: each half of the block has a base color and a modifier table
+fn etc1_subblocks(
+    bits: u64,
+    base: [[i32; 3]; 2],
+    flip: bool,
+    opaque: bool,
+    set: &mut impl FnMut(usize, [i32; 3], bool),
+    selector: &impl Fn(usize) -> usize,
+) {
+    let tables = [
+        ETC1_MODIFIERS[bits_of(bits, 39, 3) as usize],
+        ETC1_MODIFIERS[bits_of(bits, 36, 3) as usize],
+    ];
+    for i in 0..16 {
+        let (x, y) = (i / 4, i % 4);
+        let subblock = if flip {
+            (y >= 2) as usize
+        } else {
+            (x >= 2) as usize
+        };
+        let [small, large] = tables[subblock];
+        let s = selector(i);
+        let modifier = match s {
+            0 if !opaque => 0,
+            0 => small,
+            1 => large,
+            2 => -small,
+            _ => -large,
+        };
+        set(i, add(base[subblock], modifier), !opaque && s == 2);
+    }
+}
+
+// 16 values, row by row
+fn eac_values(block: &[u8], eleven_bits: bool, signed: bool) -> [u8; 16] {
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let base = bits_of(bits, 63, 8);
+    let multiplier = bits_of(bits, 55, 4);
+    let table = EAC_MODIFIERS[bits_of(bits, 51, 4) as usize];
+
+    let mut values = [0; 16];
+    for i in 0..16 {
+        let modifier = table[bits_of(bits, 47 - 3 * i as u32, 3) as usize];
+        let value = if eleven_bits {
+            // r11 and rg11, reduced to 8 bits
+            let value = if signed {
+                let base = (base as i8) as i32;
+                let value = base * 8 + modifier * if multiplier == 0 { 1 } else { multiplier * 8 };
+                (value.clamp(-1023, 1023) + 1023) * 255 / 2046
+            } else {
+                let value =
+                    base * 8 + 4 + modifier * if multiplier == 0 { 1 } else { multiplier * 8 };
+                value.clamp(0, 2047) >> 3
+            };
+            value as u8
+        } else {
+            clamp_u8(base + modifier * multiplier)
+        };
+        let (x, y) = (i / 4, i % 4);
+        values[y * 4 + x] = value;
+    }
+    values
+}
+
+fn etc2_eac(block: &[u8]) -> Block {
+    let mut pixels = etc2(&block[8..16], false);
+    let alpha = eac_values(&block[0..8], false, false);
+    for (pixel, a) in pixels.iter_mut().zip(alpha.iter()) {
+        pixel[3] = *a;
+    }
+    pixels
+}
+
+fn eac_r(block: &[u8], signed: bool) -> Block {
+    let mut pixels = [[0, 0, 0, 255]; 16];
+    for (pixel, r) in pixels
+        .iter_mut()
+        .zip(eac_values(block, true, signed).iter())
+    {
+        pixel[0] = *r;
+    }
+    pixels
+}
+
+fn eac_rg(block: &[u8], signed: bool) -> Block {
+    let mut pixels = [[0, 0, 0, 255]; 16];
+    let red = eac_values(&block[0..8], true, signed);
+    let green = eac_values(&block[8..16], true, signed);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[0] = red[i];
+        pixel[1] = green[i];
+    }
+    pixels
+}
+
+// astc, 16 bytes for 4x4 to 12x12 pixels; invalid blocks and hdr endpoints decode to magenta
+
+fn astc(block: &[u8], block_dimensions: (usize, usize), rgba: &mut [[u8; 4]]) {
+    let (block_width, block_height) = block_dimensions;
+    let mut pixels = [0; 144];
+    texture2ddecoder::decode_astc_block(block, block_width, block_height, &mut pixels);
+    from_bgra(&pixels[..block_width * block_height], rgba);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // bc1 blocks: red and blue endpoints, pixels 0-3 use indices 0-3
+    const BC1_FOUR_COLORS: [u8; 8] = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0, 0, 0];
+    const BC1_THREE_COLORS: [u8; 8] = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0, 0, 0];
+
+    #[test]
+    fn bc1_four_color_mode() {
+        let pixels = bc1(&BC1_FOUR_COLORS);
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+        assert_eq!(pixels[1], [0, 0, 255, 255]);
+        assert_eq!(pixels[2], [170, 0, 85, 255]);
+        assert_eq!(pixels[3], [85, 0, 170, 255]);
+        assert_eq!(pixels[15], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc1_three_color_mode() {
+        let pixels = bc1(&BC1_THREE_COLORS);
+        assert_eq!(pixels[0], [0, 0, 255, 255]);
+        assert_eq!(pixels[1], [255, 0, 0, 255]);
+        assert_eq!(pixels[2], [127, 0, 127, 255]);
+        assert_eq!(pixels[3], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc1_transparent_pixels() {
+        assert!(!bc1_has_transparent_pixels(&BC1_FOUR_COLORS));
+        assert!(bc1_has_transparent_pixels(&BC1_THREE_COLORS));
+        // three color mode, but index 3 isn't used
+        assert!(!bc1_has_transparent_pixels(&[
+            0x1f, 0x00, 0x00, 0xf8, 0x24, 0, 0, 0
+        ]));
+    }
+
+    #[test]
+    fn bc2_explicit_alpha() {
+        let mut block = [0; 16];
+        block[0] = 0xf0;
+        block[1] = 0x08;
+        // bc2 colors are always in four color mode, even when color_0 <= color_1
+        block[8..16].copy_from_slice(&[0x1f, 0x00, 0x00, 0xf8, 0x03, 0, 0, 0]);
+        let pixels = bc2(&block);
+        assert_eq!(pixels[0], [170, 0, 85, 0]);
+        assert_eq!(pixels[1], [0, 0, 255, 255]);
+        assert_eq!(pixels[2], [0, 0, 255, 0x88]);
+        assert_eq!(pixels[3], [0, 0, 255, 0]);
+    }
+
+    #[test]
+    fn bc3_interpolated_alpha() {
+        let mut block = [0; 16];
+        block[0] = 255;
+        block[1] = 0;
+        block[2] = 0x11;
+        let pixels = bc3(&block);
+        assert_eq!(pixels[0], [0, 0, 0, 0]);
+        assert_eq!(pixels[1], [0, 0, 0, 218]);
+        assert_eq!(pixels[2], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc4_unorm_six_values() {
+        let pixels = bc4(&[0, 255, 0xbe, 0, 0, 0, 0, 0], false);
+        assert_eq!(pixels[0], [0, 0, 0, 255]);
+        assert_eq!(pixels[1], [255, 0, 0, 255]);
+        assert_eq!(pixels[2], [51, 0, 0, 255]);
+        assert_eq!(pixels[3], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc4_snorm_maps_minus_one_to_zero() {
+        // -128 and 127, six value mode: indices 0, 1 and 6 (-1)
+        let pixels = bc4(&[0x80, 0x7f, 0x88, 0x01, 0, 0, 0, 0], true);
+        assert_eq!(pixels[0][0], 0);
+        assert_eq!(pixels[1][0], 255);
+        assert_eq!(pixels[2][0], 0);
+        assert_eq!(pixels[3][0], 0);
+        // zero is the middle
+        assert_eq!(bc4(&[0, 0, 0, 0, 0, 0, 0, 0], true)[0][0], 127);
+    }
+
+    #[test]
+    fn bc5_snorm() {
+        // red 127 and -127 in eight value mode, green -127 and 127 in six value mode
+        let block = [
+            0x7f, 0x81, 0x01, 0, 0, 0, 0, 0, 0x81, 0x7f, 0, 0, 0, 0, 0, 0,
+        ];
+        let pixels = bc5(&block, true);
+        assert_eq!(pixels[0], [0, 0, 0, 255]);
+        assert_eq!(pixels[1], [255, 0, 0, 255]);
+    }
+
+    // least significant bit first, like `Bits` reads them
+    fn pack_bits(fields: &[(u32, usize)]) -> [u8; 16] {
+        let mut block = [0; 16];
+        let mut position = 0;
+        for &(value, count) in fields {
+            for i in 0..count {
+                if (value >> i) & 1 == 1 {
+                    block[position / 8] |= 1 << (position % 8);
+                }
+                position += 1;
+            }
+        }
+        assert_eq!(position, 128);
+        block
+    }
+
+    #[test]
+    fn bc7_mode_6() {
+        let mut fields = vec![(1 << 6, 7)];
+        // r0, r1, g0, g1, b0, b1, a0, a1
+        for &value in &[0, 127, 0, 127, 0, 127, 127, 127] {
+            fields.push((value, 7));
+        }
+        // p bits
+        fields.push((0, 1));
+        fields.push((1, 1));
+        // the anchor index has 3 bits
+        fields.push((0, 3));
+        fields.push((15, 4));
+        fields.push((8, 4));
+        for _ in 3..16 {
+            fields.push((0, 4));
+        }
+
+        let pixels = bc7(&pack_bits(&fields));
+        assert_eq!(pixels[0], [0, 0, 0, 254]);
+        assert_eq!(pixels[1], [255, 255, 255, 255]);
+        assert_eq!(pixels[2], [135, 135, 135, 255]);
+    }
+
+    #[test]
+    fn bc7_reserved_mode() {
+        assert_eq!(bc7(&[0; 16]), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn etc2_individual_mode() {
+        // red and blue halves, pixel (0, 0) uses selector 3
+        let block = [0xf0, 0x00, 0x0f, 0x00, 0x00, 0x01, 0x00, 0x01];
+        let pixels = etc2(&block, false);
+        assert_eq!(pixels[0], [247, 0, 0, 255]);
+        assert_eq!(pixels[1], [255, 2, 2, 255]);
+        assert_eq!(pixels[3], [2, 2, 255, 255]);
+    }
+
+    #[test]
+    fn etc2_differential_mode() {
+        // flipped, the bottom half has a blue delta of 3
+        let block = [0xf8, 0x00, 0x03, 0x03, 0x00, 0x00, 0x00, 0x00];
+        let pixels = etc2(&block, false);
+        assert_eq!(pixels[0], [255, 2, 2, 255]);
+        assert_eq!(pixels[12], [255, 2, 26, 255]);
+    }
+
+    #[test]
+    fn etc2_punch_through() {
+        // without the opaque bit selector 2 is transparent and selector 0 has no modifier
+        let block = [0xf8, 0x00, 0x03, 0x01, 0x00, 0x01, 0x00, 0x00];
+        let pixels = etc2(&block, true);
+        assert_eq!(pixels[0], [0, 0, 0, 0]);
+        assert_eq!(pixels[4], [255, 0, 0, 255]);
+        assert_eq!(pixels[12], [255, 0, 24, 255]);
+    }
+
+    #[test]
+    fn etc2_eac_alpha() {
+        // base 128, multiplier 1, table 0: indices 7 and 4 for the first column
+        let mut block = [0; 16];
+        block[0..8].copy_from_slice(&[128, 0x10, 0xf0, 0, 0, 0, 0, 0]);
+        let pixels = etc2_eac(&block);
+        assert_eq!(pixels[0], [2, 2, 2, 142]);
+        assert_eq!(pixels[4], [2, 2, 2, 130]);
+        assert_eq!(pixels[1], [2, 2, 2, 125]);
+    }
+
+    #[test]
+    fn eac_r11_unorm() {
+        let pixels = eac_r(&[128, 0x10, 0xf0, 0, 0, 0, 0, 0], false);
+        assert_eq!(pixels[0], [142, 0, 0, 255]);
+        assert_eq!(pixels[1], [125, 0, 0, 255]);
+    }
+
+    #[test]
+    fn eac_rg11_snorm() {
+        // red 127 with indices 7 and 3, green -127
+        let block = [
+            0x7f, 0x10, 0xec, 0, 0, 0, 0, 0, 0x81, 0x10, 0, 0, 0, 0, 0, 0,
+        ];
+        let pixels = eac_rg(&block, true);
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+        assert_eq!(pixels[4], [239, 0, 0, 255]);
+        assert_eq!(pixels[1], [251, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc6h_mode_11() {
+        // unsigned endpoints of 495 unquantize to half 1.0
+        let mut fields = vec![(0b00011, 5)];
+        // rw, gw, bw, rx, gx, bx
+        for &value in &[495, 0, 0, 0, 495, 0] {
+            fields.push((value, 10));
+        }
+        // the anchor index has 3 bits
+        fields.push((0, 3));
+        fields.push((15, 4));
+        for _ in 2..16 {
+            fields.push((0, 4));
+        }
+
+        let pixels = bc6h(&pack_bits(&fields), false);
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+        assert_eq!(pixels[1], [0, 255, 0, 255]);
+        assert_eq!(pixels[15], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc6h_reserved_mode() {
+        // mode 0b10011 has no endpoints, whatever the rest of the block is
+        let mut block = [0xff; 16];
+        block[0] = 0xf3;
+        assert_eq!(bc6h(&block, false), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn astc_void_extent() {
+        // a constant unorm16 color, without extent coordinates
+        let mut block = [
+            0xfc, 0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        block[8..16].copy_from_slice(&[0xff, 0xff, 0x80, 0x80, 0x00, 0x00, 0xff, 0xff]);
+        let mut data = block.to_vec();
+        data.extend_from_slice(&[block; 3].concat());
+
+        // 2x2 blocks of 6x5 pixels, the last column and row cut off
+        let rgba = decode(wgpu::TextureFormat::Astc6x5RgbaUnorm, (11, 9), &data).unwrap();
+        assert_eq!(rgba.len(), 11 * 9 * 4);
+        assert!(rgba
+            .chunks_exact(4)
+            .all(|pixel| pixel == [255, 128, 0, 255]));
+        assert!(decode(wgpu::TextureFormat::Astc6x5RgbaUnorm, (13, 9), &data).is_none());
+    }
+
+    #[test]
+    fn astc_luminance_block() {
+        // block mode 1: a 4x2 grid of 1 bit weights, one partition with cem 0 (direct
+        // luminance), endpoints 0 and 255; the weights are stored backwards from bit 127,
+        // the second row of the grid is 1
+        let mut block = [0; 16];
+        block[0] = 0x01;
+        block[3] = 0xfe;
+        block[4] = 0x01;
+        block[15] = 0x0f;
+        let mut pixels = [[0; 4]; 16];
+        astc(&block, (4, 4), &mut pixels);
+        // the grid's rows are infilled over the block's four
+        for (row, &value) in [0, 80, 175, 255].iter().enumerate() {
+            for pixel in &pixels[row * 4..row * 4 + 4] {
+                assert_eq!(*pixel, [value, value, value, 255]);
+            }
+        }
+    }
+
+    #[test]
+    fn astc_reserved_block_is_magenta() {
+        let mut pixels = [[0; 4]; 16];
+        astc(&[0; 16], (4, 4), &mut pixels);
+        assert_eq!(pixels, [[255, 0, 255, 255]; 16]);
+    }
+
+    #[test]
+    fn decode_crops_partial_blocks() {
+        let rgba = decode(wgpu::TextureFormat::Bc1RgbaUnorm, (2, 2), &BC1_FOUR_COLORS).unwrap();
+        assert_eq!(
+            rgba,
+            vec![255, 0, 0, 255, 0, 0, 255, 255, 255, 0, 0, 255, 255, 0, 0, 255]
+        );
+        assert!(decode(wgpu::TextureFormat::Bc2RgbaUnorm, (4, 4), &BC1_FOUR_COLORS).is_none());
+    }
+}
@@ -0,0 +1,350 @@
+// pre-compressed textures from .dds and .ktx2 files; devices without the needed
+// texture compression feature get them decoded to rgba8 on the cpu instead
+use crate::graphics::block_decode;
+
+#[derive(Debug)]
+pub enum CompressedTextureError {
+    // a format or layout this loader doesn't handle, like supercompression or 3d textures
+    Unsupported(String),
+    // the data is shorter than its header says
+    Truncated,
+    Io(std::io::Error),
+    Dds(ddsfile::Error),
+    Ktx2(ktx2::ParseError),
+}
+
+impl std::fmt::Display for CompressedTextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressedTextureError::Unsupported(what) => {
+                write!(f, "unsupported compressed texture: {}", what)
+            }
+            CompressedTextureError::Truncated => write!(f, "compressed texture data is truncated"),
+            CompressedTextureError::Io(e) => write!(f, "{}", e),
+            CompressedTextureError::Dds(e) => write!(f, "{}", e),
+            CompressedTextureError::Ktx2(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CompressedTextureError {}
+
+impl From<std::io::Error> for CompressedTextureError {
+    fn from(e: std::io::Error) -> Self {
+        CompressedTextureError::Io(e)
+    }
+}
+
+impl From<ddsfile::Error> for CompressedTextureError {
+    fn from(e: ddsfile::Error) -> Self {
+        CompressedTextureError::Dds(e)
+    }
+}
+
+impl From<ktx2::ParseError> for CompressedTextureError {
+    fn from(e: ktx2::ParseError) -> Self {
+        CompressedTextureError::Ktx2(e)
+    }
+}
+
+const KTX2_MAGIC: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+// the first image of the file with its mip chain, still in the file's format;
+// array layers and cube faces other than the first are dropped
+#[derive(Clone, Debug)]
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    pub size: (u32, u32),
+    // the full size first, each one holding whole blocks
+    pub levels: Vec<Vec<u8>>,
+    // bc1 without alpha, stored as bc1 with it; the pixels its punch-through mode would make
+    // transparent are opaque black
+    pub opaque: bool,
+}
+
+impl CompressedImage {
+    // tells the containers apart by their magic bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CompressedTextureError> {
+        if bytes.starts_with(b"DDS ") {
+            CompressedImage::from_dds(bytes)
+        } else if bytes.starts_with(&KTX2_MAGIC) {
+            CompressedImage::from_ktx2(bytes)
+        } else {
+            Err(CompressedTextureError::Unsupported(
+                "not a dds or ktx2 file".to_string(),
+            ))
+        }
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, CompressedTextureError> {
+        let dds = ddsfile::Dds::read(bytes)?;
+        let (format, swap_red_blue) = dds_format(&dds)?;
+        if dds.get_depth() > 1 {
+            return Err(CompressedTextureError::Unsupported(
+                "3d textures".to_string(),
+            ));
+        }
+
+        let size = (dds.get_width(), dds.get_height());
+        if size.0 == 0 || size.1 == 0 {
+            return Err(CompressedTextureError::Unsupported(
+                "empty textures".to_string(),
+            ));
+        }
+        let level_count = dds.get_num_mipmap_levels().max(1);
+        // the mips of the first layer are stored one after another
+        let mut data = &dds.data[..];
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let level_size = level_size(format, size, level);
+            if data.len() < level_size {
+                return Err(CompressedTextureError::Truncated);
+            }
+            let mut level_data = data[..level_size].to_vec();
+            if swap_red_blue {
+                for pixel in level_data.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            levels.push(level_data);
+            data = &data[level_size..];
+        }
+
+        Ok(CompressedImage {
+            format,
+            size,
+            levels,
+            opaque: false,
+        })
+    }
+
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, CompressedTextureError> {
+        let reader = ktx2::Reader::new(bytes)?;
+        let header = reader.header();
+        if let Some(scheme) = header.supercompression_scheme {
+            return Err(CompressedTextureError::Unsupported(format!(
+                "{:?} supercompression",
+                scheme
+            )));
+        }
+        if header.pixel_depth > 1 {
+            return Err(CompressedTextureError::Unsupported(
+                "3d textures".to_string(),
+            ));
+        }
+        let opaque = matches!(
+            header.format,
+            Some(ktx2::Format::BC1_RGB_UNORM_BLOCK | ktx2::Format::BC1_RGB_SRGB_BLOCK)
+        );
+        let format = match header.format {
+            Some(format) => ktx2_format(format)?,
+            None => {
+                return Err(CompressedTextureError::Unsupported(
+                    "formats only described by the data format descriptor".to_string(),
+                ))
+            }
+        };
+
+        let size = (header.pixel_width, header.pixel_height.max(1));
+        if size.0 == 0 {
+            return Err(CompressedTextureError::Unsupported(
+                "empty textures".to_string(),
+            ));
+        }
+        let mut levels = Vec::with_capacity(reader.levels().len());
+        for (level, data) in reader.levels().enumerate() {
+            // layers and faces follow the first image inside each level
+            let level_size = level_size(format, size, level as u32);
+            if data.len() < level_size {
+                return Err(CompressedTextureError::Truncated);
+            }
+            levels.push(data[..level_size].to_vec());
+        }
+        if levels.is_empty() {
+            return Err(CompressedTextureError::Truncated);
+        }
+
+        Ok(CompressedImage {
+            format,
+            size,
+            levels,
+            opaque,
+        })
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    // every level as tightly packed rgba8 pixels, for devices that can't sample the format;
+    // snorm channels are mapped to 0..1, and bc6h's hdr colors are clamped to it
+    pub fn decompress(&self) -> Result<Vec<Vec<u8>>, CompressedTextureError> {
+        if self.format.describe().block_dimensions == (1, 1) {
+            return Ok(self.levels.clone());
+        }
+
+        self.levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let mut rgba =
+                    block_decode::decode(self.format, level_extent(self.size, level as u32), data)
+                        .ok_or_else(|| {
+                            CompressedTextureError::Unsupported(format!(
+                                "no cpu decoder for {:?}",
+                                self.format
+                            ))
+                        })?;
+                if self.opaque {
+                    for pixel in rgba.chunks_exact_mut(4) {
+                        pixel[3] = 255;
+                    }
+                }
+                Ok(rgba)
+            })
+            .collect()
+    }
+
+    // the gpu would sample some of the pixels as transparent, when they should be opaque black
+    pub(crate) fn needs_opaque_decode(&self) -> bool {
+        self.opaque
+            && self
+                .levels
+                .iter()
+                .any(|level| block_decode::bc1_has_transparent_pixels(level))
+    }
+}
+
+fn level_extent(size: (u32, u32), level: u32) -> (u32, u32) {
+    ((size.0 >> level).max(1), (size.1 >> level).max(1))
+}
+
+// in bytes, partial blocks at the edges count as whole ones
+fn level_size(format: wgpu::TextureFormat, size: (u32, u32), level: u32) -> usize {
+    let info = format.describe();
+    let (width, height) = level_extent(size, level);
+    let (block_width, block_height) = (
+        info.block_dimensions.0 as u32,
+        info.block_dimensions.1 as u32,
+    );
+    let blocks_wide = (width + block_width - 1) / block_width;
+    let blocks_high = (height + block_height - 1) / block_height;
+    (blocks_wide * blocks_high) as usize * info.block_size as usize
+}
+
+// the format, and whether it's stored as bgra and needs its red and blue swapped
+fn dds_format(dds: &ddsfile::Dds) -> Result<(wgpu::TextureFormat, bool), CompressedTextureError> {
+    use ddsfile::{D3DFormat, DxgiFormat};
+    use wgpu::TextureFormat::*;
+
+    if let Some(format) = dds.get_dxgi_format() {
+        return match format {
+            DxgiFormat::BC1_UNorm => Ok((Bc1RgbaUnorm, false)),
+            DxgiFormat::BC1_UNorm_sRGB => Ok((Bc1RgbaUnormSrgb, false)),
+            DxgiFormat::BC2_UNorm => Ok((Bc2RgbaUnorm, false)),
+            DxgiFormat::BC2_UNorm_sRGB => Ok((Bc2RgbaUnormSrgb, false)),
+            DxgiFormat::BC3_UNorm => Ok((Bc3RgbaUnorm, false)),
+            DxgiFormat::BC3_UNorm_sRGB => Ok((Bc3RgbaUnormSrgb, false)),
+            DxgiFormat::BC4_UNorm => Ok((Bc4RUnorm, false)),
+            DxgiFormat::BC4_SNorm => Ok((Bc4RSnorm, false)),
+            DxgiFormat::BC5_UNorm => Ok((Bc5RgUnorm, false)),
+            DxgiFormat::BC5_SNorm => Ok((Bc5RgSnorm, false)),
+            DxgiFormat::BC6H_UF16 => Ok((Bc6hRgbUfloat, false)),
+            DxgiFormat::BC6H_SF16 => Ok((Bc6hRgbSfloat, false)),
+            DxgiFormat::BC7_UNorm => Ok((Bc7RgbaUnorm, false)),
+            DxgiFormat::BC7_UNorm_sRGB => Ok((Bc7RgbaUnormSrgb, false)),
+            DxgiFormat::R8G8B8A8_UNorm => Ok((Rgba8Unorm, false)),
+            DxgiFormat::R8G8B8A8_UNorm_sRGB => Ok((Rgba8UnormSrgb, false)),
+            DxgiFormat::B8G8R8A8_UNorm => Ok((Rgba8Unorm, true)),
+            DxgiFormat::B8G8R8A8_UNorm_sRGB => Ok((Rgba8UnormSrgb, true)),
+            format => Err(CompressedTextureError::Unsupported(format!(
+                "dds format {:?}",
+                format
+            ))),
+        };
+    }
+
+    // legacy headers don't say whether the data is srgb, color data usually is
+    match dds.get_d3d_format() {
+        Some(D3DFormat::DXT1) => Ok((Bc1RgbaUnormSrgb, false)),
+        Some(D3DFormat::DXT3) => Ok((Bc2RgbaUnormSrgb, false)),
+        Some(D3DFormat::DXT5) => Ok((Bc3RgbaUnormSrgb, false)),
+        Some(D3DFormat::A8B8G8R8) => Ok((Rgba8UnormSrgb, false)),
+        Some(D3DFormat::A8R8G8B8) => Ok((Rgba8UnormSrgb, true)),
+        format => Err(CompressedTextureError::Unsupported(format!(
+            "dds format {:?}",
+            format
+        ))),
+    }
+}
+
+fn ktx2_format(format: ktx2::Format) -> Result<wgpu::TextureFormat, CompressedTextureError> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat::*;
+
+    Ok(match format {
+        K::R8G8B8A8_UNORM => Rgba8Unorm,
+        K::R8G8B8A8_SRGB => Rgba8UnormSrgb,
+        // bc1 without alpha is the same data, CompressedImage::opaque tells them apart
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => Bc1RgbaUnorm,
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => Bc6hRgbSfloat,
+        K::BC7_UNORM_BLOCK => Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => Bc7RgbaUnormSrgb,
+        K::ETC2_R8G8B8_UNORM_BLOCK => Etc2RgbUnorm,
+        K::ETC2_R8G8B8_SRGB_BLOCK => Etc2RgbUnormSrgb,
+        K::ETC2_R8G8B8A1_UNORM_BLOCK => Etc2RgbA1Unorm,
+        K::ETC2_R8G8B8A1_SRGB_BLOCK => Etc2RgbA1UnormSrgb,
+        K::ETC2_R8G8B8A8_UNORM_BLOCK => Etc2RgbA8Unorm,
+        K::ETC2_R8G8B8A8_SRGB_BLOCK => Etc2RgbA8UnormSrgb,
+        K::EAC_R11_UNORM_BLOCK => EacRUnorm,
+        K::EAC_R11_SNORM_BLOCK => EacRSnorm,
+        K::EAC_R11G11_UNORM_BLOCK => EtcRgUnorm,
+        K::EAC_R11G11_SNORM_BLOCK => EtcRgSnorm,
+        K::ASTC_4x4_UNORM_BLOCK => Astc4x4RgbaUnorm,
+        K::ASTC_4x4_SRGB_BLOCK => Astc4x4RgbaUnormSrgb,
+        K::ASTC_5x4_UNORM_BLOCK => Astc5x4RgbaUnorm,
+        K::ASTC_5x4_SRGB_BLOCK => Astc5x4RgbaUnormSrgb,
+        K::ASTC_5x5_UNORM_BLOCK => Astc5x5RgbaUnorm,
+        K::ASTC_5x5_SRGB_BLOCK => Astc5x5RgbaUnormSrgb,
+        K::ASTC_6x5_UNORM_BLOCK => Astc6x5RgbaUnorm,
+        K::ASTC_6x5_SRGB_BLOCK => Astc6x5RgbaUnormSrgb,
+        K::ASTC_6x6_UNORM_BLOCK => Astc6x6RgbaUnorm,
+        K::ASTC_6x6_SRGB_BLOCK => Astc6x6RgbaUnormSrgb,
+        K::ASTC_8x5_UNORM_BLOCK => Astc8x5RgbaUnorm,
+        K::ASTC_8x5_SRGB_BLOCK => Astc8x5RgbaUnormSrgb,
+        K::ASTC_8x6_UNORM_BLOCK => Astc8x6RgbaUnorm,
+        K::ASTC_8x6_SRGB_BLOCK => Astc8x6RgbaUnormSrgb,
+        K::ASTC_8x8_UNORM_BLOCK => Astc8x8RgbaUnorm,
+        K::ASTC_8x8_SRGB_BLOCK => Astc8x8RgbaUnormSrgb,
+        K::ASTC_10x5_UNORM_BLOCK => Astc10x5RgbaUnorm,
+        K::ASTC_10x5_SRGB_BLOCK => Astc10x5RgbaUnormSrgb,
+        K::ASTC_10x6_UNORM_BLOCK => Astc10x6RgbaUnorm,
+        K::ASTC_10x6_SRGB_BLOCK => Astc10x6RgbaUnormSrgb,
+        K::ASTC_10x8_UNORM_BLOCK => Astc10x8RgbaUnorm,
+        K::ASTC_10x8_SRGB_BLOCK => Astc10x8RgbaUnormSrgb,
+        K::ASTC_10x10_UNORM_BLOCK => Astc10x10RgbaUnorm,
+        K::ASTC_10x10_SRGB_BLOCK => Astc10x10RgbaUnormSrgb,
+        K::ASTC_12x10_UNORM_BLOCK => Astc12x10RgbaUnorm,
+        K::ASTC_12x10_SRGB_BLOCK => Astc12x10RgbaUnormSrgb,
+        K::ASTC_12x12_UNORM_BLOCK => Astc12x12RgbaUnorm,
+        K::ASTC_12x12_SRGB_BLOCK => Astc12x12RgbaUnormSrgb,
+        format => {
+            return Err(CompressedTextureError::Unsupported(format!(
+                "ktx2 format {:?}",
+                format
+            )))
+        }
+    })
+}
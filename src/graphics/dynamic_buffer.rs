@@ -0,0 +1,51 @@
+// a gpu buffer that's rewritten every frame and grows when the data doesn't fit
+pub struct DynamicBuffer {
+    pub(super) buffer: wgpu::Buffer,
+    capacity: wgpu::BufferAddress,
+    usage: wgpu::BufferUsage,
+    label: &'static str,
+}
+
+impl DynamicBuffer {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsage,
+        capacity: wgpu::BufferAddress,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsage::COPY_DST;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: capacity,
+            usage,
+            mapped_at_creation: false,
+        });
+
+        DynamicBuffer {
+            buffer,
+            capacity,
+            usage,
+            label,
+        }
+    }
+
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[u8]) {
+        let size = data.len() as wgpu::BufferAddress;
+        if size > self.capacity {
+            self.capacity = size.next_power_of_two();
+            self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: self.capacity,
+                usage: self.usage,
+                mapped_at_creation: false,
+            });
+        }
+        if size > 0 {
+            queue.write_buffer(&self.buffer, 0, data);
+        }
+    }
+
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+}
@@ -1,13 +1,56 @@
+pub mod animation;
+pub mod atlas;
+pub mod bitmap_font;
+pub mod blend_mode;
+mod block_decode;
+pub mod compressed_texture;
 mod current_frame;
+mod dynamic_buffer;
+mod mipmaps;
+pub mod nine_slice;
+pub mod path;
+mod pipeline_cache;
+pub mod polyline;
+pub mod rect;
 mod render_pass;
 pub mod renderers;
+pub mod scroll_region;
+mod sdf_text;
 pub mod sf_view;
+pub mod sprite;
 pub mod state;
 pub mod state_new;
 mod state_other;
 mod state_render;
+mod state_texture;
+pub mod text;
+pub mod text_field;
+mod text_markup;
+#[cfg(feature = "shaping")]
+mod text_shaping;
+pub mod texture;
+mod texture_array;
+pub mod tilemap;
+pub mod tilemap_tiled;
+pub mod transformable;
+mod view_uniform;
 
+pub use animation::{AnimatedSprite, AnimationClip, PlayMode};
+pub use atlas::{Atlas, AtlasBuilder, AtlasOptions};
+pub use bitmap_font::BitmapFont;
+pub use blend_mode::BlendMode;
 pub use current_frame::CurrentFrame;
+pub use nine_slice::{Insets, NineSlice, SliceFill};
+pub use path::{FillRule, Path};
+pub use polyline::{LineCap, LineJoin, Polyline};
+pub use rect::Rect;
 pub use render_pass::RenderPass;
+pub use scroll_region::ScrollRegion;
 pub use sf_view::SfView;
+pub use sprite::Sprite;
 pub use state::GraphicsState;
+pub use text::{SdfEffects, Text, TextRendering, TextSpace};
+pub use text_field::TextField;
+pub use texture::{Texture, TextureOptions};
+pub use tilemap::{TileLayer, Tilemap, Tileset};
+pub use transformable::Transformable;
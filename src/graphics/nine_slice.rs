@@ -0,0 +1,178 @@
+// nine-patch sprites: the corners keep their size, the edges and the middle stretch or repeat
+// to fill `size`; drawn with RendererSprite::push_nine_slice
+use crate::graphics::sprite::quad_vertices;
+use crate::graphics::{Rect, Texture, Transformable};
+use crate::vertex::TexturedVertex;
+use std::rc::Rc;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SliceFill {
+    Stretch,
+    // whole copies of the source, the last one cut off
+    Tile,
+}
+
+impl Default for SliceFill {
+    fn default() -> Self {
+        SliceFill::Stretch
+    }
+}
+
+// in pixels of the texture, measured inwards from each side of texture_rect
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Insets {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Insets {
+    pub fn new(left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Insets {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn uniform(inset: f32) -> Self {
+        Insets::new(inset, inset, inset, inset)
+    }
+}
+
+#[derive(Clone)]
+pub struct NineSlice {
+    pub texture: Rc<Texture>,
+    // in pixels, the whole texture if None
+    pub texture_rect: Option<Rect>,
+    pub insets: Insets,
+    // the drawn size before the transform, corners shrink when it's smaller than the insets
+    pub size: (f32, f32),
+    pub edge_fill: SliceFill,
+    pub center_fill: SliceFill,
+    pub color: [f32; 4],
+    pub transformable: Transformable,
+}
+
+impl NineSlice {
+    pub fn new(texture: Rc<Texture>, insets: Insets, size: (f32, f32)) -> Self {
+        NineSlice {
+            texture,
+            texture_rect: None,
+            insets,
+            size,
+            edge_fill: SliceFill::Stretch,
+            center_fill: SliceFill::Stretch,
+            color: [1.0, 1.0, 1.0, 1.0],
+            transformable: Transformable::default(),
+        }
+    }
+
+    pub fn texture_rect(&self) -> Rect {
+        self.texture_rect.unwrap_or_else(|| {
+            let (width, height) = self.texture.size();
+            Rect::new(0.0, 0.0, width as f32, height as f32)
+        })
+    }
+
+    // before the transform
+    pub fn local_bounds(&self) -> Rect {
+        Rect::new(0.0, 0.0, self.size.0, self.size.1)
+    }
+
+    pub fn vertices(&self) -> Vec<TexturedVertex> {
+        let source = self.texture_rect();
+        let insets = self.insets;
+
+        // the three columns and rows, in the texture and on screen
+        let source_columns = [
+            (source.left, insets.left),
+            (
+                source.left + insets.left,
+                source.width - insets.left - insets.right,
+            ),
+            (source.right() - insets.right, insets.right),
+        ];
+        let source_rows = [
+            (source.top, insets.top),
+            (
+                source.top + insets.top,
+                source.height - insets.top - insets.bottom,
+            ),
+            (source.bottom() - insets.bottom, insets.bottom),
+        ];
+        let columns = split(self.size.0, insets.left, insets.right);
+        let rows = split(self.size.1, insets.top, insets.bottom);
+
+        let mut vertices = Vec::with_capacity(9 * 6);
+        for (row, &(y, height)) in rows.iter().enumerate() {
+            for (column, &(x, width)) in columns.iter().enumerate() {
+                if width <= 0.0 || height <= 0.0 {
+                    continue;
+                }
+                let (source_x, source_width) = source_columns[column];
+                let (source_y, source_height) = source_rows[row];
+                if source_width <= 0.0 || source_height <= 0.0 {
+                    continue;
+                }
+
+                let fill = match (column, row) {
+                    (1, 1) => self.center_fill,
+                    (1, _) | (_, 1) => self.edge_fill,
+                    _ => SliceFill::Stretch,
+                };
+                // corners only scale when there isn't room for them
+                let (tile_width, tile_height) = match fill {
+                    SliceFill::Stretch => (width, height),
+                    SliceFill::Tile => (
+                        if column == 1 { source_width } else { width },
+                        if row == 1 { source_height } else { height },
+                    ),
+                };
+
+                let mut tile_y = 0.0;
+                while tile_y < height {
+                    let part_height = tile_height.min(height - tile_y);
+                    let mut tile_x = 0.0;
+                    while tile_x < width {
+                        let part_width = tile_width.min(width - tile_x);
+                        // partial tiles show the matching part of the source, not a squashed copy
+                        let texture_rect = Rect::new(
+                            source_x,
+                            source_y,
+                            source_width * part_width / tile_width,
+                            source_height * part_height / tile_height,
+                        );
+                        vertices.extend_from_slice(&quad_vertices(
+                            &self.texture,
+                            &self.transformable,
+                            Rect::new(x + tile_x, y + tile_y, part_width, part_height),
+                            texture_rect,
+                            self.color,
+                        ));
+                        tile_x += tile_width;
+                    }
+                    tile_y += tile_height;
+                }
+            }
+        }
+        vertices
+    }
+}
+
+// start and length of the two borders and the middle along one axis
+fn split(size: f32, start_inset: f32, end_inset: f32) -> [(f32, f32); 3] {
+    let size = size.max(0.0);
+    let borders = start_inset + end_inset;
+    // not enough room: the borders shrink proportionally and the middle disappears
+    let scale = if borders > size && borders > 0.0 {
+        size / borders
+    } else {
+        1.0
+    };
+    let start = start_inset * scale;
+    let end = end_inset * scale;
+    [(0.0, start), (start, size - start - end), (size - end, end)]
+}
@@ -0,0 +1,390 @@
+// vector paths: flattened into polylines, then filled by slicing them into
+// horizontal trapezoids (which handles holes and self-intersections for both
+// fill rules) or stroked with `Polyline`
+
+use crate::graphics::Polyline;
+use crate::vertex::Vertex;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FillRule {
+    EvenOdd,
+    NonZero,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathCommand {
+    MoveTo((f32, f32)),
+    LineTo((f32, f32)),
+    QuadraticTo((f32, f32), (f32, f32)),
+    CubicTo((f32, f32), (f32, f32), (f32, f32)),
+    // angles in degrees, like SfView::rotation
+    Arc {
+        center: (f32, f32),
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+    },
+    Close,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubPath {
+    pub points: Vec<(f32, f32)>,
+    pub closed: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub commands: Vec<PathCommand>,
+    // max distance between the curves and their flattened polylines, in world units
+    pub tolerance: f32,
+}
+
+impl Default for Path {
+    fn default() -> Self {
+        Path {
+            commands: vec![],
+            tolerance: 0.25,
+        }
+    }
+}
+
+// building
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn move_to(mut self, x: f32, y: f32) -> Self {
+        self.commands.push(PathCommand::MoveTo((x, y)));
+        self
+    }
+
+    pub fn line_to(mut self, x: f32, y: f32) -> Self {
+        self.commands.push(PathCommand::LineTo((x, y)));
+        self
+    }
+
+    pub fn quadratic_to(mut self, cx: f32, cy: f32, x: f32, y: f32) -> Self {
+        self.commands
+            .push(PathCommand::QuadraticTo((cx, cy), (x, y)));
+        self
+    }
+
+    pub fn cubic_to(mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) -> Self {
+        self.commands
+            .push(PathCommand::CubicTo((c1x, c1y), (c2x, c2y), (x, y)));
+        self
+    }
+
+    // like canvas' arc(): connects to the current point with a line, if there is one
+    pub fn arc(
+        mut self,
+        cx: f32,
+        cy: f32,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+    ) -> Self {
+        self.commands.push(PathCommand::Arc {
+            center: (cx, cy),
+            radius,
+            start_angle,
+            sweep_angle,
+        });
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.commands.push(PathCommand::Close);
+        self
+    }
+
+    pub fn rect(self, x: f32, y: f32, width: f32, height: f32) -> Self {
+        self.move_to(x, y)
+            .line_to(x + width, y)
+            .line_to(x + width, y + height)
+            .line_to(x, y + height)
+            .close()
+    }
+
+    pub fn circle(self, cx: f32, cy: f32, radius: f32) -> Self {
+        self.move_to(cx + radius, cy)
+            .arc(cx, cy, radius, 0.0, 360.0)
+            .close()
+    }
+}
+
+// flattening
+impl Path {
+    pub fn flatten(&self) -> Vec<SubPath> {
+        let tolerance = self.tolerance.max(0.001);
+        let mut sub_paths = vec![];
+        let mut current = SubPath {
+            points: vec![],
+            closed: false,
+        };
+
+        fn finish(sub_paths: &mut Vec<SubPath>, current: &mut SubPath, closed: bool) {
+            let points = std::mem::take(&mut current.points);
+            if points.len() > 1 {
+                sub_paths.push(SubPath { points, closed });
+            }
+        }
+
+        for command in &self.commands {
+            match *command {
+                PathCommand::MoveTo(to) => {
+                    finish(&mut sub_paths, &mut current, false);
+                    current.points.push(to);
+                }
+                PathCommand::LineTo(to) => {
+                    current.points.push(to);
+                }
+                PathCommand::QuadraticTo(control, to) => {
+                    let from = current.points.last().copied().unwrap_or(control);
+                    let dd = length(sub(add(from, to), scale(control, 2.0)));
+                    let steps = segment_count((dd / (4.0 * tolerance)).sqrt());
+                    for i in 1..=steps {
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        current.points.push(add(
+                            add(scale(from, mt * mt), scale(control, 2.0 * mt * t)),
+                            scale(to, t * t),
+                        ));
+                    }
+                }
+                PathCommand::CubicTo(control_1, control_2, to) => {
+                    let from = current.points.last().copied().unwrap_or(control_1);
+                    let dd = length(sub(add(from, control_2), scale(control_1, 2.0)))
+                        .max(length(sub(add(control_1, to), scale(control_2, 2.0))));
+                    let steps = segment_count((6.0 * dd / (8.0 * tolerance)).sqrt());
+                    for i in 1..=steps {
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        current.points.push(add(
+                            add(
+                                scale(from, mt * mt * mt),
+                                scale(control_1, 3.0 * mt * mt * t),
+                            ),
+                            add(scale(control_2, 3.0 * mt * t * t), scale(to, t * t * t)),
+                        ));
+                    }
+                }
+                PathCommand::Arc {
+                    center,
+                    radius,
+                    start_angle,
+                    sweep_angle,
+                } => {
+                    let start = start_angle.to_radians();
+                    let sweep = sweep_angle.to_radians();
+                    let max_step = 2.0
+                        * (1.0 - tolerance / radius.abs().max(tolerance))
+                            .max(-1.0)
+                            .acos();
+                    let steps = segment_count(sweep.abs() / max_step.max(0.001));
+                    for i in 0..=steps {
+                        let angle = start + sweep * i as f32 / steps as f32;
+                        current.points.push((
+                            center.0 + radius * angle.cos(),
+                            center.1 + radius * angle.sin(),
+                        ));
+                    }
+                }
+                PathCommand::Close => {
+                    let start = current.points.first().copied();
+                    finish(&mut sub_paths, &mut current, true);
+                    // like svg, drawing continues from the start of the closed sub-path
+                    if let Some(start) = start {
+                        current.points.push(start);
+                    }
+                }
+            }
+        }
+        finish(&mut sub_paths, &mut current, false);
+
+        sub_paths
+    }
+}
+
+// tessellation
+impl Path {
+    // every sub-path is implicitly closed when filling
+    pub fn fill(&self, fill_rule: FillRule, color: [f32; 4]) -> Vec<Vertex> {
+        struct Edge {
+            top: (f32, f32),
+            bottom: (f32, f32),
+            winding: i32,
+        }
+
+        impl Edge {
+            fn x_at(&self, y: f32) -> f32 {
+                let t = (y - self.top.1) / (self.bottom.1 - self.top.1);
+                self.top.0 + (self.bottom.0 - self.top.0) * t
+            }
+        }
+
+        let mut edges = vec![];
+        for sub_path in self.flatten() {
+            // nan and infinite points can't be ordered, they're left out
+            let points: Vec<(f32, f32)> = sub_path
+                .points
+                .into_iter()
+                .filter(|point| point.0.is_finite() && point.1.is_finite())
+                .collect();
+            for i in 0..points.len() {
+                let a = points[i];
+                let b = points[(i + 1) % points.len()];
+                if a.1 < b.1 {
+                    edges.push(Edge {
+                        top: a,
+                        bottom: b,
+                        winding: 1,
+                    });
+                } else if a.1 > b.1 {
+                    edges.push(Edge {
+                        top: b,
+                        bottom: a,
+                        winding: -1,
+                    });
+                }
+            }
+        }
+
+        // from the top down, so only edges overlapping vertically are compared
+        edges.sort_by(|a, b| compare(a.top.1, b.top.1));
+
+        // slab boundaries: every end point and every crossing between edges
+        let mut ys = vec![];
+        for (i, edge) in edges.iter().enumerate() {
+            ys.push(edge.top.1);
+            ys.push(edge.bottom.1);
+            for other in edges[i + 1..]
+                .iter()
+                .take_while(|other| other.top.1 < edge.bottom.1)
+            {
+                if let Some(y) = crossing_y(edge.top, edge.bottom, other.top, other.bottom) {
+                    ys.push(y);
+                }
+            }
+        }
+        ys.retain(|y| y.is_finite());
+        ys.sort_by(|a, b| compare(*a, *b));
+        ys.dedup_by(|a, b| (*a - *b).abs() <= f32::EPSILON * a.abs().max(1.0));
+
+        let mut vertices = vec![];
+        let mut crossings = vec![];
+        // the edges spanning the current slab
+        let mut active: Vec<&Edge> = vec![];
+        let mut next_edge = 0;
+        for slab in ys.windows(2) {
+            let (y0, y1) = (slab[0], slab[1]);
+            let y_mid = (y0 + y1) * 0.5;
+
+            while next_edge < edges.len() && edges[next_edge].top.1 <= y_mid {
+                active.push(&edges[next_edge]);
+                next_edge += 1;
+            }
+            active.retain(|edge| edge.bottom.1 > y_mid);
+
+            crossings.clear();
+            crossings.extend(
+                active
+                    .iter()
+                    .map(|edge| (edge.x_at(y_mid), edge.x_at(y0), edge.x_at(y1), edge.winding)),
+            );
+            crossings.sort_by(|a, b| compare(a.0, b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                let (left, right) = (pair[0], pair[1]);
+                winding += left.3;
+                let inside = match fill_rule {
+                    FillRule::EvenOdd => winding % 2 != 0,
+                    FillRule::NonZero => winding != 0,
+                };
+                if !inside {
+                    continue;
+                }
+
+                for &(x, y) in &[
+                    (left.1, y0),
+                    (right.1, y0),
+                    (right.2, y1),
+                    (left.1, y0),
+                    (right.2, y1),
+                    (left.2, y1),
+                ] {
+                    vertices.push(Vertex {
+                        position: [x, y, 0.0],
+                        color,
+                    });
+                }
+            }
+        }
+
+        vertices
+    }
+
+    // `style` supplies everything but the points, which come from the path
+    pub fn stroke(&self, style: &Polyline) -> Vec<Vertex> {
+        let mut vertices = vec![];
+        for sub_path in self.flatten() {
+            Polyline {
+                points: sub_path.points,
+                closed: sub_path.closed,
+                ..style.clone()
+            }
+            .append_vertices(&mut vertices);
+        }
+        vertices
+    }
+}
+
+fn add(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(a: (f32, f32), s: f32) -> (f32, f32) {
+    (a.0 * s, a.1 * s)
+}
+
+fn length(a: (f32, f32)) -> f32 {
+    (a.0 * a.0 + a.1 * a.1).sqrt()
+}
+
+// for sorting, nan never reaches it but doesn't panic if it does
+fn compare(a: f32, b: f32) -> std::cmp::Ordering {
+    a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+}
+
+fn segment_count(n: f32) -> usize {
+    if n.is_finite() {
+        (n.ceil() as usize).clamp(1, 1024)
+    } else {
+        1
+    }
+}
+
+// y of the point where segments ab and cd cross, if they cross between their end points
+fn crossing_y(a: (f32, f32), b: (f32, f32), c: (f32, f32), d: (f32, f32)) -> Option<f32> {
+    let r = sub(b, a);
+    let s = sub(d, c);
+    let denominator = r.0 * s.1 - r.1 * s.0;
+    if denominator.abs() <= f32::EPSILON {
+        return None;
+    }
+    let ac = sub(c, a);
+    let t = (ac.0 * s.1 - ac.1 * s.0) / denominator;
+    let u = (ac.0 * r.1 - ac.1 * r.0) / denominator;
+    if t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0 {
+        Some(a.1 + r.1 * t)
+    } else {
+        None
+    }
+}
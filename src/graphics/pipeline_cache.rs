@@ -0,0 +1,94 @@
+use crate::graphics::BlendMode;
+use std::collections::HashMap;
+
+pub type PipelineKey = (BlendMode, wgpu::PrimitiveTopology);
+
+// one render pipeline per blend mode and topology, all sharing the same shaders and layout,
+// created the first time they're needed
+pub struct PipelineCache {
+    label: &'static str,
+    layout: wgpu::PipelineLayout,
+    vs_module: wgpu::ShaderModule,
+    fs_module: wgpu::ShaderModule,
+    vertex_buffers: Vec<wgpu::VertexBufferLayout<'static>>,
+    format: wgpu::TextureFormat,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+}
+
+impl PipelineCache {
+    pub fn new(
+        label: &'static str,
+        layout: wgpu::PipelineLayout,
+        vs_module: wgpu::ShaderModule,
+        fs_module: wgpu::ShaderModule,
+        vertex_buffers: Vec<wgpu::VertexBufferLayout<'static>>,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        PipelineCache {
+            label,
+            layout,
+            vs_module,
+            fs_module,
+            vertex_buffers,
+            format,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    // has to be called for every key before the render pass that uses get()
+    pub fn prepare(&mut self, device: &wgpu::Device, key: PipelineKey) {
+        if self.pipelines.contains_key(&key) {
+            return;
+        }
+
+        let (blend_mode, topology) = key;
+        log::debug!(
+            "creating {} pipeline for {:?}, {:?}",
+            self.label,
+            blend_mode,
+            topology
+        );
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(self.label),
+            layout: Some(&self.layout),
+            vertex: wgpu::VertexState {
+                module: &self.vs_module,
+                entry_point: "main",
+                buffers: &self.vertex_buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.fs_module,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: self.format,
+                    alpha_blend: blend_mode.alpha_blend(),
+                    color_blend: blend_mode.color_blend(),
+                    write_mask: wgpu::ColorWrite::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // tessellated shapes don't keep a consistent winding
+                cull_mode: wgpu::CullMode::None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        });
+
+        self.pipelines.insert(key, pipeline);
+    }
+
+    pub fn get(&self, key: PipelineKey) -> &wgpu::RenderPipeline {
+        self.pipelines
+            .get(&key)
+            .expect("PipelineCache::prepare wasn't called for this key")
+    }
+}
@@ -0,0 +1,419 @@
+// thick polylines, tessellated on the cpu into a triangle list
+// joins and caps work like svg's stroke-linejoin and stroke-linecap
+// the triangles don't overlap, so translucent strokes blend once everywhere; only corners too sharp
+// for their segments' lengths have the segments overlapping on the inside of the corner
+
+use crate::vertex::Vertex;
+use cgmath::{vec2, InnerSpace, Vector2};
+
+type V2 = Vector2<f32>;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineJoin {
+    Miter,
+    Bevel,
+    Round,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineCap {
+    Butt,
+    Square,
+    Round,
+}
+
+#[derive(Clone, Debug)]
+pub struct Polyline {
+    pub points: Vec<(f32, f32)>,
+    pub width: f32,
+    pub color: [f32; 4],
+    pub join: LineJoin,
+    pub cap: LineCap,
+    // miters longer than miter_limit * width / 2 fall back to bevels
+    pub miter_limit: f32,
+    pub closed: bool,
+    // width of the anti-aliased fringe, centered on the edges; 0 turns it off
+    pub feather: f32,
+}
+
+impl Default for Polyline {
+    fn default() -> Self {
+        Polyline {
+            points: vec![],
+            width: 1.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+            closed: false,
+            feather: 1.0,
+        }
+    }
+}
+
+impl Polyline {
+    pub fn vertices(&self) -> Vec<Vertex> {
+        let mut vertices = vec![];
+        self.append_vertices(&mut vertices);
+        vertices
+    }
+
+    pub fn append_vertices(&self, vertices: &mut Vec<Vertex>) {
+        // repeated points have no direction, so they're dropped
+        let mut points: Vec<V2> = Vec::with_capacity(self.points.len());
+        for &(x, y) in &self.points {
+            let point = vec2(x, y);
+            match points.last() {
+                Some(&last) if (point - last).magnitude2() <= f32::EPSILON => {}
+                _ => points.push(point),
+            }
+        }
+        if self.closed
+            && points.len() > 2
+            && (points[0] - points[points.len() - 1]).magnitude2() <= f32::EPSILON
+        {
+            points.pop();
+        }
+        if points.len() < 2 || self.width <= 0.0 {
+            return;
+        }
+
+        let feather = self.feather.max(0.0);
+        let half_width = self.width * 0.5;
+        let mut tessellator = Tessellator {
+            vertices,
+            solid: self.color,
+            clear: [self.color[0], self.color[1], self.color[2], 0.0],
+            inner: (half_width - feather * 0.5).max(0.0),
+            outer: half_width + feather * 0.5,
+        };
+
+        let closed = self.closed && points.len() > 2;
+        let n_segments = if closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+
+        let corners: Vec<Option<Corner>> = (0..points.len())
+            .map(|i| {
+                if !closed && (i == 0 || i == points.len() - 1) {
+                    return None;
+                }
+                let previous = points[(i + points.len() - 1) % points.len()];
+                let next = points[(i + 1) % points.len()];
+                Corner::new(
+                    previous,
+                    points[i],
+                    next,
+                    tessellator.inner,
+                    tessellator.outer,
+                )
+            })
+            .collect();
+
+        for i in 0..n_segments {
+            let a = points[i];
+            let b = points[(i + 1) % points.len()];
+            let direction = (b - a).normalize();
+            let normal = vec2(-direction.y, direction.x);
+
+            let mut start = a;
+            let mut end = b;
+            if !closed {
+                // butt and square caps move the end of the solid part,
+                // round caps are added as separate fans
+                let extension = match self.cap {
+                    LineCap::Butt => Some(-feather * 0.5),
+                    LineCap::Square => Some(half_width - feather * 0.5),
+                    LineCap::Round => None,
+                };
+                if let Some(extension) = extension {
+                    if i == 0 {
+                        start = a - direction * extension;
+                        tessellator.cap_fringe(start, -direction, normal, feather);
+                    }
+                    if i == n_segments - 1 {
+                        end = b + direction * extension;
+                        tessellator.cap_fringe(end, direction, -normal, feather);
+                    }
+                }
+            }
+
+            let start = tessellator.segment_end(start, normal, &corners[i]);
+            let end = tessellator.segment_end(end, normal, &corners[(i + 1) % points.len()]);
+            tessellator.segment(start, end);
+        }
+
+        for corner in corners.iter().flatten() {
+            tessellator.join(self, corner);
+        }
+        if !closed && self.cap == LineCap::Round {
+            let first = points[0];
+            let direction = (points[1] - first).normalize();
+            tessellator.arc(
+                first,
+                first,
+                vec2(-direction.y, direction.x),
+                std::f32::consts::PI,
+            );
+
+            let last = points[points.len() - 1];
+            let direction = (last - points[points.len() - 2]).normalize();
+            tessellator.arc(
+                last,
+                last,
+                vec2(direction.y, -direction.x),
+                std::f32::consts::PI,
+            );
+        }
+    }
+}
+
+// a bend in the line, between the segment coming in and the one going out
+struct Corner {
+    current: V2,
+    // unit vectors to the outer side of the corner, perpendicular to the incoming and the
+    // outgoing segment
+    o1: V2,
+    o2: V2,
+    // 1 when the outer side is on the left of the segments, along their normals, otherwise -1
+    side: f32,
+    u_turn: bool,
+    // where the solid edges and the fringe edges on the inner side meet, when that's within
+    // both segments; the segments end there instead of overlapping past it
+    inner_trim: Option<(V2, V2)>,
+}
+
+impl Corner {
+    fn new(previous: V2, current: V2, next: V2, inner: f32, outer: f32) -> Option<Self> {
+        let d1 = (current - previous).normalize();
+        let d2 = (next - current).normalize();
+        let cross = d1.x * d2.y - d1.y * d2.x;
+        if cross.abs() < 1e-6 && d1.dot(d2) > 0.0 {
+            return None; // straight, the segments already meet
+        }
+
+        // normals are on the left of the direction, so a left turn has its outer side on the right
+        let side = if cross > 0.0 { -1.0 } else { 1.0 };
+        let o1 = vec2(-d1.y, d1.x) * side;
+        let o2 = vec2(-d2.y, d2.x) * side;
+        let u_turn = o1.dot(o2) < -0.9999;
+
+        let inner_trim = if u_turn {
+            None
+        } else {
+            let miter = (o1 + o2).normalize();
+            let length = 1.0 / miter.dot(o1);
+            // how far back along the segments the fringe edges meet, each segment's other end
+            // may need half of it for its own corner
+            let trim = outer * (length * length - 1.0).max(0.0).sqrt();
+            let max_trim = (current - previous)
+                .magnitude()
+                .min((next - current).magnitude())
+                * 0.5;
+            if trim <= max_trim {
+                Some((
+                    current - miter * inner * length,
+                    current - miter * outer * length,
+                ))
+            } else {
+                None
+            }
+        };
+
+        Some(Corner {
+            current,
+            o1,
+            o2,
+            side,
+            u_turn,
+            inner_trim,
+        })
+    }
+
+    // the point the join's triangles fan out from, the solid part of both segments ends on it
+    fn hub(&self) -> V2 {
+        self.inner_trim.map_or(self.current, |(inner, _)| inner)
+    }
+}
+
+// the solid and the fringe edge of a segment's end, on the left and the right side
+struct SegmentEnd {
+    left: (V2, V2),
+    right: (V2, V2),
+}
+
+struct Tessellator<'a> {
+    vertices: &'a mut Vec<Vertex>,
+    solid: [f32; 4],
+    clear: [f32; 4],
+    // half widths of the solid part and of the solid part plus the fringe
+    inner: f32,
+    outer: f32,
+}
+
+impl Tessellator<'_> {
+    fn vertex(&mut self, position: V2, color: [f32; 4]) {
+        self.vertices.push(Vertex {
+            position: [position.x, position.y, 0.0],
+            color,
+        });
+    }
+
+    fn solid_triangle(&mut self, a: V2, b: V2, c: V2) {
+        self.vertex(a, self.solid);
+        self.vertex(b, self.solid);
+        self.vertex(c, self.solid);
+    }
+
+    // inner_* are opaque, outer_* are fully transparent
+    fn fringe_quad(&mut self, inner_a: V2, outer_a: V2, outer_b: V2, inner_b: V2) {
+        if self.outer <= self.inner {
+            return;
+        }
+        self.vertex(inner_a, self.solid);
+        self.vertex(outer_a, self.clear);
+        self.vertex(outer_b, self.clear);
+        self.vertex(inner_a, self.solid);
+        self.vertex(outer_b, self.clear);
+        self.vertex(inner_b, self.solid);
+    }
+
+    // square across the segment, except on the inner side of a trimmed corner
+    fn segment_end(&self, end: V2, normal: V2, corner: &Option<Corner>) -> SegmentEnd {
+        let (inner, outer) = (self.inner, self.outer);
+        let mut segment_end = SegmentEnd {
+            left: (end + normal * inner, end + normal * outer),
+            right: (end - normal * inner, end - normal * outer),
+        };
+        if let Some(Corner {
+            side,
+            inner_trim: Some(inner_trim),
+            ..
+        }) = corner
+        {
+            if *side > 0.0 {
+                segment_end.right = *inner_trim;
+            } else {
+                segment_end.left = *inner_trim;
+            }
+        }
+        segment_end
+    }
+
+    fn segment(&mut self, start: SegmentEnd, end: SegmentEnd) {
+        self.solid_triangle(start.left.0, start.right.0, end.right.0);
+        self.solid_triangle(start.left.0, end.right.0, end.left.0);
+        self.fringe_quad(start.left.0, start.left.1, end.left.1, end.left.0);
+        self.fringe_quad(start.right.0, start.right.1, end.right.1, end.right.0);
+    }
+
+    // fringe across the flat end of a butt or square cap, `outward` points away from the line
+    fn cap_fringe(&mut self, end: V2, outward: V2, normal: V2, feather: f32) {
+        if self.outer <= self.inner {
+            return;
+        }
+        let (inner, outer) = (self.inner, self.outer);
+        let corner_a = end + outward * feather + normal * outer;
+        let corner_b = end + outward * feather - normal * outer;
+        self.fringe_quad(
+            end + normal * inner,
+            corner_a,
+            corner_b,
+            end - normal * inner,
+        );
+
+        // the corners between the end fringe and the side fringes
+        self.vertex(end + normal * inner, self.solid);
+        self.vertex(end + normal * outer, self.clear);
+        self.vertex(corner_a, self.clear);
+        self.vertex(end - normal * inner, self.solid);
+        self.vertex(end - normal * outer, self.clear);
+        self.vertex(corner_b, self.clear);
+    }
+
+    // fan from `hub` along the circle around `center`, starting at the unit vector `from` and
+    // sweeping `angle` radians
+    fn arc(&mut self, hub: V2, center: V2, from: V2, angle: f32) {
+        let (inner, outer) = (self.inner, self.outer);
+
+        // keep the chord within ~0.1 units of the real circle
+        let max_step = 2.0 * (1.0 - 0.1 / outer.max(0.1)).max(-1.0).acos();
+        let steps = ((angle.abs() / max_step.max(0.01)).ceil() as usize).clamp(1, 128);
+        let step = angle / steps as f32;
+
+        let mut previous = from;
+        for i in 1..=steps {
+            let (sine, cosine) = (step * i as f32).sin_cos();
+            let next = vec2(
+                from.x * cosine - from.y * sine,
+                from.x * sine + from.y * cosine,
+            );
+            self.solid_triangle(hub, center + previous * inner, center + next * inner);
+            self.fringe_quad(
+                center + previous * inner,
+                center + previous * outer,
+                center + next * outer,
+                center + next * inner,
+            );
+            previous = next;
+        }
+    }
+
+    // fills the gap between the segments' ends, up to the outer side of the corner
+    fn join(&mut self, polyline: &Polyline, corner: &Corner) {
+        let Corner {
+            current,
+            o1,
+            o2,
+            side,
+            u_turn,
+            ..
+        } = *corner;
+        let hub = corner.hub();
+
+        let (inner, outer) = (self.inner, self.outer);
+        match polyline.join {
+            LineJoin::Round => {
+                let angle = if u_turn {
+                    -side * std::f32::consts::PI
+                } else {
+                    (o1.x * o2.y - o1.y * o2.x).atan2(o1.dot(o2))
+                };
+                self.arc(hub, current, o1, angle);
+            }
+            LineJoin::Miter
+                if !u_turn && 1.0 / (o1 + o2).normalize().dot(o1) <= polyline.miter_limit =>
+            {
+                let miter = (o1 + o2).normalize();
+                let length = 1.0 / miter.dot(o1);
+                let tip_inner = current + miter * inner * length;
+                let tip_outer = current + miter * outer * length;
+                self.solid_triangle(hub, current + o1 * inner, tip_inner);
+                self.solid_triangle(hub, tip_inner, current + o2 * inner);
+                self.fringe_quad(
+                    current + o1 * inner,
+                    current + o1 * outer,
+                    tip_outer,
+                    tip_inner,
+                );
+                self.fringe_quad(
+                    tip_inner,
+                    tip_outer,
+                    current + o2 * outer,
+                    current + o2 * inner,
+                );
+            }
+            LineJoin::Miter | LineJoin::Bevel => {
+                self.solid_triangle(hub, current + o1 * inner, current + o2 * inner);
+                self.fringe_quad(
+                    current + o1 * inner,
+                    current + o1 * outer,
+                    current + o2 * outer,
+                    current + o2 * inner,
+                );
+            }
+        }
+    }
+}
@@ -1,9 +1,13 @@
 pub mod renderer_glyph;
 pub mod renderer_imgui;
 pub mod renderer_simple_triangle;
+pub mod renderer_sprite;
+pub mod renderer_tilemap;
 pub mod renderer_with_view;
 
 pub use renderer_glyph::RendererGlyph;
 pub use renderer_imgui::RendererImgui;
 pub use renderer_simple_triangle::RendererSimpleTriangle;
+pub use renderer_sprite::RendererSprite;
+pub use renderer_tilemap::RendererTilemap;
 pub use renderer_with_view::RendererWithView;
@@ -1,66 +1,430 @@
-use crate::graphics::{CurrentFrame, GraphicsState};
-use rand::Rng;
+use crate::graphics::sdf_text::SdfTextRenderer;
+use crate::graphics::text::{scissor_rect, FontVariants, TextLayout, TextMetrics, TextRendering};
+#[cfg(feature = "shaping")]
+use crate::graphics::text_shaping::Shaper;
+use crate::graphics::{
+    BlendMode, CurrentFrame, GraphicsState, Rect, SdfEffects, SfView, Text, TextSpace,
+    Transformable,
+};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+#[cfg(feature = "shaping")]
+use std::sync::Arc;
+use wgpu_glyph::ab_glyph::{FontArc, InvalidFont};
+use wgpu_glyph::{FontId, GlyphBrush, GlyphBrushBuilder, Region, Section};
 
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    InvalidFont(InvalidFont),
+    UnknownFont(FontId),
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Io(e) => write!(f, "{}", e),
+            FontError::InvalidFont(e) => write!(f, "{}", e),
+            FontError::UnknownFont(id) => write!(f, "font {:?} hasn't been added", id),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+impl From<std::io::Error> for FontError {
+    fn from(e: std::io::Error) -> Self {
+        FontError::Io(e)
+    }
+}
+
+impl From<InvalidFont> for FontError {
+    fn from(e: InvalidFont) -> Self {
+        FontError::InvalidFont(e)
+    }
+}
+
+// text through long-lived glyph brushes, so the glyph cache textures survive between frames;
+// fonts added at runtime are kept in the brushes
 pub struct RendererGlyph {
-    //
+    glyph_brush: GlyphBrush<()>,
+    // world space text has its own brush, drawn with the view's transform before the screen space one
+    world_glyph_brush: GlyphBrush<()>,
+    // the brushes' pipelines are built for it
+    format: wgpu::TextureFormat,
+    // by regular font
+    font_variants: HashMap<FontId, FontVariants>,
+    fallback_fonts: Vec<FontId>,
+    // the fonts' data, for texts that are shaped
+    #[cfg(feature = "shaping")]
+    shaper: Shaper,
+    // laid out in draw, once the view's zoom is known
+    queued_world: Vec<Text>,
+    // texts with a clip rect or a transform, drawn after the others in their space, a draw
+    // per clip rect and transform
+    queued_apart: Vec<Text>,
+    // TextRendering::Sdf texts, drawn between the world and the screen brushes
+    sdf: SdfTextRenderer,
+    // drawn every frame until released, in the order they were retained
+    retained: BTreeMap<u64, Text>,
+    next_retained: u64,
 }
 
+// a text kept by RendererGlyph::retain
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextHandle(u64);
+
+// new
 impl RendererGlyph {
-    pub fn new(_graphics_state: &GraphicsState) -> Self {
-        RendererGlyph {}
+    // Inconsolata is always there, as FontId(0) / RendererGlyph::DEFAULT_FONT
+    pub const DEFAULT_FONT: FontId = FontId(0);
+
+    pub fn new(graphics_state: &mut GraphicsState) -> Self {
+        let inconsolata_data = include_bytes!("../../Inconsolata-Regular.ttf");
+        let inconsolata = FontArc::try_from_slice(inconsolata_data).unwrap();
+        let format = graphics_state.swap_chain_descriptor.format;
+        #[cfg(feature = "shaping")]
+        let mut shaper = Shaper::default();
+        #[cfg(feature = "shaping")]
+        shaper.push_font(Some(Arc::from(&inconsolata_data[..])));
+
+        RendererGlyph {
+            glyph_brush: GlyphBrushBuilder::using_font(inconsolata.clone())
+                .build(&graphics_state.device, format),
+            world_glyph_brush: GlyphBrushBuilder::using_font(inconsolata)
+                .build(&graphics_state.device, format),
+            format,
+            font_variants: HashMap::new(),
+            fallback_fonts: Vec::new(),
+            #[cfg(feature = "shaping")]
+            shaper,
+            queued_world: Vec::new(),
+            queued_apart: Vec::new(),
+            sdf: SdfTextRenderer::new(graphics_state),
+            retained: BTreeMap::new(),
+            next_retained: 0,
+        }
     }
+}
 
-    pub fn draw(&mut self, current_frame: &mut CurrentFrame) {
-        let window_inner_size = current_frame.graphics_state.window_inner_size();
+// fonts
+impl RendererGlyph {
+    // shaped texts place its glyphs without shaping, it has no data for rustybuzz
+    pub fn add_font(&mut self, font: FontArc) -> FontId {
+        #[cfg(feature = "shaping")]
+        self.shaper.push_font(None);
+        self.push_font(font)
+    }
 
-        let inconsolata = wgpu_glyph::ab_glyph::FontArc::try_from_slice(include_bytes!(
-            "../../Inconsolata-Regular.ttf"
-        ))
-        .unwrap();
+    // .ttf or .otf
+    pub fn add_font_from_bytes(&mut self, bytes: Vec<u8>) -> Result<FontId, FontError> {
+        #[cfg(feature = "shaping")]
+        let data: Arc<[u8]> = Arc::from(bytes.as_slice());
+        let font = FontArc::try_from_vec(bytes)?;
+        #[cfg(feature = "shaping")]
+        self.shaper.push_font(Some(data));
+        Ok(self.push_font(font))
+    }
 
-        let mut glyph_brush = wgpu_glyph::GlyphBrushBuilder::using_font(inconsolata).build(
-            &current_frame.graphics_state.device,
-            current_frame.graphics_state.swap_chain_descriptor.format,
-        );
+    fn push_font(&mut self, font: FontArc) -> FontId {
+        self.world_glyph_brush.add_font(font.clone());
+        self.glyph_brush.add_font(font)
+    }
 
-        glyph_brush.queue(wgpu_glyph::Section {
-            screen_position: (30.0, 30.0),
-            bounds: (
-                window_inner_size.width as f32,
-                window_inner_size.height as f32,
-            ),
-            text: vec![wgpu_glyph::Text::new("Hello wgpu_glyph!")
-                .with_color([0.0, 1.0, 0.0, 1.0])
-                .with_scale(40.0)],
-            ..wgpu_glyph::Section::default()
-        });
-
-        glyph_brush.queue(wgpu_glyph::Section {
-            screen_position: (30.0, 90.0),
-            bounds: (
-                window_inner_size.width as f32,
-                window_inner_size.height as f32,
+    pub fn add_font_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<FontId, FontError> {
+        self.add_font_from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn fonts(&self) -> &[FontArc] {
+        self.glyph_brush.fonts()
+    }
+
+    pub fn font(&self, font_id: FontId) -> Option<&FontArc> {
+        self.fonts().get(font_id.0)
+    }
+
+    // used by bold and italic TextStyles of texts in the `regular` font
+    pub fn set_font_variants(&mut self, regular: FontId, variants: FontVariants) {
+        self.font_variants.insert(regular, variants);
+    }
+
+    pub fn font_variants(&self, regular: FontId) -> FontVariants {
+        self.font_variants
+            .get(&regular)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // tried in order for the characters a text's fonts don't have, e.g. the glyphs of
+    // other scripts; kept as they were when one of them isn't a font of this renderer
+    pub fn set_fallback_fonts(&mut self, fallbacks: Vec<FontId>) -> Result<(), FontError> {
+        let font_count = self.fonts().len();
+        if let Some(unknown) = fallbacks.iter().find(|fallback| fallback.0 >= font_count) {
+            return Err(FontError::UnknownFont(*unknown));
+        }
+        self.fallback_fonts = fallbacks;
+        Ok(())
+    }
+
+    pub fn fallback_fonts(&self) -> &[FontId] {
+        &self.fallback_fonts
+    }
+}
+
+// layout
+impl RendererGlyph {
+    // how `text` is laid out when it's drawn, in the units of its space
+    pub fn metrics(&self, text: &Text) -> TextMetrics {
+        text.metrics(
+            self.fonts(),
+            self.font_variants(text.font),
+            &self.layout(text),
+        )
+    }
+
+    // with the fallback fonts, and shaped if the text asks for it
+    fn layout(&self, text: &Text) -> TextLayout {
+        let layout = text.layout().with_fallbacks(&self.fallback_fonts);
+        #[cfg(feature = "shaping")]
+        if text.shaping {
+            return layout.with_shaper(self.shaper.clone());
+        }
+        layout
+    }
+}
+
+// queue
+impl RendererGlyph {
+    // in screen space, drawn, then forgotten, by the next draw
+    pub fn queue<'a, S>(&mut self, section: S)
+    where
+        S: Into<Cow<'a, Section<'a>>>,
+    {
+        self.glyph_brush.queue(section);
+    }
+
+    // drawn by the next draw only
+    pub fn queue_text(&mut self, text: &Text) {
+        let variants = self.font_variants(text.font);
+        let layout = self.layout(text);
+        match (text.rendering, text.space) {
+            (TextRendering::Sdf(effects), _) => {
+                self.sdf
+                    .queue(text, &effects, self.glyph_brush.fonts(), variants, &layout)
+            }
+            (TextRendering::Raster, _) if text.blend_mode != BlendMode::Alpha => self.sdf.queue(
+                text,
+                &SdfEffects::default(),
+                self.glyph_brush.fonts(),
+                variants,
+                &layout,
             ),
-            text: vec![wgpu_glyph::Text::new(&*format!(
-                "Hello wgpu_glyph! Random number: {}",
-                rand::thread_rng().gen_range(0..100)
-            ))
-            .with_color([1.0, 1.0, 1.0, 1.0])
-            .with_scale(40.0)],
-            ..wgpu_glyph::Section::default()
-        });
-
-        glyph_brush
+            (TextRendering::Raster, _) if drawn_apart(text) => self.queued_apart.push(text.clone()),
+            (TextRendering::Raster, TextSpace::Screen) => self
+                .glyph_brush
+                .queue_custom_layout(text.section(1.0, variants), &layout),
+            (TextRendering::Raster, TextSpace::World) => self.queued_world.push(text.clone()),
+        }
+    }
+
+    // a solid rectangle drawn by the next draw only, with the Sdf texts: over world space raster
+    // text, and under screen space raster text
+    pub fn queue_rect(
+        &mut self,
+        rect: Rect,
+        color: [f32; 4],
+        space: TextSpace,
+        clip: Option<Rect>,
+    ) {
+        self.sdf
+            .queue_rect(rect, color, space, clip, BlendMode::Alpha);
+    }
+
+    // drawn by every draw until it's released
+    pub fn retain(&mut self, text: Text) -> TextHandle {
+        let handle = TextHandle(self.next_retained);
+        self.next_retained += 1;
+        self.retained.insert(handle.0, text);
+        handle
+    }
+
+    pub fn retained(&self, handle: TextHandle) -> Option<&Text> {
+        self.retained.get(&handle.0)
+    }
+
+    pub fn retained_mut(&mut self, handle: TextHandle) -> Option<&mut Text> {
+        self.retained.get_mut(&handle.0)
+    }
+
+    pub fn release(&mut self, handle: TextHandle) -> Option<Text> {
+        self.retained.remove(&handle.0)
+    }
+}
+
+// draw
+impl RendererGlyph {
+    pub fn draw(&mut self, current_frame: &mut CurrentFrame, view: &SfView) {
+        let graphics_state = &mut *current_frame.graphics_state;
+
+        // the pipelines target one format, rebuild with the same fonts when it changes;
+        // sections queued before that are lost for this frame
+        if graphics_state.swap_chain_descriptor.format != self.format {
+            self.format = graphics_state.swap_chain_descriptor.format;
+            log::debug!("rebuilding the glyph brushes for {:?}", self.format);
+            self.glyph_brush = GlyphBrushBuilder::using_fonts(self.fonts().to_vec())
+                .build(&graphics_state.device, self.format);
+            self.world_glyph_brush = GlyphBrushBuilder::using_fonts(self.fonts().to_vec())
+                .build(&graphics_state.device, self.format);
+        }
+
+        let window_inner_size = graphics_state.window_inner_size();
+
+        // world text is rasterized at its size on screen, and scaled back to world units by the
+        // transform, so it stays sharp when zoomed in
+        let scale = (window_inner_size.width as f32 / view.size.0)
+            .max(window_inner_size.height as f32 / view.size.1);
+        let scale = if scale.is_normal() { scale } else { 1.0 };
+        let mut world_text_count = self.queued_world.len();
+        for text in std::mem::take(&mut self.queued_world) {
+            self.world_glyph_brush.queue_custom_layout(
+                text.section(scale, self.font_variants(text.font)),
+                &self.layout(&text),
+            );
+        }
+        let mut apart = std::mem::take(&mut self.queued_apart);
+        for text in self.retained.values() {
+            let variants = self.font_variants(text.font);
+            let layout = self.layout(text);
+            match (text.rendering, text.space) {
+                (TextRendering::Sdf(effects), _) => {
+                    self.sdf
+                        .queue(text, &effects, self.glyph_brush.fonts(), variants, &layout)
+                }
+                (TextRendering::Raster, _) if drawn_apart(text) => apart.push(text.clone()),
+                (TextRendering::Raster, TextSpace::Screen) => self
+                    .glyph_brush
+                    .queue_custom_layout(text.section(1.0, variants), &layout),
+                (TextRendering::Raster, TextSpace::World) => {
+                    world_text_count += 1;
+                    self.world_glyph_brush
+                        .queue_custom_layout(text.section(scale, variants), &layout);
+                }
+            }
+        }
+        let (apart_world, apart_screen): (Vec<Text>, Vec<Text>) = apart
+            .into_iter()
+            .partition(|text| text.space == TextSpace::World);
+
+        let view_transform = view.get_matrix4() * crate::graphics::sf_view::OPENGL_TO_WGPU_MATRIX4;
+        let transform = view_transform * cgmath::Matrix4::from_scale(1.0 / scale);
+        if world_text_count > 0 {
+            self.world_glyph_brush
+                .draw_queued_with_transform(
+                    &graphics_state.device,
+                    &mut graphics_state.staging_belt,
+                    &mut current_frame.encoder,
+                    &current_frame.frame.output.view,
+                    *transform.as_ref(),
+                )
+                .expect("Draw queued");
+        }
+        self.draw_apart(current_frame, view, apart_world, scale, view_transform);
+
+        self.sdf.draw(current_frame, view);
+
+        let graphics_state = &mut *current_frame.graphics_state;
+        self.glyph_brush
             .draw_queued(
-                &current_frame.graphics_state.device,
-                &mut current_frame.graphics_state.staging_belt,
+                &graphics_state.device,
+                &mut graphics_state.staging_belt,
                 &mut current_frame.encoder,
                 &current_frame.frame.output.view,
                 window_inner_size.width,
                 window_inner_size.height,
             )
             .expect("Draw queued");
+        let projection =
+            wgpu_glyph::orthographic_projection(window_inner_size.width, window_inner_size.height);
+        let projection: [[f32; 4]; 4] = bytemuck::cast(projection);
+        self.draw_apart(current_frame, view, apart_screen, 1.0, projection.into());
+    }
 
-        // current_frame.graphics_state.staging_belt.finish();
+    // one draw for each run of texts with the same clip rect and transform, all in the same
+    // space; `base` takes the space's units to clip space
+    fn draw_apart(
+        &mut self,
+        current_frame: &mut CurrentFrame,
+        view: &SfView,
+        texts: Vec<Text>,
+        scale: f32,
+        base: cgmath::Matrix4<f32>,
+    ) {
+        let graphics_state = &mut *current_frame.graphics_state;
+        let window_inner_size = graphics_state.window_inner_size();
+        let window_size = (window_inner_size.width, window_inner_size.height);
+
+        let mut texts = texts.into_iter().peekable();
+        while let Some(first) = texts.next() {
+            let mut run = vec![first];
+            while let Some(text) =
+                texts.next_if(|text| text.clip == run[0].clip && text.transform == run[0].transform)
+            {
+                run.push(text);
+            }
+            let region = match run[0].clip {
+                Some(clip) => match scissor_rect(clip, run[0].space, view, window_size) {
+                    Some([x, y, width, height]) => Some(Region {
+                        x,
+                        y,
+                        width,
+                        height,
+                    }),
+                    // clipped out of the window entirely
+                    None => continue,
+                },
+                None => None,
+            };
+
+            let sections: Vec<_> = run
+                .iter()
+                .map(|text| {
+                    let section = text.section(scale, self.font_variants(text.font));
+                    (section, self.layout(text))
+                })
+                .collect();
+            let brush = match run[0].space {
+                TextSpace::Screen => &mut self.glyph_brush,
+                TextSpace::World => &mut self.world_glyph_brush,
+            };
+            for (section, layout) in sections {
+                brush.queue_custom_layout(section, &layout);
+            }
+            let transform =
+                base * run[0].transform.get_matrix4() * cgmath::Matrix4::from_scale(1.0 / scale);
+            let result = match region {
+                Some(region) => brush.draw_queued_with_transform_and_scissoring(
+                    &graphics_state.device,
+                    &mut graphics_state.staging_belt,
+                    &mut current_frame.encoder,
+                    &current_frame.frame.output.view,
+                    *transform.as_ref(),
+                    region,
+                ),
+                None => brush.draw_queued_with_transform(
+                    &graphics_state.device,
+                    &mut graphics_state.staging_belt,
+                    &mut current_frame.encoder,
+                    &current_frame.frame.output.view,
+                    *transform.as_ref(),
+                ),
+            };
+            result.expect("Draw queued");
+        }
     }
 }
+
+// clipped and transformed texts can't share the brushes' other draws
+fn drawn_apart(text: &Text) -> bool {
+    text.clip.is_some() || text.transform != Transformable::default()
+}
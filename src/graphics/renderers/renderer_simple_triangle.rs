@@ -1,34 +1,32 @@
-// #![allow(dead_code)]
-
-use crate::graphics::{CurrentFrame, GraphicsState};
-use crate::vertex::Vertex;
+use crate::graphics::pipeline_cache::PipelineCache;
+use crate::graphics::{BlendMode, CurrentFrame, GraphicsState};
+use crate::vertex::{Vertex, VertexLayout};
 use rand::Rng;
 
 pub struct RendererSimpleTriangle {
-    pipeline: wgpu::RenderPipeline,
+    pipelines: PipelineCache,
+    blend_mode: BlendMode,
     buffer: wgpu::Buffer,
 }
 
 impl RendererSimpleTriangle {
-    pub(crate) fn new(graphics_state: &mut GraphicsState) -> Self {
-        let pipeline = {
-            let vs_module = crate::shader_compilation::vertex_module(
-                &mut graphics_state.shader_compiler,
-                &graphics_state.device,
-                include_str!("../../shader_vert.glsl"),
-                "shader_vert.glsl",
-                "Vertex Shader",
-            )
-            .unwrap();
-
-            let fs_module = crate::shader_compilation::fragment_module(
+    pub fn new(graphics_state: &mut GraphicsState) -> Self {
+        let pipelines = {
+            let (vs_module, fs_module) = crate::shader_compilation::modules_or_error(
                 &mut graphics_state.shader_compiler,
                 &graphics_state.device,
-                include_str!("../../shader_frag.glsl"),
-                "shader_frag.glsl",
-                "Fragment Shader",
-            )
-            .unwrap();
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../../shader_vert.glsl"),
+                    filename: "shader_vert.glsl",
+                    label: "Vertex Shader",
+                },
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../../shader_frag.glsl"),
+                    filename: "shader_frag.glsl",
+                    label: "Fragment Shader",
+                },
+                crate::shader_compilation::ErrorVertex::Clip,
+            );
 
             let render_pipeline_layout =
                 graphics_state
@@ -39,40 +37,14 @@ impl RendererSimpleTriangle {
                         push_constant_ranges: &[],
                     });
 
-            graphics_state
-                .device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Render Pipeline"),
-                    layout: Some(&render_pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &vs_module,
-                        entry_point: "main",
-                        buffers: &[Vertex::descriptor()],
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &fs_module,
-                        entry_point: "main",
-                        targets: &[wgpu::ColorTargetState {
-                            format: graphics_state.swap_chain_descriptor.format,
-                            alpha_blend: wgpu::BlendState::REPLACE,
-                            color_blend: wgpu::BlendState::REPLACE,
-                            write_mask: wgpu::ColorWrite::ALL,
-                        }],
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: wgpu::CullMode::Back,
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                    },
-                    depth_stencil: None,
-                    multisample: wgpu::MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                })
+            PipelineCache::new(
+                "Render Pipeline",
+                render_pipeline_layout,
+                vs_module,
+                fs_module,
+                vec![Vertex::descriptor()],
+                graphics_state.swap_chain_descriptor.format,
+            )
         }; // let render_pipeline =
 
         let buffer = {
@@ -86,7 +58,15 @@ impl RendererSimpleTriangle {
                 })
         };
 
-        RendererSimpleTriangle { pipeline, buffer }
+        RendererSimpleTriangle {
+            pipelines,
+            blend_mode: BlendMode::None,
+            buffer,
+        }
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
     }
 
     // fn prepare(
@@ -99,6 +79,10 @@ impl RendererSimpleTriangle {
     // }
 
     pub fn draw(&mut self, current_frame: &mut CurrentFrame) {
+        let pipeline_key = (self.blend_mode, wgpu::PrimitiveTopology::TriangleList);
+        self.pipelines
+            .prepare(&current_frame.graphics_state.device, pipeline_key);
+
         let mut render_pass =
             current_frame
                 .encoder
@@ -123,6 +107,7 @@ impl RendererSimpleTriangle {
                         rand::thread_rng().gen_range(0.0..1.0),
                         rand::thread_rng().gen_range(0.0..1.0),
                         rand::thread_rng().gen_range(0.0..1.0),
+                        1.0,
                     ],
                 },
                 Vertex {
@@ -131,6 +116,7 @@ impl RendererSimpleTriangle {
                         rand::thread_rng().gen_range(0.0..1.0),
                         rand::thread_rng().gen_range(0.0..1.0),
                         rand::thread_rng().gen_range(0.0..1.0),
+                        1.0,
                     ],
                 },
                 Vertex {
@@ -139,6 +125,7 @@ impl RendererSimpleTriangle {
                         rand::thread_rng().gen_range(0.0..1.0),
                         rand::thread_rng().gen_range(0.0..1.0),
                         rand::thread_rng().gen_range(0.0..1.0),
+                        1.0,
                     ],
                 },
             ]
@@ -150,7 +137,7 @@ impl RendererSimpleTriangle {
             bytemuck::cast_slice(vertices),
         );
 
-        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_pipeline(self.pipelines.get(pipeline_key));
         render_pass.set_vertex_buffer(0, self.buffer.slice(..));
         render_pass.draw(0..vertices.len() as u32, 0..1);
     }
@@ -0,0 +1,253 @@
+use crate::graphics::dynamic_buffer::DynamicBuffer;
+use crate::graphics::pipeline_cache::{PipelineCache, PipelineKey};
+use crate::graphics::texture_array::TextureArrays;
+use crate::graphics::view_uniform::ViewUniform;
+use crate::graphics::{
+    BitmapFont, BlendMode, CurrentFrame, GraphicsState, NineSlice, SfView, Sprite, Text, Texture,
+};
+use crate::vertex::{LayeredVertex, TexturedVertex, VertexLayout};
+use std::ops::Range;
+use std::rc::Rc;
+
+// what a batch samples from
+#[derive(Clone)]
+enum BatchTexture {
+    Texture(Rc<Texture>),
+    // index into the renderer's TextureArrays
+    Array(usize),
+}
+
+impl PartialEq for BatchTexture {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (BatchTexture::Texture(a), BatchTexture::Texture(b)) => a.id == b.id,
+            (BatchTexture::Array(a), BatchTexture::Array(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// consecutive quads with the same texture and pipeline
+struct Batch {
+    texture: BatchTexture,
+    vertices: Range<u32>,
+    pipeline_key: PipelineKey,
+}
+
+pub struct RendererSprite {
+    pipelines: PipelineCache,
+    blend_mode: BlendMode,
+    view_uniform: ViewUniform,
+    buffer: DynamicBuffer,
+    vertices: Vec<LayeredVertex>,
+    batches: Vec<Batch>,
+    // None draws every texture on its own
+    texture_arrays: Option<TextureArrays>,
+}
+
+// new
+impl RendererSprite {
+    pub fn new(graphics_state: &mut GraphicsState) -> Self {
+        let view_uniform = ViewUniform::new(&graphics_state.device);
+
+        let pipelines = {
+            let (vs_module, fs_module) = crate::shader_compilation::modules_or_error(
+                &mut graphics_state.shader_compiler,
+                &graphics_state.device,
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../../shader_sprite_array_vert.glsl"),
+                    filename: "shader_sprite_array_vert.glsl",
+                    label: "Sprite Vertex Shader",
+                },
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../../shader_sprite_array_frag.glsl"),
+                    filename: "shader_sprite_array_frag.glsl",
+                    label: "Sprite Fragment Shader",
+                },
+                crate::shader_compilation::ErrorVertex::View,
+            );
+
+            let render_pipeline_layout =
+                graphics_state
+                    .device
+                    .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some("Sprite Pipeline Layout"),
+                        bind_group_layouts: &[
+                            &view_uniform.bind_group_layout,
+                            &graphics_state.texture_array_bind_group_layout,
+                        ],
+                        push_constant_ranges: &[],
+                    });
+
+            PipelineCache::new(
+                "Sprite Pipeline",
+                render_pipeline_layout,
+                vs_module,
+                fs_module,
+                vec![LayeredVertex::descriptor()],
+                graphics_state.swap_chain_descriptor.format,
+            )
+        };
+
+        let buffer = DynamicBuffer::new(
+            &graphics_state.device,
+            "Sprite Vertex Buffer",
+            wgpu::BufferUsage::VERTEX,
+            1024,
+        );
+
+        RendererSprite {
+            pipelines,
+            blend_mode: BlendMode::default(),
+            view_uniform,
+            buffer,
+            vertices: vec![],
+            batches: vec![],
+            texture_arrays: None,
+        }
+    }
+}
+
+// push
+impl RendererSprite {
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.batches.clear();
+        if let Some(arrays) = &mut self.texture_arrays {
+            arrays.release();
+        }
+    }
+
+    // off by default: textures of the same size, format and options are copied into the layers
+    // of shared array textures, so switching between them doesn't break batches;
+    // costs the memory of the copies. also clears what's been pushed
+    pub fn set_texture_arrays(&mut self, enabled: bool) {
+        self.clear();
+        self.texture_arrays = if enabled {
+            Some(TextureArrays::default())
+        } else {
+            None
+        };
+    }
+
+    // applied to everything pushed after it's set
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    pub fn push(&mut self, sprite: &Sprite) {
+        self.push_vertices(&sprite.texture, &sprite.vertices());
+    }
+
+    pub fn push_nine_slice(&mut self, nine_slice: &NineSlice) {
+        self.push_vertices(&nine_slice.texture, &nine_slice.vertices());
+    }
+
+    // see BitmapFont::vertices for what's taken from `text`
+    pub fn push_text(&mut self, font: &BitmapFont, text: &Text) {
+        for (page, vertices) in font.vertices(text) {
+            self.push_vertices(font.page(page), &vertices);
+        }
+    }
+
+    // triangles in world space, textured with `texture`
+    pub fn push_vertices(&mut self, texture: &Rc<Texture>, vertices: &[TexturedVertex]) {
+        let (batch_texture, layer) = match &mut self.texture_arrays {
+            Some(arrays) if TextureArrays::supports(texture) => {
+                let (array_index, layer) = arrays.slot(texture);
+                (BatchTexture::Array(array_index), layer)
+            }
+            _ => (BatchTexture::Texture(texture.clone()), 0),
+        };
+
+        let start = self.vertices.len() as u32;
+        self.vertices.extend(
+            vertices
+                .iter()
+                .map(|&vertex| LayeredVertex::new(vertex, layer)),
+        );
+        let end = self.vertices.len() as u32;
+        let pipeline_key = (self.blend_mode, wgpu::PrimitiveTopology::TriangleList);
+
+        match self.batches.last_mut() {
+            Some(batch)
+                if batch.texture == batch_texture
+                    && batch.pipeline_key == pipeline_key
+                    && batch.vertices.end == start =>
+            {
+                batch.vertices.end = end;
+            }
+            _ => self.batches.push(Batch {
+                texture: batch_texture,
+                vertices: start..end,
+                pipeline_key,
+            }),
+        }
+    }
+}
+
+// accessors
+impl RendererSprite {
+    // one draw call each
+    pub fn batch_count(&self) -> usize {
+        self.batches.len()
+    }
+}
+
+// draw
+impl RendererSprite {
+    pub fn draw(&mut self, current_frame: &mut CurrentFrame, view: &SfView) {
+        if self.batches.is_empty() {
+            return;
+        }
+
+        if let Some(arrays) = &mut self.texture_arrays {
+            arrays.prepare(current_frame.graphics_state, &mut current_frame.encoder);
+        }
+
+        let device = &current_frame.graphics_state.device;
+        let queue = &current_frame.graphics_state.queue;
+        self.buffer
+            .write(device, queue, bytemuck::cast_slice(&self.vertices));
+        self.view_uniform.write(queue, view);
+        for batch in &self.batches {
+            self.pipelines.prepare(device, batch.pipeline_key);
+        }
+
+        let mut render_pass =
+            current_frame
+                .encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Sprite Render Pass"),
+                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: &current_frame.frame.output.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+
+        render_pass.set_bind_group(0, &self.view_uniform.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice());
+        let mut current_key = None;
+        for batch in &self.batches {
+            if current_key != Some(batch.pipeline_key) {
+                render_pass.set_pipeline(self.pipelines.get(batch.pipeline_key));
+                current_key = Some(batch.pipeline_key);
+            }
+            let bind_group = match &batch.texture {
+                BatchTexture::Texture(texture) => &texture.array_bind_group,
+                BatchTexture::Array(array_index) => self
+                    .texture_arrays
+                    .as_ref()
+                    .unwrap()
+                    .bind_group(*array_index),
+            };
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw(batch.vertices.clone(), 0..1);
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use crate::frame_counter::FrameCounter;
+use crate::graphics::dynamic_buffer::DynamicBuffer;
+use crate::graphics::pipeline_cache::PipelineCache;
+use crate::graphics::tilemap::{flipped_size, split_gid, tile_vertices, Tilemap};
+use crate::graphics::view_uniform::ViewUniform;
+use crate::graphics::{BlendMode, CurrentFrame, GraphicsState, Rect, SfView};
+use crate::vertex::{TexturedVertex, VertexLayout};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::time::Duration;
+
+// in tiles
+const CHUNK_SIZE: u32 = 32;
+
+// (layer, chunk x, chunk y)
+type ChunkKey = (usize, u32, u32);
+
+// the static tiles of one chunk of one layer, one buffer per tileset used
+struct Chunk {
+    // in world units, including tiles sticking out of their cells
+    bounds: Rect,
+    parts: Vec<ChunkPart>,
+    // gids and cells of the animated tiles, rebuilt every frame
+    animated: Vec<(u32, Rect)>,
+}
+
+struct ChunkPart {
+    tileset: usize,
+    buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+enum Draw {
+    Static {
+        chunk: ChunkKey,
+        part: usize,
+    },
+    Animated {
+        tileset: usize,
+        vertices: Range<u32>,
+    },
+}
+
+pub struct RendererTilemap {
+    pipelines: PipelineCache,
+    // every layer is drawn with it
+    blend_mode: BlendMode,
+    view_uniform: ViewUniform,
+    tilemap: Tilemap,
+    chunks: HashMap<ChunkKey, Chunk>,
+    // chunks to rebuild before the next draw
+    dirty: HashSet<ChunkKey>,
+    // drives animated tiles
+    time: Duration,
+    animated_buffer: DynamicBuffer,
+    animated_vertices: Vec<TexturedVertex>,
+    draws: Vec<Draw>,
+}
+
+// new
+impl RendererTilemap {
+    pub fn new(graphics_state: &mut GraphicsState, tilemap: Tilemap) -> Self {
+        let view_uniform = ViewUniform::new(&graphics_state.device);
+
+        // tiles are textured quads, exactly like sprites
+        let pipelines = {
+            let (vs_module, fs_module) = crate::shader_compilation::modules_or_error(
+                &mut graphics_state.shader_compiler,
+                &graphics_state.device,
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../../shader_sprite_vert.glsl"),
+                    filename: "shader_sprite_vert.glsl",
+                    label: "Tilemap Vertex Shader",
+                },
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../../shader_sprite_frag.glsl"),
+                    filename: "shader_sprite_frag.glsl",
+                    label: "Tilemap Fragment Shader",
+                },
+                crate::shader_compilation::ErrorVertex::View,
+            );
+
+            let render_pipeline_layout =
+                graphics_state
+                    .device
+                    .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some("Tilemap Pipeline Layout"),
+                        bind_group_layouts: &[
+                            &view_uniform.bind_group_layout,
+                            &graphics_state.texture_bind_group_layout,
+                        ],
+                        push_constant_ranges: &[],
+                    });
+
+            PipelineCache::new(
+                "Tilemap Pipeline",
+                render_pipeline_layout,
+                vs_module,
+                fs_module,
+                vec![TexturedVertex::descriptor()],
+                graphics_state.swap_chain_descriptor.format,
+            )
+        };
+
+        let animated_buffer = DynamicBuffer::new(
+            &graphics_state.device,
+            "Tilemap Animated Vertex Buffer",
+            wgpu::BufferUsage::VERTEX,
+            1024,
+        );
+
+        let mut renderer = RendererTilemap {
+            pipelines,
+            blend_mode: BlendMode::default(),
+            view_uniform,
+            tilemap,
+            chunks: HashMap::new(),
+            dirty: HashSet::new(),
+            time: Duration::default(),
+            animated_buffer,
+            animated_vertices: vec![],
+            draws: vec![],
+        };
+        renderer.invalidate();
+        renderer
+    }
+}
+
+// map
+impl RendererTilemap {
+    pub fn tilemap(&self) -> &Tilemap {
+        &self.tilemap
+    }
+
+    // for bigger changes, everything is rebuilt before the next draw
+    pub fn tilemap_mut(&mut self) -> &mut Tilemap {
+        self.invalidate();
+        &mut self.tilemap
+    }
+
+    pub fn set_tile(&mut self, layer: usize, x: u32, y: u32, gid: u32) {
+        let tile_layer = &mut self.tilemap.layers[layer];
+        if x >= tile_layer.size.0 || y >= tile_layer.size.1 {
+            return;
+        }
+        tile_layer.tiles[y as usize * tile_layer.size.0 as usize + x as usize] = gid;
+        self.dirty.insert((layer, x / CHUNK_SIZE, y / CHUNK_SIZE));
+    }
+
+    // visibility is checked every draw, so this doesn't rebuild anything
+    pub fn set_layer_visible(&mut self, layer: usize, visible: bool) {
+        self.tilemap.layers[layer].visible = visible;
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    fn invalidate(&mut self) {
+        self.chunks.clear();
+        self.dirty.clear();
+        for (index, layer) in self.tilemap.layers.iter().enumerate() {
+            for chunk_y in 0..(layer.size.1 + CHUNK_SIZE - 1) / CHUNK_SIZE {
+                for chunk_x in 0..(layer.size.0 + CHUNK_SIZE - 1) / CHUNK_SIZE {
+                    self.dirty.insert((index, chunk_x, chunk_y));
+                }
+            }
+        }
+    }
+}
+
+// animation
+impl RendererTilemap {
+    pub fn advance(&mut self, frame_counter: &FrameCounter) {
+        self.update(frame_counter.last_frame_time());
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        self.time += delta;
+    }
+}
+
+// draw
+impl RendererTilemap {
+    fn build_chunk(&self, device: &wgpu::Device, key: ChunkKey) -> Chunk {
+        use wgpu::util::DeviceExt;
+
+        let (layer_index, chunk_x, chunk_y) = key;
+        let layer = &self.tilemap.layers[layer_index];
+        let (tile_width, tile_height) = self.tilemap.tile_size;
+        let color = [1.0, 1.0, 1.0, layer.opacity];
+
+        let mut vertices: Vec<Vec<TexturedVertex>> = vec![vec![]; self.tilemap.tilesets.len()];
+        let mut animated = vec![];
+        let mut bounds: Option<Rect> = None;
+
+        let x_range = chunk_x * CHUNK_SIZE..((chunk_x + 1) * CHUNK_SIZE).min(layer.size.0);
+        let y_range = chunk_y * CHUNK_SIZE..((chunk_y + 1) * CHUNK_SIZE).min(layer.size.1);
+        for y in y_range {
+            for x in x_range.clone() {
+                let gid = layer.tile(x, y);
+                let tileset_index = match self.tilemap.tileset_index(gid) {
+                    Some(tileset_index) => tileset_index,
+                    None => continue,
+                };
+                let tileset = &self.tilemap.tilesets[tileset_index];
+                let cell = Rect::new(
+                    layer.offset.0 + (x * tile_width) as f32,
+                    layer.offset.1 + (y * tile_height) as f32,
+                    tile_width as f32,
+                    tile_height as f32,
+                );
+
+                let (gid, flags) = split_gid(gid);
+
+                // the tile image is anchored to the bottom left of the cell
+                let tile_size = flipped_size(
+                    (tileset.tile_size.0 as f32, tileset.tile_size.1 as f32),
+                    flags,
+                );
+                let tile_bounds = Rect::new(
+                    cell.left,
+                    cell.bottom() - tile_size.1,
+                    tile_size.0,
+                    tile_size.1,
+                );
+                bounds = Some(match bounds {
+                    Some(bounds) => union(bounds, tile_bounds),
+                    None => tile_bounds,
+                });
+
+                let tile_id = gid - tileset.first_gid;
+                if tileset.animations.contains_key(&tile_id) {
+                    animated.push((gid | flags, cell));
+                } else {
+                    vertices[tileset_index]
+                        .extend_from_slice(&tile_vertices(tileset, tile_id, flags, cell, color));
+                }
+            }
+        }
+
+        let parts = vertices
+            .into_iter()
+            .enumerate()
+            .filter(|(_, vertices)| !vertices.is_empty())
+            .map(|(tileset, vertices)| ChunkPart {
+                tileset,
+                buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Tilemap Chunk Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsage::VERTEX,
+                }),
+                vertex_count: vertices.len() as u32,
+            })
+            .collect();
+
+        Chunk {
+            bounds: bounds.unwrap_or_default(),
+            parts,
+            animated,
+        }
+    }
+
+    pub fn draw(&mut self, current_frame: &mut CurrentFrame, view: &SfView) {
+        let device = &current_frame.graphics_state.device;
+        let queue = &current_frame.graphics_state.queue;
+
+        let dirty = std::mem::take(&mut self.dirty);
+        for key in dirty {
+            let chunk = self.build_chunk(device, key);
+            self.chunks.insert(key, chunk);
+        }
+
+        // only chunks overlapping the view, layer by layer
+        let visible_rect = view.visible_rect();
+        let mut visible: Vec<ChunkKey> = self
+            .chunks
+            .iter()
+            .filter(|(&(layer, _, _), chunk)| {
+                self.tilemap.layers[layer].visible
+                    && (!chunk.parts.is_empty() || !chunk.animated.is_empty())
+                    && chunk.bounds.intersects(&visible_rect)
+            })
+            .map(|(&key, _)| key)
+            .collect();
+        visible.sort_unstable();
+
+        self.draws.clear();
+        self.animated_vertices.clear();
+        for &key in &visible {
+            let chunk = &self.chunks[&key];
+            for part in 0..chunk.parts.len() {
+                self.draws.push(Draw::Static { chunk: key, part });
+            }
+
+            let color = [1.0, 1.0, 1.0, self.tilemap.layers[key.0].opacity];
+            for &(gid, cell) in &chunk.animated {
+                let tileset_index = self.tilemap.tileset_index(gid).unwrap();
+                let tileset = &self.tilemap.tilesets[tileset_index];
+                let (gid, flags) = split_gid(gid);
+                let tile_id = tileset.animated_tile_id(gid - tileset.first_gid, self.time);
+
+                let start = self.animated_vertices.len() as u32;
+                self.animated_vertices
+                    .extend_from_slice(&tile_vertices(tileset, tile_id, flags, cell, color));
+                let end = self.animated_vertices.len() as u32;
+                match self.draws.last_mut() {
+                    Some(Draw::Animated { tileset, vertices })
+                        if *tileset == tileset_index && vertices.end == start =>
+                    {
+                        vertices.end = end;
+                    }
+                    _ => self.draws.push(Draw::Animated {
+                        tileset: tileset_index,
+                        vertices: start..end,
+                    }),
+                }
+            }
+        }
+
+        if self.draws.is_empty() {
+            return;
+        }
+
+        self.animated_buffer
+            .write(device, queue, bytemuck::cast_slice(&self.animated_vertices));
+        self.view_uniform.write(queue, view);
+        let pipeline_key = (self.blend_mode, wgpu::PrimitiveTopology::TriangleList);
+        self.pipelines.prepare(device, pipeline_key);
+
+        let mut render_pass =
+            current_frame
+                .encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Tilemap Render Pass"),
+                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: &current_frame.frame.output.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+
+        render_pass.set_pipeline(self.pipelines.get(pipeline_key));
+        render_pass.set_bind_group(0, &self.view_uniform.bind_group, &[]);
+        let mut current_tileset = None;
+        for draw in &self.draws {
+            let tileset = match *draw {
+                Draw::Static { chunk, part } => self.chunks[&chunk].parts[part].tileset,
+                Draw::Animated { tileset, .. } => tileset,
+            };
+            if current_tileset != Some(tileset) {
+                render_pass.set_bind_group(
+                    1,
+                    &self.tilemap.tilesets[tileset].texture.bind_group,
+                    &[],
+                );
+                current_tileset = Some(tileset);
+            }
+
+            match draw {
+                Draw::Static { chunk, part } => {
+                    let part = &self.chunks[chunk].parts[*part];
+                    render_pass.set_vertex_buffer(0, part.buffer.slice(..));
+                    render_pass.draw(0..part.vertex_count, 0..1);
+                }
+                Draw::Animated { vertices, .. } => {
+                    render_pass.set_vertex_buffer(0, self.animated_buffer.slice());
+                    render_pass.draw(vertices.clone(), 0..1);
+                }
+            }
+        }
+    }
+}
+
+fn union(a: Rect, b: Rect) -> Rect {
+    let left = a.left.min(b.left);
+    let top = a.top.min(b.top);
+    Rect::new(
+        left,
+        top,
+        a.right().max(b.right()) - left,
+        a.bottom().max(b.bottom()) - top,
+    )
+}
@@ -1,154 +1,93 @@
-// #![allow(dead_code)]
-
-use crate::graphics::{CurrentFrame, GraphicsState, SfView};
-use crate::vertex::Vertex;
-use cgmath::Transform;
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct Uniforms {
-    m: [[f32; 4]; 4],
+use crate::graphics::dynamic_buffer::DynamicBuffer;
+use crate::graphics::pipeline_cache::{PipelineCache, PipelineKey};
+use crate::graphics::view_uniform::ViewUniform;
+use crate::graphics::{BlendMode, CurrentFrame, GraphicsState, SfView, Transformable};
+use crate::vertex::{Instance, Vertex, VertexLayout};
+use std::ops::Range;
+
+struct Draw {
+    vertices: Range<u32>,
+    instances: Range<u32>,
+    pipeline_key: PipelineKey,
 }
 
 pub struct RendererWithView {
-    pipeline: wgpu::RenderPipeline,
-    buffer: wgpu::Buffer,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
+    pipelines: PipelineCache,
+    // applied to everything pushed after they're set
+    blend_mode: BlendMode,
+    topology: wgpu::PrimitiveTopology,
+    buffer: DynamicBuffer,
+    instance_buffer: DynamicBuffer,
+    vertices: Vec<Vertex>,
+    // the first instance is always the identity, for untransformed vertices
+    instances: Vec<Instance>,
+    draws: Vec<Draw>,
+    view_uniform: ViewUniform,
 }
 
 impl RendererWithView {
-    pub(crate) fn new(graphics_state: &mut GraphicsState) -> Self {
-        let uniforms = Uniforms { m: [[0.0; 4]; 4] };
-
-        let uniform_buffer = {
-            graphics_state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Uniform Buffer"),
-                    contents: bytemuck::cast_slice(&[uniforms]),
-                    usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-                })
-        };
-
-        let uniform_bind_group_layout = {
-            graphics_state
-                .device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStage::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }],
-                    label: Some("uniform_bind_group_layout"),
-                })
-        };
-
-        let uniform_bind_group = {
-            graphics_state
-                .device
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &uniform_bind_group_layout,
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: uniform_buffer.as_entire_binding(),
-                    }],
-                    label: Some("uniform_bind_group"),
-                })
-        };
-
-        let pipeline = {
-            let (vs_module, fs_module) = {
-                let vs_module = crate::shader_compilation::vertex_module(
-                    &mut graphics_state.shader_compiler,
-                    &graphics_state.device,
-                    include_str!("../../shader_vert_with_view.glsl"),
-                    "shader.vert",
-                    "Vertex Shader",
-                )
-                .unwrap();
-
-                let fs_module = crate::shader_compilation::fragment_module(
-                    &mut graphics_state.shader_compiler,
-                    &graphics_state.device,
-                    include_str!("../../shader_frag.glsl"),
-                    "shader.frag",
-                    "Fragment Shader",
-                )
-                .unwrap();
-
-                (vs_module, fs_module)
-            };
+    pub fn new(graphics_state: &mut GraphicsState) -> Self {
+        let view_uniform = ViewUniform::new(&graphics_state.device);
+
+        let pipelines = {
+            let (vs_module, fs_module) = crate::shader_compilation::modules_or_error(
+                &mut graphics_state.shader_compiler,
+                &graphics_state.device,
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../../shader_vert_with_view.glsl"),
+                    filename: "shader.vert",
+                    label: "Vertex Shader",
+                },
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../../shader_frag.glsl"),
+                    filename: "shader.frag",
+                    label: "Fragment Shader",
+                },
+                crate::shader_compilation::ErrorVertex::View,
+            );
 
             let render_pipeline_layout =
                 graphics_state
                     .device
                     .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                         label: Some("Render Pipeline Layout"),
-                        bind_group_layouts: &[
-                            &uniform_bind_group_layout, //     <-- <-- <--
-                        ],
+                        bind_group_layouts: &[&view_uniform.bind_group_layout],
                         push_constant_ranges: &[],
                     });
 
-            graphics_state
-                .device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Render Pipeline"),
-                    layout: Some(&render_pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &vs_module,
-                        entry_point: "main",
-                        buffers: &[Vertex::descriptor()],
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &fs_module,
-                        entry_point: "main",
-                        targets: &[wgpu::ColorTargetState {
-                            format: graphics_state.swap_chain_descriptor.format,
-                            alpha_blend: wgpu::BlendState::REPLACE,
-                            color_blend: wgpu::BlendState::REPLACE,
-                            write_mask: wgpu::ColorWrite::ALL,
-                        }],
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: wgpu::CullMode::Back,
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                    },
-                    depth_stencil: None,
-                    multisample: wgpu::MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                })
+            PipelineCache::new(
+                "Render Pipeline",
+                render_pipeline_layout,
+                vs_module,
+                fs_module,
+                vec![Vertex::descriptor(), Instance::descriptor()],
+                graphics_state.swap_chain_descriptor.format,
+            )
         }; // let render_pipeline =
 
-        use wgpu::util::DeviceExt;
-
-        let buffer = {
-            graphics_state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Vertex Buffer"),
-                    contents: &[0; 320],
-                    usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
-                })
-        };
+        let buffer = DynamicBuffer::new(
+            &graphics_state.device,
+            "Vertex Buffer",
+            wgpu::BufferUsage::VERTEX,
+            320,
+        );
+        let instance_buffer = DynamicBuffer::new(
+            &graphics_state.device,
+            "Instance Buffer",
+            wgpu::BufferUsage::VERTEX,
+            320,
+        );
 
         Self {
-            pipeline,
+            pipelines,
+            blend_mode: BlendMode::default(),
+            topology: wgpu::PrimitiveTopology::TriangleList,
             buffer,
-            uniform_buffer,
-            uniform_bind_group,
+            instance_buffer,
+            vertices: vec![],
+            instances: vec![Instance::IDENTITY],
+            draws: vec![],
+            view_uniform,
         }
     }
 
@@ -161,7 +100,86 @@ impl RendererWithView {
     //     // Renderer {}
     // }
 
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.instances.truncate(1);
+        self.draws.clear();
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    pub fn set_topology(&mut self, topology: wgpu::PrimitiveTopology) {
+        self.topology = topology;
+    }
+
+    pub fn push(&mut self, vertices: &[Vertex]) {
+        let start = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(vertices);
+        let end = self.vertices.len() as u32;
+        let pipeline_key = (self.blend_mode, self.topology);
+
+        // untransformed vertices pushed one after another share a draw,
+        // as long as the blend mode and topology didn't change in between;
+        // strips would be joined into one, so they're always drawn on their own
+        let list = match self.topology {
+            wgpu::PrimitiveTopology::PointList
+            | wgpu::PrimitiveTopology::LineList
+            | wgpu::PrimitiveTopology::TriangleList => true,
+            wgpu::PrimitiveTopology::LineStrip | wgpu::PrimitiveTopology::TriangleStrip => false,
+        };
+        match self.draws.last_mut() {
+            Some(draw)
+                if list
+                    && draw.instances == (0..1)
+                    && draw.vertices.end == start
+                    && draw.pipeline_key == pipeline_key =>
+            {
+                draw.vertices.end = end;
+            }
+            _ => self.draws.push(Draw {
+                vertices: start..end,
+                instances: 0..1,
+                pipeline_key,
+            }),
+        }
+    }
+
+    // transformed on the cpu, so it still batches with other pushes
+    pub fn push_transformed(&mut self, vertices: &[Vertex], transformable: &Transformable) {
+        let start = self.vertices.len();
+        self.push(vertices);
+        transformable.transform_vertices(&mut self.vertices[start..]);
+    }
+
+    // transformed on the gpu, the vertices are drawn once per transformable
+    pub fn push_instanced(&mut self, vertices: &[Vertex], transformables: &[Transformable]) {
+        let vertices_start = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(vertices);
+        let instances_start = self.instances.len() as u32;
+        self.instances
+            .extend(transformables.iter().map(Transformable::instance));
+
+        self.draws.push(Draw {
+            vertices: vertices_start..self.vertices.len() as u32,
+            instances: instances_start..self.instances.len() as u32,
+            pipeline_key: (self.blend_mode, self.topology),
+        });
+    }
+
     pub fn draw(&mut self, current_frame: &mut CurrentFrame, view: &SfView) {
+        let device = &current_frame.graphics_state.device;
+        let queue = &current_frame.graphics_state.queue;
+        self.buffer
+            .write(device, queue, bytemuck::cast_slice(&self.vertices));
+        self.instance_buffer
+            .write(device, queue, bytemuck::cast_slice(&self.instances));
+        self.view_uniform.write(queue, view);
+        for draw in &self.draws {
+            self.pipelines.prepare(device, draw.pipeline_key);
+        }
+
         let mut render_pass =
             current_frame
                 .encoder
@@ -183,56 +201,20 @@ impl RendererWithView {
                     depth_stencil_attachment: None,
                 });
 
-        let vertices: &[Vertex] = {
-            &[
-                Vertex {
-                    position: [100.0, 100.0, 0.0],
-                    color: [0.0, 0.1, 1.0],
-                },
-                Vertex {
-                    position: [900.0, 900.0, 0.0],
-                    color: [0.0, 0.2, 1.0],
-                },
-                Vertex {
-                    position: [900.0, 100.0, 0.0],
-                    color: [0.0, 0.3, 1.0],
-                },
-            ]
-        };
-
-        current_frame.graphics_state.queue.write_buffer(
-            &self.buffer,
-            wgpu::BufferAddress::from(0u32),
-            bytemuck::cast_slice(vertices),
-        );
-
-        // let m: cgmath::Matrix4<f32> = view.get_matrix3().into();
-        // let m: cgmath::Matrix4<f32> = m * crate::graphics::sf_view::OPENGL_TO_WGPU_MATRIX4;
-        let m = view.get_matrix4();
-        let m = m * crate::graphics::sf_view::OPENGL_TO_WGPU_MATRIX4;
-
-        // dbg!("oof");
-        // vertices.iter().for_each(|&v| {
-        //     // dbg!(v);
-        //     let p = m.transform_point(cgmath::Point3::new(
-        //         v.position[0],
-        //         v.position[1],
-        //         v.position[2],
-        //     ));
-        //     // dbg!(p);
-        // });
-
-        let u = Uniforms { m: m.into() };
-
-        current_frame.graphics_state.queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[u]),
-        );
+        if self.draws.is_empty() {
+            return;
+        }
 
-        render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
-        render_pass.draw(0..vertices.len() as u32, 0..1);
+        render_pass.set_bind_group(0, &self.view_uniform.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice());
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice());
+        let mut current_key = None;
+        for draw in &self.draws {
+            if current_key != Some(draw.pipeline_key) {
+                render_pass.set_pipeline(self.pipelines.get(draw.pipeline_key));
+                current_key = Some(draw.pipeline_key);
+            }
+            render_pass.draw(draw.vertices.clone(), draw.instances.clone());
+        }
     }
 }
@@ -0,0 +1,102 @@
+// a box of screen space texts, like a chat panel or a list, scrolled by the mouse wheel; the
+// texts are placed inside it and cut off at its edges
+use crate::graphics::{Rect, Text};
+use winit::event::{MouseScrollDelta, WindowEvent};
+
+pub struct ScrollRegion {
+    // in window pixels
+    pub rect: Rect,
+    // how far the content is scrolled right and down, from its top left corner at the rect's
+    pub offset: (f32, f32),
+    // how much there is to scroll through, the offset stops where its end meets the rect's
+    pub content_size: (f32, f32),
+    // how far a line of the mouse wheel scrolls
+    pub line_height: f32,
+    cursor: (f32, f32),
+}
+
+// new
+impl ScrollRegion {
+    pub fn new(rect: Rect) -> Self {
+        ScrollRegion {
+            rect,
+            offset: (0.0, 0.0),
+            content_size: (0.0, 0.0),
+            line_height: 20.0,
+            cursor: (0.0, 0.0),
+        }
+    }
+}
+
+// accessors
+impl ScrollRegion {
+    pub fn max_offset(&self) -> (f32, f32) {
+        (
+            (self.content_size.0 - self.rect.width).max(0.0),
+            (self.content_size.1 - self.rect.height).max(0.0),
+        )
+    }
+
+    // a text positioned relative to the content's top left corner, moved to where it's drawn;
+    // its own clip, also relative to the content, is kept where it's inside the region
+    pub fn place(&self, text: &Text) -> Text {
+        let (x, y) = (
+            self.rect.left - self.offset.0,
+            self.rect.top - self.offset.1,
+        );
+        let clip = match text.clip {
+            Some(clip) => Rect::new(clip.left + x, clip.top + y, clip.width, clip.height)
+                .intersection(&self.rect)
+                .unwrap_or_default(),
+            None => self.rect,
+        };
+        Text {
+            position: (text.position.0 + x, text.position.1 + y),
+            clip: Some(clip),
+            ..text.clone()
+        }
+    }
+}
+
+// scroll
+impl ScrollRegion {
+    pub fn scroll_by(&mut self, x: f32, y: f32) {
+        let max = self.max_offset();
+        self.offset = (
+            (self.offset.0 + x).clamp(0.0, max.0),
+            (self.offset.1 + y).clamp(0.0, max.1),
+        );
+    }
+
+    // to the bottom, where the newest line of a chat is
+    pub fn scroll_to_end(&mut self) {
+        self.offset.1 = self.max_offset().1;
+    }
+}
+
+// events
+impl ScrollRegion {
+    // true when the wheel scrolled the region, with the cursor over it
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x as f32, position.y as f32);
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } if self.rect.contains(self.cursor) => {
+                // wheeling up moves the content down, to show what's above
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        (x * self.line_height, y * self.line_height)
+                    }
+                    MouseScrollDelta::PixelDelta(position) => {
+                        (position.x as f32, position.y as f32)
+                    }
+                };
+                self.scroll_by(-x, -y);
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,656 @@
+// signed distance field glyphs, generated from the font outlines on the cpu and packed into a
+// single channel atlas; RendererGlyph draws texts with TextRendering::Sdf through this
+use crate::graphics::atlas::ShelfPacker;
+use crate::graphics::dynamic_buffer::DynamicBuffer;
+use crate::graphics::pipeline_cache::PipelineCache;
+use crate::graphics::text::{scissor_rect, FontVariants, SdfEffects, Text, TextLayout, TextSpace};
+use crate::graphics::view_uniform::ViewUniform;
+use crate::graphics::{
+    BlendMode, CurrentFrame, GraphicsState, Rect, SfView, Texture, TextureOptions,
+};
+use crate::vertex::{SdfVertex, VertexLayout};
+use cgmath::Transform;
+use std::collections::HashMap;
+use wgpu_glyph::ab_glyph::{Font, FontArc, GlyphId, OutlineCurve, Point, ScaleFont};
+use wgpu_glyph::{FontId, GlyphPositioner, SectionGeometry};
+
+// the size glyphs are generated at, every other size is scaled from it
+const BASE_SIZE: f32 = 48.0;
+// how far from the outline distances are stored, in atlas pixels; effects can't reach further
+const SPREAD: f32 = 8.0;
+// between glyphs in the atlas, so linear filtering doesn't pull in the neighbours
+const PADDING: u32 = 1;
+const INITIAL_ATLAS_SIZE: u32 = 512;
+const MAX_ATLAS_SIZE: u32 = 4096;
+// segments per bezier curve
+const CURVE_STEPS: usize = 8;
+
+#[derive(Copy, Clone, Debug)]
+struct SdfGlyph {
+    // in atlas pixels
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    // of the bitmap's top left corner from the glyph's origin, in BASE_SIZE pixels
+    left: f32,
+    top: f32,
+}
+
+// in BASE_SIZE pixels, y down
+type Segment = ((f32, f32), (f32, f32));
+
+struct SdfBitmap {
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+// None for glyphs without an outline, like spaces
+fn generate(font: &FontArc, glyph_id: GlyphId) -> Option<SdfBitmap> {
+    let outline = font.outline(glyph_id)?;
+    let scale = font.as_scaled(BASE_SIZE).h_scale_factor();
+    // font units are y up, pixels are y down
+    let to_pixels = |point: Point| (point.x * scale, -point.y * scale);
+
+    let mut segments = Vec::new();
+    for curve in &outline.curves {
+        match *curve {
+            OutlineCurve::Line(p0, p1) => segments.push((to_pixels(p0), to_pixels(p1))),
+            OutlineCurve::Quad(p0, p1, p2) => {
+                let (p0, p1, p2) = (to_pixels(p0), to_pixels(p1), to_pixels(p2));
+                flatten(&mut segments, |t| {
+                    let u = 1.0 - t;
+                    let (a, b, c) = (u * u, 2.0 * u * t, t * t);
+                    (
+                        a * p0.0 + b * p1.0 + c * p2.0,
+                        a * p0.1 + b * p1.1 + c * p2.1,
+                    )
+                });
+            }
+            OutlineCurve::Cubic(p0, p1, p2, p3) => {
+                let (p0, p1, p2, p3) = (to_pixels(p0), to_pixels(p1), to_pixels(p2), to_pixels(p3));
+                flatten(&mut segments, |t| {
+                    let u = 1.0 - t;
+                    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                    (
+                        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+                        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+                    )
+                });
+            }
+        }
+    }
+
+    // ab_glyph's bounds have the top in min.y and the bottom in max.y
+    let pad = SPREAD.ceil() as i32;
+    let left = (outline.bounds.min.x * scale).floor() as i32 - pad;
+    let top = (-outline.bounds.min.y * scale).floor() as i32 - pad;
+    let right = (outline.bounds.max.x * scale).ceil() as i32 + pad;
+    let bottom = (-outline.bounds.max.y * scale).ceil() as i32 + pad;
+    let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let point = (left as f32 + x as f32 + 0.5, top as f32 + y as f32 + 0.5);
+            let mut distance_squared = f32::MAX;
+            let mut winding = 0;
+            for &(a, b) in &segments {
+                distance_squared = distance_squared.min(segment_distance_squared(point, a, b));
+                winding += crossing(point, a, b);
+            }
+            // non-zero winding is inside, like the rasterizer fills it
+            let distance = if winding != 0 {
+                distance_squared.sqrt()
+            } else {
+                -distance_squared.sqrt()
+            };
+            let value = (0.5 + distance / (2.0 * SPREAD)).clamp(0.0, 1.0);
+            pixels.push((value * 255.0).round() as u8);
+        }
+    }
+
+    Some(SdfBitmap {
+        left,
+        top,
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn flatten<F: Fn(f32) -> (f32, f32)>(segments: &mut Vec<Segment>, curve: F) {
+    let mut previous = curve(0.0);
+    for step in 1..=CURVE_STEPS {
+        let point = curve(step as f32 / CURVE_STEPS as f32);
+        segments.push((previous, point));
+        previous = point;
+    }
+}
+
+fn segment_distance_squared(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (abx, aby) = (b.0 - a.0, b.1 - a.1);
+    let (apx, apy) = (p.0 - a.0, p.1 - a.1);
+    let length_squared = abx * abx + aby * aby;
+    let t = if length_squared > 0.0 {
+        ((apx * abx + apy * aby) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (dx, dy) = (apx - abx * t, apy - aby * t);
+    dx * dx + dy * dy
+}
+
+// +1 or -1 when the segment crosses the ray from `p` to +x upwards or downwards
+fn crossing(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> i32 {
+    let side = (b.0 - a.0) * (p.1 - a.1) - (p.0 - a.0) * (b.1 - a.1);
+    if a.1 <= p.1 {
+        if b.1 > p.1 && side > 0.0 {
+            return 1;
+        }
+    } else if b.1 <= p.1 && side < 0.0 {
+        return -1;
+    }
+    0
+}
+
+// one page that grows, glyphs are generated the first time they're drawn
+struct SdfAtlas {
+    size: u32,
+    // cpu copy, copied into the bigger page when it grows
+    pixels: Vec<u8>,
+    packer: ShelfPacker,
+    glyphs: HashMap<(FontId, GlyphId), Option<SdfGlyph>>,
+    // rows to upload before the next draw
+    dirty_rows: Option<(u32, u32)>,
+    // recreated when the atlas grows
+    texture: Option<(wgpu::Texture, wgpu::BindGroup)>,
+}
+
+impl SdfAtlas {
+    fn new() -> Self {
+        SdfAtlas {
+            size: INITIAL_ATLAS_SIZE,
+            pixels: vec![0; (INITIAL_ATLAS_SIZE * INITIAL_ATLAS_SIZE) as usize],
+            packer: ShelfPacker::default(),
+            glyphs: HashMap::new(),
+            dirty_rows: None,
+            texture: None,
+        }
+    }
+
+    fn glyph(&mut self, fonts: &[FontArc], font_id: FontId, glyph_id: GlyphId) -> Option<SdfGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(font_id, glyph_id)) {
+            return *glyph;
+        }
+
+        let glyph = generate(&fonts[font_id.0], glyph_id).and_then(|bitmap| self.place(&bitmap));
+        self.glyphs.insert((font_id, glyph_id), glyph);
+        glyph
+    }
+
+    fn place(&mut self, bitmap: &SdfBitmap) -> Option<SdfGlyph> {
+        let padded_size = (bitmap.width + PADDING, bitmap.height + PADDING);
+        let (x, y) = loop {
+            if let Some(position) = self.packer.pack((self.size, self.size), padded_size) {
+                break position;
+            }
+            if self.size >= MAX_ATLAS_SIZE {
+                log::warn!("the distance field glyph atlas is full");
+                return None;
+            }
+            self.grow();
+        };
+
+        for row in 0..bitmap.height {
+            let source = (row * bitmap.width) as usize;
+            let target = ((y + row) * self.size + x) as usize;
+            self.pixels[target..target + bitmap.width as usize]
+                .copy_from_slice(&bitmap.pixels[source..source + bitmap.width as usize]);
+        }
+        let rows = (y, y + bitmap.height);
+        self.dirty_rows = Some(match self.dirty_rows {
+            Some((start, end)) => (start.min(rows.0), end.max(rows.1)),
+            None => rows,
+        });
+
+        Some(SdfGlyph {
+            x,
+            y,
+            width: bitmap.width,
+            height: bitmap.height,
+            left: bitmap.left as f32,
+            top: bitmap.top as f32,
+        })
+    }
+
+    // the packed glyphs stay where they are, the page gets more room to the right and below
+    fn grow(&mut self) {
+        let size = self.size * 2;
+        log::debug!("growing the distance field glyph atlas to {}", size);
+        let mut pixels = vec![0; (size * size) as usize];
+        for (row, old) in self.pixels.chunks_exact(self.size as usize).enumerate() {
+            let target = row * size as usize;
+            pixels[target..target + old.len()].copy_from_slice(old);
+        }
+        self.size = size;
+        self.pixels = pixels;
+        self.texture = None;
+        self.dirty_rows = Some((0, size));
+    }
+
+    // creates the texture and uploads the new glyphs
+    fn prepare(&mut self, graphics_state: &GraphicsState) {
+        let device = &graphics_state.device;
+        let size = self.size;
+        let texture = self.texture.get_or_insert_with(|| {
+            let label = "SDF Glyph Atlas";
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let options = TextureOptions {
+                srgb: false,
+                ..TextureOptions::default()
+            };
+            let sampler = Texture::create_sampler(device, label, options, 1);
+            let bind_group = Texture::create_bind_group(
+                device,
+                &graphics_state.texture_bind_group_layout,
+                label,
+                &view,
+                &sampler,
+            );
+            (texture, bind_group)
+        });
+
+        if let Some((start, end)) = self.dirty_rows.take() {
+            // whole rows, so the data doesn't have to be repacked
+            graphics_state.queue.write_texture(
+                wgpu::TextureCopyView {
+                    texture: &texture.0,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: start,
+                        z: 0,
+                    },
+                },
+                &self.pixels[(start * size) as usize..(end * size) as usize],
+                wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: size,
+                    rows_per_image: end - start,
+                },
+                wgpu::Extent3d {
+                    width: size,
+                    height: end - start,
+                    depth: 1,
+                },
+            );
+        }
+    }
+}
+
+pub(crate) struct SdfTextRenderer {
+    atlas: SdfAtlas,
+    pipelines: PipelineCache,
+    world_uniform: ViewUniform,
+    screen_uniform: ViewUniform,
+    buffer: DynamicBuffer,
+    // world then screen, so screen text is drawn over world text
+    world_vertices: Vec<SdfVertex>,
+    screen_vertices: Vec<SdfVertex>,
+    world_batches: Vec<Batch>,
+    screen_batches: Vec<Batch>,
+}
+
+// vertices in a row that are cut off by the same clip rect and drawn with the same blend mode
+struct Batch {
+    vertices: usize,
+    clip: Option<Rect>,
+    blend_mode: BlendMode,
+}
+
+// new
+impl SdfTextRenderer {
+    pub fn new(graphics_state: &mut GraphicsState) -> Self {
+        let world_uniform = ViewUniform::new(&graphics_state.device);
+        let screen_uniform = ViewUniform::new(&graphics_state.device);
+
+        let pipelines = {
+            let (vs_module, fs_module) = crate::shader_compilation::modules_or_error(
+                &mut graphics_state.shader_compiler,
+                &graphics_state.device,
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../shader_sdf_text_vert.glsl"),
+                    filename: "shader_sdf_text_vert.glsl",
+                    label: "SDF Text Vertex Shader",
+                },
+                crate::shader_compilation::ShaderSource {
+                    src: include_str!("../shader_sdf_text_frag.glsl"),
+                    filename: "shader_sdf_text_frag.glsl",
+                    label: "SDF Text Fragment Shader",
+                },
+                crate::shader_compilation::ErrorVertex::View,
+            );
+
+            let render_pipeline_layout =
+                graphics_state
+                    .device
+                    .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some("SDF Text Pipeline Layout"),
+                        bind_group_layouts: &[
+                            &world_uniform.bind_group_layout,
+                            &graphics_state.texture_bind_group_layout,
+                        ],
+                        push_constant_ranges: &[],
+                    });
+
+            PipelineCache::new(
+                "SDF Text Pipeline",
+                render_pipeline_layout,
+                vs_module,
+                fs_module,
+                vec![SdfVertex::descriptor()],
+                graphics_state.swap_chain_descriptor.format,
+            )
+        };
+
+        let buffer = DynamicBuffer::new(
+            &graphics_state.device,
+            "SDF Text Vertex Buffer",
+            wgpu::BufferUsage::VERTEX,
+            1024,
+        );
+
+        SdfTextRenderer {
+            atlas: SdfAtlas::new(),
+            pipelines,
+            world_uniform,
+            screen_uniform,
+            buffer,
+            world_vertices: vec![],
+            screen_vertices: vec![],
+            world_batches: vec![],
+            screen_batches: vec![],
+        }
+    }
+}
+
+// queue
+impl SdfTextRenderer {
+    // laid out like RendererGlyph lays out raster text, in the units of the text's space
+    pub fn queue(
+        &mut self,
+        text: &Text,
+        effects: &SdfEffects,
+        fonts: &[FontArc],
+        variants: FontVariants,
+        layout: &TextLayout,
+    ) {
+        let section = text.section(1.0, variants);
+        let geometry = SectionGeometry::from(&section);
+        let glyphs = layout.calculate_glyphs(fonts, &geometry, &section.text);
+
+        let (vertices, batches) = match text.space {
+            TextSpace::World => (&mut self.world_vertices, &mut self.world_batches),
+            TextSpace::Screen => (&mut self.screen_vertices, &mut self.screen_batches),
+        };
+        let transform = text.transform.get_matrix4();
+        let start = vertices.len();
+        for section_glyph in glyphs {
+            let glyph = match self
+                .atlas
+                .glyph(fonts, section_glyph.font_id, section_glyph.glyph.id)
+            {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+
+            // from atlas pixels to the text's units
+            let scale = (
+                section_glyph.glyph.scale.x / BASE_SIZE,
+                section_glyph.glyph.scale.y / BASE_SIZE,
+            );
+            let origin = section_glyph.glyph.position;
+            let mut min = (
+                origin.x + glyph.left * scale.0,
+                origin.y + glyph.top * scale.1,
+            );
+            let mut max = (
+                min.0 + glyph.width as f32 * scale.0,
+                min.1 + glyph.height as f32 * scale.1,
+            );
+            let uv_rect = [
+                glyph.x as f32,
+                glyph.y as f32,
+                (glyph.x + glyph.width) as f32,
+                (glyph.y + glyph.height) as f32,
+            ];
+            let mut uv_min = (uv_rect[0], uv_rect[1]);
+            let mut uv_max = (uv_rect[2], uv_rect[3]);
+
+            // the quad grows towards the shadow, past the glyph's rect in the atlas
+            let shadow_offset = (
+                effects.shadow_offset.0 / scale.0,
+                effects.shadow_offset.1 / scale.1,
+            );
+            if effects.shadow_color[3] > 0.0 {
+                if shadow_offset.0 < 0.0 {
+                    min.0 += effects.shadow_offset.0;
+                    uv_min.0 += shadow_offset.0;
+                } else {
+                    max.0 += effects.shadow_offset.0;
+                    uv_max.0 += shadow_offset.0;
+                }
+                if shadow_offset.1 < 0.0 {
+                    min.1 += effects.shadow_offset.1;
+                    uv_min.1 += shadow_offset.1;
+                } else {
+                    max.1 += effects.shadow_offset.1;
+                    uv_max.1 += shadow_offset.1;
+                }
+            }
+
+            // from the text's units to distance field units, 0.5 is the whole spread
+            let to_distance = |width: f32| (width / scale.1 / (2.0 * SPREAD)).clamp(0.0, 0.5);
+            let outline_width = to_distance(effects.outline_width);
+            let widths = [
+                outline_width,
+                to_distance(effects.glow_width).min(0.5 - outline_width),
+                to_distance(effects.shadow_softness).min(0.5 - outline_width),
+            ];
+
+            let color = section.text[section_glyph.section_index].extra.color;
+            let vertex = |position: (f32, f32), uv: (f32, f32)| SdfVertex {
+                position: {
+                    let position =
+                        transform.transform_point(cgmath::Point3::new(position.0, position.1, 0.0));
+                    [position.x, position.y]
+                },
+                uv: [uv.0, uv.1],
+                uv_rect,
+                color,
+                outline_color: effects.outline_color,
+                shadow_color: effects.shadow_color,
+                glow_color: effects.glow_color,
+                widths,
+                shadow_offset: [shadow_offset.0, shadow_offset.1],
+            };
+            let top_left = vertex(min, uv_min);
+            let top_right = vertex((max.0, min.1), (uv_max.0, uv_min.1));
+            let bottom_left = vertex((min.0, max.1), (uv_min.0, uv_max.1));
+            let bottom_right = vertex(max, uv_max);
+            vertices.extend_from_slice(&[
+                top_left,
+                bottom_left,
+                bottom_right,
+                top_left,
+                bottom_right,
+                top_right,
+            ]);
+        }
+
+        // texts without glyphs, like blank ones, don't need a batch
+        let count = vertices.len() - start;
+        push_batch(batches, count, text.clip, text.blend_mode);
+    }
+
+    // a solid rectangle in the units of `space`, drawn in order with the texts
+    pub fn queue_rect(
+        &mut self,
+        rect: Rect,
+        color: [f32; 4],
+        space: TextSpace,
+        clip: Option<Rect>,
+        blend_mode: BlendMode,
+    ) {
+        let (vertices, batches) = match space {
+            TextSpace::World => (&mut self.world_vertices, &mut self.world_batches),
+            TextSpace::Screen => (&mut self.screen_vertices, &mut self.screen_batches),
+        };
+        // an empty rect in the atlas tells the shader it's solid
+        let vertex = |x: f32, y: f32| SdfVertex {
+            position: [x, y],
+            uv: [0.0, 0.0],
+            uv_rect: [0.0; 4],
+            color,
+            outline_color: [0.0; 4],
+            shadow_color: [0.0; 4],
+            glow_color: [0.0; 4],
+            widths: [0.0; 3],
+            shadow_offset: [0.0; 2],
+        };
+        let top_left = vertex(rect.left, rect.top);
+        let bottom_right = vertex(rect.right(), rect.bottom());
+        vertices.extend_from_slice(&[
+            top_left,
+            vertex(rect.left, rect.bottom()),
+            bottom_right,
+            top_left,
+            bottom_right,
+            vertex(rect.right(), rect.top),
+        ]);
+        push_batch(batches, 6, clip, blend_mode);
+    }
+}
+
+fn push_batch(batches: &mut Vec<Batch>, count: usize, clip: Option<Rect>, blend_mode: BlendMode) {
+    if count == 0 {
+        return;
+    }
+    match batches.last_mut() {
+        Some(batch) if batch.clip == clip && batch.blend_mode == blend_mode => {
+            batch.vertices += count
+        }
+        _ => batches.push(Batch {
+            vertices: count,
+            clip,
+            blend_mode,
+        }),
+    }
+}
+
+// draw
+impl SdfTextRenderer {
+    // draws and forgets what was queued
+    pub fn draw(&mut self, current_frame: &mut CurrentFrame, view: &SfView) {
+        if self.world_vertices.is_empty() && self.screen_vertices.is_empty() {
+            self.world_batches.clear();
+            self.screen_batches.clear();
+            return;
+        }
+
+        let graphics_state = &*current_frame.graphics_state;
+        let device = &graphics_state.device;
+        let queue = &graphics_state.queue;
+        self.atlas.prepare(graphics_state);
+
+        self.world_vertices.append(&mut self.screen_vertices);
+        self.buffer
+            .write(device, queue, bytemuck::cast_slice(&self.world_vertices));
+        self.world_vertices.clear();
+        let world_batches = std::mem::take(&mut self.world_batches);
+        let screen_batches = std::mem::take(&mut self.screen_batches);
+
+        let window_inner_size = graphics_state.window_inner_size();
+        let (width, height) = (
+            window_inner_size.width as f32,
+            window_inner_size.height as f32,
+        );
+        self.world_uniform.write(queue, view);
+        self.screen_uniform.write(
+            queue,
+            &SfView {
+                center: (width / 2.0, height / 2.0),
+                size: (width, height),
+                rotation: 0.0,
+            },
+        );
+        let pipeline_key =
+            |batch: &Batch| (batch.blend_mode, wgpu::PrimitiveTopology::TriangleList);
+        for batch in world_batches.iter().chain(&screen_batches) {
+            self.pipelines.prepare(device, pipeline_key(batch));
+        }
+
+        let mut render_pass =
+            current_frame
+                .encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("SDF Text Render Pass"),
+                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: &current_frame.frame.output.view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+
+        render_pass.set_bind_group(1, &self.atlas.texture.as_ref().unwrap().1, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice());
+        let window_size = (window_inner_size.width, window_inner_size.height);
+        let mut start = 0;
+        let mut current_key = None;
+        for (space, batches, uniform) in [
+            (TextSpace::World, &world_batches, &self.world_uniform),
+            (TextSpace::Screen, &screen_batches, &self.screen_uniform),
+        ] {
+            render_pass.set_bind_group(0, &uniform.bind_group, &[]);
+            for batch in batches {
+                let vertices = start as u32..(start + batch.vertices) as u32;
+                start += batch.vertices;
+                let [x, y, width, height] = match batch.clip {
+                    None => [0, 0, window_size.0, window_size.1],
+                    Some(clip) => match scissor_rect(clip, space, view, window_size) {
+                        Some(scissor_rect) => scissor_rect,
+                        // clipped out of the window entirely
+                        None => continue,
+                    },
+                };
+                if current_key != Some(pipeline_key(batch)) {
+                    render_pass.set_pipeline(self.pipelines.get(pipeline_key(batch)));
+                    current_key = Some(pipeline_key(batch));
+                }
+                render_pass.set_scissor_rect(x, y, width, height);
+                render_pass.draw(vertices, 0..1);
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::graphics::Rect;
+
 // reimplementation of <https://www.sfml-dev.org/documentation/2.5.1/classsf_1_1View.php>
 pub struct SfView {
     pub center: (f32, f32),
@@ -45,4 +47,27 @@ impl SfView {
 
         m4
     }
+
+    // axis-aligned bounds of what the view shows, in world units (bigger than size when rotated)
+    pub fn visible_rect(&self) -> Rect {
+        let angle = self.rotation.to_radians();
+        let (sine, cosine) = angle.sin_cos();
+        let width = (self.size.0 * cosine).abs() + (self.size.1 * sine).abs();
+        let height = (self.size.0 * sine).abs() + (self.size.1 * cosine).abs();
+        Rect::new(
+            self.center.0 - width * 0.5,
+            self.center.1 - height * 0.5,
+            width,
+            height,
+        )
+    }
+
+    // the window pixel `point` is drawn at, in a window of `window_size` pixels
+    pub fn world_to_window(&self, point: (f32, f32), window_size: (f32, f32)) -> (f32, f32) {
+        let ndc = self.get_matrix4() * cgmath::Vector4::new(point.0, point.1, 0.0, 1.0);
+        (
+            (ndc.x + 1.0) / 2.0 * window_size.0,
+            (1.0 - ndc.y) / 2.0 * window_size.1,
+        )
+    }
 }
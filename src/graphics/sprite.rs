@@ -0,0 +1,90 @@
+// reimplementation of <https://www.sfml-dev.org/documentation/2.5.1/classsf_1_1Sprite.php>
+use crate::graphics::{Rect, Texture, Transformable};
+use crate::vertex::TexturedVertex;
+use std::rc::Rc;
+
+#[derive(Clone)]
+pub struct Sprite {
+    pub texture: Rc<Texture>,
+    // in pixels, the whole texture if None
+    pub texture_rect: Option<Rect>,
+    // multiplied with the texture's colors
+    pub color: [f32; 4],
+    pub transformable: Transformable,
+}
+
+impl Sprite {
+    pub fn new(texture: Rc<Texture>) -> Self {
+        Sprite {
+            texture,
+            texture_rect: None,
+            color: [1.0, 1.0, 1.0, 1.0],
+            transformable: Transformable::default(),
+        }
+    }
+
+    pub fn texture_rect(&self) -> Rect {
+        self.texture_rect.unwrap_or_else(|| {
+            let (width, height) = self.texture.size();
+            Rect::new(0.0, 0.0, width as f32, height as f32)
+        })
+    }
+
+    // before the transform
+    pub fn local_bounds(&self) -> Rect {
+        let rect = self.texture_rect();
+        Rect::new(0.0, 0.0, rect.width.abs(), rect.height.abs())
+    }
+
+    // two triangles, in world space
+    pub fn vertices(&self) -> [TexturedVertex; 6] {
+        let bounds = self.local_bounds();
+        quad_vertices(
+            &self.texture,
+            &self.transformable,
+            bounds,
+            self.texture_rect(),
+            self.color,
+        )
+    }
+}
+
+// a `local` rectangle showing the `texture_rect` part of `texture`, transformed by `transformable`
+pub fn quad_vertices(
+    texture: &Texture,
+    transformable: &Transformable,
+    local: Rect,
+    texture_rect: Rect,
+    color: [f32; 4],
+) -> [TexturedVertex; 6] {
+    let (texture_width, texture_height) = texture.size();
+    let u0 = texture_rect.left / texture_width as f32;
+    let v0 = texture_rect.top / texture_height as f32;
+    let u1 = texture_rect.right() / texture_width as f32;
+    let v1 = texture_rect.bottom() / texture_height as f32;
+
+    let m = transformable.get_matrix4();
+    let corner = |x: f32, y: f32, u: f32, v: f32| {
+        use cgmath::Transform;
+        let p = m.transform_point(cgmath::Point3::new(x, y, 0.0));
+        TexturedVertex {
+            position: [p.x, p.y],
+            uv: [u, v],
+            color,
+        }
+    };
+
+    let top_left = corner(local.left, local.top, u0, v0);
+    let top_right = corner(local.right(), local.top, u1, v0);
+    let bottom_right = corner(local.right(), local.bottom(), u1, v1);
+    let bottom_left = corner(local.left, local.bottom(), u0, v1);
+
+    [
+        top_left,
+        top_right,
+        bottom_right,
+        top_left,
+        bottom_right,
+        bottom_left,
+    ]
+}
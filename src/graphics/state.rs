@@ -1,5 +1,8 @@
 use crate::frame_counter::FrameCounter;
+use crate::graphics::mipmaps::MipmapGenerator;
+use crate::graphics::Texture;
 use std::cell::RefCell;
+use std::rc::Rc;
 
 pub struct GraphicsState {
     pub window: winit::window::Window,
@@ -10,6 +13,12 @@ pub struct GraphicsState {
     pub(super) swap_chain_descriptor: wgpu::SwapChainDescriptor,
     pub(super) swap_chain: wgpu::SwapChain,
 
+    pub(super) texture_bind_group_layout: wgpu::BindGroupLayout,
+    pub(super) texture_array_bind_group_layout: wgpu::BindGroupLayout,
+    pub(super) mipmap_generator: MipmapGenerator,
+    // created the first time something is missing
+    pub(super) placeholder_texture: RefCell<Option<Rc<Texture>>>,
+
     pub(super) staging_belt: wgpu::util::StagingBelt,
     pub(super) local_pool: futures::executor::LocalPool,
     pub(super) local_spawner: futures::executor::LocalSpawner,
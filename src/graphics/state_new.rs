@@ -1,5 +1,7 @@
 use crate::frame_counter::FrameCounter;
+use crate::graphics::mipmaps::MipmapGenerator;
 use crate::graphics::state::GraphicsState;
+use crate::graphics::Texture;
 use std::cell::RefCell;
 
 // new
@@ -19,10 +21,16 @@ impl GraphicsState {
             }))
             .unwrap();
 
+        // compressed textures are decoded on the cpu when these aren't available
+        let features = adapter.features()
+            & (wgpu::Features::TEXTURE_COMPRESSION_BC
+                | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+                | wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR);
+
         let (device, queue) = futures::executor::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
-                features: wgpu::Features::empty(),
+                features,
                 limits: wgpu::Limits::default(),
             },
             None, // Trace path
@@ -38,7 +46,21 @@ impl GraphicsState {
         };
         let swap_chain = device.create_swap_chain(&surface, &swap_chain_descriptor);
 
-        let shader_compiler = shaderc::Compiler::new().unwrap();
+        let texture_bind_group_layout = Texture::bind_group_layout(
+            &device,
+            "texture_bind_group_layout",
+            wgpu::TextureViewDimension::D2,
+        );
+        let texture_array_bind_group_layout = Texture::bind_group_layout(
+            &device,
+            "texture_array_bind_group_layout",
+            wgpu::TextureViewDimension::D2Array,
+        );
+
+        let mut shader_compiler = shaderc::Compiler::new().unwrap();
+
+        let mipmap_generator =
+            MipmapGenerator::new(&device, &mut shader_compiler, &texture_bind_group_layout);
 
         let local_pool = futures::executor::LocalPool::new();
         let local_spawner = local_pool.spawner();
@@ -52,6 +74,11 @@ impl GraphicsState {
             swap_chain_descriptor,
             swap_chain,
 
+            texture_bind_group_layout,
+            texture_array_bind_group_layout,
+            mipmap_generator,
+            placeholder_texture: RefCell::new(None),
+
             staging_belt: wgpu::util::StagingBelt::new(1024),
             local_pool,
             local_spawner,
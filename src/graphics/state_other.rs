@@ -1,3 +1,4 @@
+use crate::frame_counter::FrameCounter;
 use crate::graphics::state::GraphicsState;
 
 // other
@@ -29,4 +30,8 @@ impl GraphicsState {
     pub fn window_inner_size(&self) -> winit::dpi::PhysicalSize<u32> {
         self.window.inner_size()
     }
+
+    pub fn frame_counter(&self) -> &FrameCounter {
+        &self.frame_counter
+    }
 }
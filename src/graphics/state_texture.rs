@@ -0,0 +1,146 @@
+use crate::graphics::compressed_texture::{CompressedImage, CompressedTextureError};
+use crate::graphics::state::GraphicsState;
+use crate::graphics::texture::{Texture, TextureOptions};
+use std::rc::Rc;
+
+// textures
+impl GraphicsState {
+    // shared, magenta and black
+    pub fn placeholder_texture(&self) -> Rc<Texture> {
+        self.placeholder_texture
+            .borrow_mut()
+            .get_or_insert_with(|| Rc::new(Texture::placeholder(self)))
+            .clone()
+    }
+
+    // .dds and .ktx2 files are loaded as compressed textures, everything else as images;
+    // when that fails the cause is logged and the placeholder texture is returned instead
+    pub fn load_texture_or_placeholder<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        options: TextureOptions,
+    ) -> Rc<Texture> {
+        let path = path.as_ref();
+        let compressed = path.extension().map_or(false, |extension| {
+            extension.eq_ignore_ascii_case("dds") || extension.eq_ignore_ascii_case("ktx2")
+        });
+        let texture = if compressed {
+            self.create_texture_from_compressed_path(path, options)
+                .map_err(|e| e.to_string())
+        } else {
+            self.create_texture_from_path(path, options)
+                .map_err(|e| e.to_string())
+        };
+
+        match texture {
+            Ok(texture) => Rc::new(texture),
+            Err(e) => {
+                log::error!("couldn't load {}: {}", path.display(), e);
+                self.placeholder_texture()
+            }
+        }
+    }
+
+    // returns the error, see load_texture_or_placeholder for the fallback
+    pub fn create_texture_from_path<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        options: TextureOptions,
+    ) -> Result<Texture, image::ImageError> {
+        let path = path.as_ref();
+        let image = image::open(path)?;
+        self.create_texture(&path.to_string_lossy(), &image, options)
+    }
+
+    pub fn create_texture_from_bytes(
+        &self,
+        bytes: &[u8],
+        options: TextureOptions,
+    ) -> Result<Texture, image::ImageError> {
+        let image = image::load_from_memory(bytes)?;
+        self.create_texture("texture from bytes", &image, options)
+    }
+
+    pub fn create_texture_from_image(
+        &self,
+        image: &image::DynamicImage,
+        options: TextureOptions,
+    ) -> Result<Texture, image::ImageError> {
+        self.create_texture("texture from image", image, options)
+    }
+
+    fn create_texture(
+        &self,
+        label: &str,
+        image: &image::DynamicImage,
+        options: TextureOptions,
+    ) -> Result<Texture, image::ImageError> {
+        let rgba = image.to_rgba8();
+        Texture::new(self, label, rgba.dimensions(), &rgba, options)
+    }
+
+    // .dds or .ktx2, uploaded as is when the device supports the format, decoded otherwise;
+    // mipmaps are only generated for decoded images that come without them
+    pub fn create_texture_from_compressed_path<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        options: TextureOptions,
+    ) -> Result<Texture, CompressedTextureError> {
+        let path = path.as_ref();
+        let image = CompressedImage::from_bytes(&std::fs::read(path)?)?;
+        self.create_texture_from_compressed_image(&path.to_string_lossy(), &image, options)
+    }
+
+    pub fn create_texture_from_compressed_bytes(
+        &self,
+        bytes: &[u8],
+        options: TextureOptions,
+    ) -> Result<Texture, CompressedTextureError> {
+        let image = CompressedImage::from_bytes(bytes)?;
+        self.create_texture_from_compressed_image("compressed texture from bytes", &image, options)
+    }
+
+    pub fn create_texture_from_compressed_image(
+        &self,
+        label: &str,
+        image: &CompressedImage,
+        options: TextureOptions,
+    ) -> Result<Texture, CompressedTextureError> {
+        let info = image.format.describe();
+        if self.device.features().contains(info.required_features) && !image.needs_opaque_decode() {
+            return Ok(Texture::from_levels(
+                self,
+                label,
+                image.size,
+                image.format,
+                &image.levels,
+                options,
+            ));
+        }
+
+        log::info!(
+            "{:?} isn't supported by the device or needs to be opaque, decoding {} on the cpu",
+            image.format,
+            label
+        );
+        let levels = image.decompress()?;
+        // the file decides the color space, not the options
+        let options = TextureOptions {
+            srgb: info.srgb,
+            ..options
+        };
+        if levels.len() == 1 {
+            Texture::new(self, label, image.size, &levels[0], options)
+                .map_err(|e| CompressedTextureError::Unsupported(e.to_string()))
+        } else {
+            let format = if info.srgb {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            };
+            Ok(Texture::from_levels(
+                self, label, image.size, format, &levels, options,
+            ))
+        }
+    }
+}
@@ -0,0 +1,876 @@
+// text for RendererGlyph, queued each frame or retained by the renderer
+#[cfg(feature = "shaping")]
+use crate::graphics::text_shaping::Shaper;
+use crate::graphics::{text_markup, BlendMode, Rect, SfView, Transformable};
+use glyph_brush::ToSectionText;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use wgpu_glyph::ab_glyph::{self, Font, ScaleFont};
+use wgpu_glyph::{
+    BuiltInLineBreaker, FontId, GlyphPositioner, HorizontalAlign, Layout, Section, SectionGeometry,
+    SectionGlyph, SectionText, VerticalAlign,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextSpace {
+    // in window pixels, drawn over everything else
+    Screen,
+    // in SfView units, zooming and rotating with the view
+    World,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text {
+    pub string: String,
+    pub font: FontId,
+    // in the units of `space`, like position and wrap_width
+    pub size: f32,
+    pub color: [f32; 4],
+    pub space: TextSpace,
+    // the anchor the alignment is relative to, in the units of `space`
+    pub position: (f32, f32),
+    // moves, rotates and scales the laid out text, around the transform's origin in the units
+    // of `space`; metrics, hit tests and `clip` are without it
+    pub transform: Transformable,
+    pub horizontal_align: HorizontalAlign,
+    pub vertical_align: VerticalAlign,
+    // lines longer than this are broken between words, otherwise only at '\n'
+    pub wrap_width: Option<f32>,
+    // multiplies the font's line height
+    pub line_spacing: f32,
+    // in order, without overlaps, others are fixed up when drawn; the rest of the string has
+    // the style above
+    pub styles: Vec<TextStyle>,
+    pub rendering: TextRendering,
+    // shaped, and reordered where it's right-to-left, as scripts like Arabic and Devanagari
+    // need it; only with the `shaping` feature, otherwise it's laid out character by character
+    pub shaping: bool,
+    // the text is cut off outside of it, in the units of `space`; in a rotated view, outside of
+    // its axis-aligned bounds on screen
+    pub clip: Option<Rect>,
+    // wgpu_glyph only alpha blends, raster text with any other mode is drawn like Sdf text
+    // without effects
+    pub blend_mode: BlendMode,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextRendering {
+    // glyphs rasterized at their size on screen, the sharpest for small, unrotated text
+    Raster,
+    // signed distance fields, they stay crisp when scaled and rotated and can have effects
+    Sdf(SdfEffects),
+}
+
+// in the units of the text's space, limited to a few pixels at the font's size by the
+// distance fields' spread; transparent colors turn an effect off
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SdfEffects {
+    pub outline_width: f32,
+    pub outline_color: [f32; 4],
+    pub shadow_offset: (f32, f32),
+    // blurs the shadow's edge by this much
+    pub shadow_softness: f32,
+    pub shadow_color: [f32; 4],
+    // fades out around the outline, or the glyphs without one
+    pub glow_width: f32,
+    pub glow_color: [f32; 4],
+}
+
+// overrides the Text's style for a part of its string
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    // in bytes
+    pub range: Range<usize>,
+    pub color: Option<[f32; 4]>,
+    pub size: Option<f32>,
+    // switch to the text's font's variants, when they're registered with RendererGlyph
+    pub bold: bool,
+    pub italic: bool,
+}
+
+// the fonts bold and italic styles of a font switch to, missing ones fall back to the regular font
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FontVariants {
+    pub bold: Option<FontId>,
+    pub italic: Option<FontId>,
+    pub bold_italic: Option<FontId>,
+}
+
+impl FontVariants {
+    fn pick(&self, regular: FontId, bold: bool, italic: bool) -> FontId {
+        let variant = match (bold, italic) {
+            (false, false) => None,
+            (true, false) => self.bold,
+            (false, true) => self.italic,
+            (true, true) => self.bold_italic.or(self.bold).or(self.italic),
+        };
+        variant.unwrap_or(regular)
+    }
+}
+
+impl Text {
+    pub fn new<S: Into<String>>(string: S) -> Self {
+        Text {
+            string: string.into(),
+            font: FontId(0),
+            size: 16.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            space: TextSpace::Screen,
+            position: (0.0, 0.0),
+            transform: Transformable::default(),
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Top,
+            wrap_width: None,
+            line_spacing: 1.0,
+            styles: Vec::new(),
+            rendering: TextRendering::Raster,
+            shaping: false,
+            clip: None,
+            blend_mode: BlendMode::Alpha,
+        }
+    }
+
+    // see text_markup.rs for the tags
+    pub fn from_markup(markup: &str) -> Self {
+        let (string, styles) = text_markup::parse(markup);
+        Text {
+            styles,
+            ..Text::new(string)
+        }
+    }
+
+    // the string split where the style changes; style ranges are cut to the string and to
+    // whole characters, reversed ones are skipped and overlapping ones start where the
+    // previous one ends
+    pub(crate) fn spans(&self) -> Vec<(Range<usize>, Option<&TextStyle>)> {
+        let char_start = |index: usize| {
+            let mut index = index.min(self.string.len());
+            while !self.string.is_char_boundary(index) {
+                index -= 1;
+            }
+            index
+        };
+        let mut styles: Vec<(Range<usize>, &TextStyle)> = self
+            .styles
+            .iter()
+            .map(|style| {
+                (
+                    char_start(style.range.start)..char_start(style.range.end),
+                    style,
+                )
+            })
+            .filter(|(range, _)| range.start < range.end)
+            .collect();
+        styles.sort_by_key(|(range, _)| range.start);
+
+        let mut spans = Vec::with_capacity(styles.len() * 2 + 1);
+        let mut start = 0;
+        for (range, style) in styles {
+            if range.end <= start {
+                continue;
+            }
+            if range.start > start {
+                spans.push((start..range.start, None));
+            }
+            spans.push((range.start.max(start)..range.end, Some(style)));
+            start = range.end;
+        }
+        if start < self.string.len() || spans.is_empty() {
+            spans.push((start..self.string.len(), None));
+        }
+        spans
+    }
+
+    // `scale` multiplies everything in pixels, for world text rasterized at the view's zoom
+    pub(crate) fn section(&self, scale: f32, variants: FontVariants) -> Section<'_> {
+        let text = self
+            .spans()
+            .into_iter()
+            .map(|(range, style)| {
+                let (font, size, color) = match style {
+                    Some(style) => (
+                        variants.pick(self.font, style.bold, style.italic),
+                        style.size.unwrap_or(self.size),
+                        style.color.unwrap_or(self.color),
+                    ),
+                    None => (self.font, self.size, self.color),
+                };
+                wgpu_glyph::Text::new(&self.string[range])
+                    .with_font_id(font)
+                    .with_scale(size * scale)
+                    .with_color(color)
+            })
+            .collect();
+        Section::default()
+            .with_screen_position((self.position.0 * scale, self.position.1 * scale))
+            .with_bounds((
+                self.wrap_width.map_or(f32::INFINITY, |width| width * scale),
+                f32::INFINITY,
+            ))
+            .with_text(text)
+    }
+
+    pub(crate) fn metrics<F: Font>(
+        &self,
+        fonts: &[F],
+        variants: FontVariants,
+        layout: &TextLayout,
+    ) -> TextMetrics {
+        let section = self.section(1.0, variants);
+        let geometry = SectionGeometry::from(&section);
+        let glyphs = layout.laid_out_glyphs(fonts, &geometry, &section.text);
+        // glyph byte indices are in their span
+        let span_starts: Vec<usize> = self.spans().iter().map(|(range, _)| range.start).collect();
+        let font = fonts[self.font.0].as_scaled(self.size);
+        let (ascent, descent) = (font.ascent(), font.descent());
+
+        // glyphs are laid out in order, lines end at a '\n' or where the baseline moves;
+        // line breaks and empty lines don't have glyphs
+        let mut lines = Vec::new();
+        let mut glyph_metrics = Vec::with_capacity(glyphs.len());
+        let mut glyph_iter = glyphs
+            .iter()
+            .map(|glyph| {
+                (
+                    span_starts[glyph.glyph.section_index] + glyph.glyph.byte_index,
+                    glyph,
+                )
+            })
+            .peekable();
+        let mut start = 0;
+        for hard_line in self.string.split('\n') {
+            let end = start + hard_line.len();
+            let mut line_start = start;
+            let mut baseline = None;
+            while let Some((byte_index, laid_out)) =
+                glyph_iter.next_if(|(byte_index, _)| *byte_index < end)
+            {
+                let glyph = &laid_out.glyph;
+                let y = glyph.glyph.position.y;
+                if baseline.map_or(false, |baseline| baseline != y) {
+                    lines.push((line_start..byte_index, baseline));
+                    line_start = byte_index;
+                }
+                baseline = Some(y);
+                // styled spans can have another font or size
+                let glyph_font = fonts[glyph.font_id.0].as_scaled(glyph.glyph.scale);
+                glyph_metrics.push(GlyphMetrics {
+                    byte_index,
+                    line: lines.len(),
+                    rect: Rect::new(
+                        glyph.glyph.position.x,
+                        y - glyph_font.ascent(),
+                        laid_out.advance,
+                        glyph_font.ascent() - glyph_font.descent(),
+                    ),
+                    rtl: laid_out.rtl,
+                });
+            }
+            let line_end = end - hard_line.ends_with('\r') as usize;
+            lines.push((line_start..line_end, baseline));
+            start = end + 1;
+        }
+
+        // empty lines have the text's own line height, and follow the line above them
+        let line_advance = font.height() + font.line_gap();
+        let mut known = lines
+            .iter()
+            .enumerate()
+            .find_map(|(line, (_, baseline))| baseline.map(|baseline| (line, baseline)))
+            .unwrap_or_else(|| (0, self.first_baseline(lines.len(), ascent, line_advance)));
+        let line_advance = line_advance * self.line_spacing;
+        let mut glyph_iter = glyph_metrics.iter().peekable();
+        let lines: Vec<LineMetrics> = lines
+            .into_iter()
+            .enumerate()
+            .map(|(line, (range, baseline))| {
+                let baseline = match baseline {
+                    Some(baseline) => {
+                        known = (line, baseline);
+                        baseline
+                    }
+                    None => known.1 + (line as f32 - known.0 as f32) * line_advance,
+                };
+                let (mut left, mut right) = (f32::MAX, f32::MIN);
+                let (mut top, mut bottom) = (f32::MAX, f32::MIN);
+                while let Some(glyph) = glyph_iter.next_if(|glyph| glyph.line == line) {
+                    left = left.min(glyph.rect.left);
+                    right = right.max(glyph.rect.right());
+                    top = top.min(glyph.rect.top);
+                    bottom = bottom.max(glyph.rect.bottom());
+                }
+                if left > right {
+                    left = self.position.0;
+                    right = self.position.0;
+                    top = baseline - ascent;
+                    bottom = baseline - descent;
+                }
+                LineMetrics {
+                    range,
+                    baseline,
+                    rect: Rect::new(left, top, right - left, bottom - top),
+                }
+            })
+            .collect();
+
+        let left = lines
+            .iter()
+            .map(|line| line.rect.left)
+            .fold(f32::MAX, f32::min);
+        let right = lines
+            .iter()
+            .map(|line| line.rect.right())
+            .fold(f32::MIN, f32::max);
+        let top = lines
+            .iter()
+            .map(|line| line.rect.top)
+            .fold(f32::MAX, f32::min);
+        let bottom = lines
+            .iter()
+            .map(|line| line.rect.bottom())
+            .fold(f32::MIN, f32::max);
+        TextMetrics {
+            glyphs: glyph_metrics,
+            lines,
+            bounds: Rect::new(left, top, right - left, bottom - top),
+        }
+    }
+
+    // where the first line would be if there were glyphs to lay out, like the layout does it
+    fn first_baseline(&self, line_count: usize, ascent: f32, line_advance: f32) -> f32 {
+        let height = line_count as f32 * line_advance;
+        let top = match self.vertical_align {
+            VerticalAlign::Top => self.position.1,
+            VerticalAlign::Center => self.position.1 - height / 2.0,
+            VerticalAlign::Bottom => self.position.1 - height,
+        };
+        let first = top + ascent;
+        let last = first + (line_count - 1) as f32 * line_advance;
+        let anchor = match self.vertical_align {
+            VerticalAlign::Top => first,
+            VerticalAlign::Center => (first + last) / 2.0,
+            VerticalAlign::Bottom => last,
+        };
+        anchor + (first - anchor) * self.line_spacing
+    }
+
+    pub(crate) fn layout(&self) -> TextLayout {
+        TextLayout {
+            layout: Layout::default_wrap()
+                .h_align(self.horizontal_align)
+                .v_align(self.vertical_align),
+            line_spacing: self.line_spacing,
+            fallbacks: Vec::new(),
+            #[cfg(feature = "shaping")]
+            shaper: None,
+        }
+    }
+}
+
+// a clip rect of a text in `space` in window pixels, grown to whole ones, as x, y, width and
+// height for scissoring; None when none of it is in the window
+pub(crate) fn scissor_rect(
+    clip: Rect,
+    space: TextSpace,
+    view: &SfView,
+    window_size: (u32, u32),
+) -> Option<[u32; 4]> {
+    let size = (window_size.0 as f32, window_size.1 as f32);
+    let clip = match space {
+        TextSpace::Screen => clip,
+        TextSpace::World => {
+            let corners = [
+                (clip.left, clip.top),
+                (clip.right(), clip.top),
+                (clip.left, clip.bottom()),
+                (clip.right(), clip.bottom()),
+            ]
+            .iter()
+            .map(|&corner| view.world_to_window(corner, size))
+            .collect::<Vec<_>>();
+            let (left, top) = corners.iter().fold((f32::MAX, f32::MAX), |min, corner| {
+                (min.0.min(corner.0), min.1.min(corner.1))
+            });
+            let (right, bottom) = corners.iter().fold((f32::MIN, f32::MIN), |max, corner| {
+                (max.0.max(corner.0), max.1.max(corner.1))
+            });
+            Rect::new(left, top, right - left, bottom - top)
+        }
+    };
+    let clip = clip.intersection(&Rect::new(0.0, 0.0, size.0, size.1))?;
+    let (left, top) = (clip.left.floor(), clip.top.floor());
+    let (right, bottom) = (clip.right().ceil(), clip.bottom().ceil());
+    Some([
+        left as u32,
+        top as u32,
+        (right - left) as u32,
+        (bottom - top) as u32,
+    ])
+}
+
+// a glyph with what metrics need and SectionGlyph doesn't have
+pub(crate) struct LaidOutGlyph {
+    pub(crate) glyph: SectionGlyph,
+    // shaped, or the font's advance
+    pub(crate) advance: f32,
+    pub(crate) rtl: bool,
+}
+
+// the built-in wrapping layout, or the shaping one, with the lines moved apart (or together)
+// afterwards
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TextLayout {
+    layout: Layout<BuiltInLineBreaker>,
+    line_spacing: f32,
+    // tried in order for the characters a section's font doesn't have
+    fallbacks: Vec<FontId>,
+    #[cfg(feature = "shaping")]
+    shaper: Option<Shaper>,
+}
+
+impl Hash for TextLayout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.layout.hash(state);
+        self.line_spacing.to_bits().hash(state);
+        self.fallbacks.hash(state);
+        #[cfg(feature = "shaping")]
+        self.shaper.is_some().hash(state);
+    }
+}
+
+impl TextLayout {
+    pub(crate) fn with_fallbacks(mut self, fallbacks: &[FontId]) -> Self {
+        self.fallbacks = fallbacks.to_vec();
+        self
+    }
+
+    #[cfg(feature = "shaping")]
+    pub(crate) fn with_shaper(mut self, shaper: Shaper) -> Self {
+        self.shaper = Some(shaper);
+        self
+    }
+
+    // the first of `font_id` and the fallbacks that has a glyph for `c`, or `font_id`
+    fn font_for<F: Font>(&self, fonts: &[F], font_id: FontId, c: char) -> FontId {
+        let has_glyph = |font_id: FontId| fonts[font_id.0].glyph_id(c).0 != 0;
+        if c.is_control() || has_glyph(font_id) {
+            return font_id;
+        }
+        self.fallbacks
+            .iter()
+            .copied()
+            .find(|&fallback| has_glyph(fallback))
+            .unwrap_or(font_id)
+    }
+
+    // the sections split where a fallback font takes over, laid out, and mapped back
+    fn fallback_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        let mut split = Vec::with_capacity(sections.len());
+        // the section each split one is from, and where it starts in it
+        let mut origins = Vec::with_capacity(sections.len());
+        for (section_index, section) in sections.iter().enumerate() {
+            let section = section.to_section_text();
+            let mut start = 0;
+            let mut font_id = section.font_id;
+            for (i, c) in section.text.char_indices() {
+                let font = self.font_for(fonts, section.font_id, c);
+                if font != font_id && i > start {
+                    split.push(SectionText {
+                        text: &section.text[start..i],
+                        font_id,
+                        ..section
+                    });
+                    origins.push((section_index, start));
+                    start = i;
+                }
+                font_id = font;
+            }
+            split.push(SectionText {
+                text: &section.text[start..],
+                font_id,
+                ..section
+            });
+            origins.push((section_index, start));
+        }
+
+        let mut glyphs = self.layout.calculate_glyphs(fonts, geometry, &split);
+        for glyph in &mut glyphs {
+            let (section_index, start) = origins[glyph.section_index];
+            glyph.section_index = section_index;
+            glyph.byte_index += start;
+        }
+        glyphs
+    }
+
+    // calculate_glyphs, with the glyphs' advances and directions
+    pub(crate) fn laid_out_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<LaidOutGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        #[cfg(feature = "shaping")]
+        let shaped = self.shaper.as_ref().map(|shaper| {
+            let align = match self.layout {
+                Layout::Wrap {
+                    h_align, v_align, ..
+                }
+                | Layout::SingleLine {
+                    h_align, v_align, ..
+                } => (h_align, v_align),
+            };
+            shaper.calculate_glyphs(fonts, geometry, sections, align, |font_id, c| {
+                self.font_for(fonts, font_id, c)
+            })
+        });
+        #[cfg(not(feature = "shaping"))]
+        let shaped: Option<Vec<LaidOutGlyph>> = None;
+
+        let mut glyphs = match shaped {
+            Some(glyphs) => glyphs,
+            None => {
+                let glyphs = if self.fallbacks.is_empty() {
+                    self.layout.calculate_glyphs(fonts, geometry, sections)
+                } else {
+                    self.fallback_glyphs(fonts, geometry, sections)
+                };
+                glyphs
+                    .into_iter()
+                    .map(|glyph| LaidOutGlyph {
+                        advance: fonts[glyph.font_id.0]
+                            .as_scaled(glyph.glyph.scale)
+                            .h_advance(glyph.glyph.id),
+                        rtl: false,
+                        glyph,
+                    })
+                    .collect()
+            }
+        };
+        if self.line_spacing == 1.0 || glyphs.is_empty() {
+            return glyphs;
+        }
+
+        // baselines are scaled away from the line that stays put
+        let (first, last) = glyphs
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(first, last), glyph| {
+                let y = glyph.glyph.glyph.position.y;
+                (first.min(y), last.max(y))
+            });
+        let anchor = match self.layout {
+            Layout::Wrap { v_align, .. } | Layout::SingleLine { v_align, .. } => match v_align {
+                VerticalAlign::Top => first,
+                VerticalAlign::Center => (first + last) / 2.0,
+                VerticalAlign::Bottom => last,
+            },
+        };
+        for glyph in &mut glyphs {
+            let y = &mut glyph.glyph.glyph.position.y;
+            *y = anchor + (*y - anchor) * self.line_spacing;
+        }
+        glyphs
+    }
+}
+
+impl GlyphPositioner for TextLayout {
+    fn calculate_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        self.laid_out_glyphs(fonts, geometry, sections)
+            .into_iter()
+            .map(|glyph| glyph.glyph)
+            .collect()
+    }
+
+    // vertically unbounded, lines can be moved outside of the built-in layout's bounds
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> ab_glyph::Rect {
+        let mut rect = self.layout.bounds_rect(geometry);
+        rect.min.y = f32::NEG_INFINITY;
+        rect.max.y = f32::INFINITY;
+        rect
+    }
+}
+
+// where the glyphs of a Text end up, in the units of its space
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextMetrics {
+    pub glyphs: Vec<GlyphMetrics>,
+    // including empty lines and the ones made by wrapping
+    pub lines: Vec<LineMetrics>,
+    // of all the lines
+    pub bounds: Rect,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlyphMetrics {
+    // of its character in Text::string
+    pub byte_index: usize,
+    pub line: usize,
+    // the advance box, from the font's ascent to its descent
+    pub rect: Rect,
+    // right-to-left, the caret before it is at the rect's right
+    pub rtl: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineMetrics {
+    // of its characters in Text::string, without the line break
+    pub range: Range<usize>,
+    pub baseline: f32,
+    pub rect: Rect,
+}
+
+impl TextMetrics {
+    // the byte index in Text::string of the caret position closest to `point`
+    pub fn hit_test(&self, point: (f32, f32)) -> usize {
+        let line_index = self
+            .lines
+            .iter()
+            .rposition(|line| line.rect.top <= point.1)
+            .unwrap_or(0);
+        let line = &self.lines[line_index];
+        // the glyph under the point, or the one closest to it; right-to-left ones can be
+        // anywhere on the line
+        let distance = |glyph: &GlyphMetrics| {
+            (glyph.rect.left - point.0)
+                .max(point.0 - glyph.rect.right())
+                .max(0.0)
+        };
+        let closest =
+            self.glyphs_on(line_index)
+                .fold(
+                    None,
+                    |closest: Option<&GlyphMetrics>, glyph| match closest {
+                        Some(closest) if distance(closest) <= distance(glyph) => Some(closest),
+                        _ => Some(glyph),
+                    },
+                );
+        let glyph = match closest {
+            Some(glyph) => glyph,
+            None => return line.range.end,
+        };
+        let left_half = point.0 < glyph.rect.left + glyph.rect.width / 2.0;
+        if left_half != glyph.rtl {
+            return glyph.byte_index;
+        }
+
+        // after the glyph is before the next character on the line
+        let next = self
+            .glyphs_on(line_index)
+            .map(|other| other.byte_index)
+            .filter(|&byte_index| byte_index > glyph.byte_index)
+            .min();
+        if let Some(next) = next {
+            return next;
+        }
+        // the end of a wrapped line is the start of the next one, stay before the character
+        // it was broken at instead
+        match self.lines.get(line_index + 1) {
+            Some(next) if next.range.start == line.range.end => glyph.byte_index,
+            _ => line.range.end,
+        }
+    }
+
+    // the line the caret at `byte_index` is drawn on
+    pub fn line_of(&self, byte_index: usize) -> usize {
+        (0..self.lines.len())
+            .find(|&line_index| {
+                let range = &self.lines[line_index].range;
+                // a wrapped line's end is drawn at the start of the next line
+                byte_index < range.end
+                    || byte_index == range.end
+                        && self
+                            .lines
+                            .get(line_index + 1)
+                            .map_or(true, |next| next.range.start != range.end)
+            })
+            .unwrap_or(self.lines.len() - 1)
+    }
+
+    // zero width, as tall as the line, before the character at `byte_index`: at its left,
+    // or its right when it's right-to-left
+    pub fn caret_rect(&self, byte_index: usize) -> Rect {
+        let line_index = self.line_of(byte_index);
+        let line = &self.lines[line_index];
+        let edge = |glyph: &GlyphMetrics, before: bool| {
+            if before != glyph.rtl {
+                glyph.rect.left
+            } else {
+                glyph.rect.right()
+            }
+        };
+        // the first glyph of the character, or after the line's last character
+        let at = self
+            .glyphs_on(line_index)
+            .filter(|glyph| glyph.byte_index >= byte_index)
+            .fold(None, |first: Option<&GlyphMetrics>, glyph| match first {
+                Some(first) if first.byte_index <= glyph.byte_index => Some(first),
+                _ => Some(glyph),
+            });
+        let x = match at {
+            Some(glyph) => edge(glyph, true),
+            None => self
+                .glyphs_on(line_index)
+                .fold(None, |last: Option<&GlyphMetrics>, glyph| match last {
+                    Some(last) if last.byte_index > glyph.byte_index => Some(last),
+                    _ => Some(glyph),
+                })
+                .map_or(line.rect.right(), |glyph| edge(glyph, false)),
+        };
+        Rect::new(x, line.rect.top, 0.0, line.rect.height)
+    }
+
+    fn glyphs_on(&self, line_index: usize) -> impl Iterator<Item = &GlyphMetrics> {
+        self.glyphs
+            .iter()
+            .filter(move |glyph| glyph.line == line_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(range: Range<usize>) -> TextStyle {
+        TextStyle {
+            range,
+            color: None,
+            size: None,
+            bold: true,
+            italic: false,
+        }
+    }
+
+    fn span_ranges(text: &Text) -> Vec<(Range<usize>, bool)> {
+        text.spans()
+            .into_iter()
+            .map(|(range, style)| (range, style.is_some()))
+            .collect()
+    }
+
+    #[test]
+    fn clamps_style_ranges() {
+        let mut text = Text::from_markup("[b]hello[/b] world");
+        text.string.truncate(3);
+        assert_eq!(span_ranges(&text), vec![(0..3, true)]);
+
+        let mut text = Text::new("héllo");
+        // inside 'é', past the end and reversed
+        text.styles = vec![style(2..100), style(Range { start: 4, end: 1 })];
+        assert_eq!(span_ranges(&text), vec![(0..1, false), (1..6, true)]);
+        for (range, _) in text.spans() {
+            let _ = &text.string[range];
+        }
+    }
+
+    #[test]
+    fn sorts_and_cuts_overlapping_styles() {
+        let mut text = Text::new("abcdefgh");
+        text.styles = vec![style(4..6), style(1..5), style(2..3)];
+        assert_eq!(
+            span_ranges(&text),
+            vec![(0..1, false), (1..5, true), (5..6, true), (6..8, false)]
+        );
+    }
+
+    // "ab " wraps before "cd", "ef" is after a line break
+    fn wrapped_metrics() -> (TextMetrics, f32) {
+        let fonts =
+            [
+                ab_glyph::FontArc::try_from_slice(include_bytes!("../Inconsolata-Regular.ttf"))
+                    .unwrap(),
+            ];
+        let mut text = Text::new("ab cd\nef");
+        text.size = 20.0;
+        text.wrap_width = Some(35.0);
+        let metrics = text.metrics(&fonts, FontVariants::default(), &text.layout());
+        let advance = metrics.glyphs[0].rect.width;
+        (metrics, advance)
+    }
+
+    #[test]
+    fn lays_out_lines() {
+        let (metrics, _) = wrapped_metrics();
+        let ranges: Vec<Range<usize>> = metrics
+            .lines
+            .iter()
+            .map(|line| line.range.clone())
+            .collect();
+        assert_eq!(ranges, vec![0..3, 3..5, 6..8]);
+        let tops: Vec<f32> = metrics.lines.iter().map(|line| line.rect.top).collect();
+        assert_eq!(tops, vec![0.0, 20.0, 40.0]);
+        let byte_indices: Vec<usize> = metrics
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.byte_index)
+            .collect();
+        assert_eq!(byte_indices, vec![0, 1, 2, 3, 4, 6, 7]);
+    }
+
+    #[test]
+    fn finds_the_line_of_a_caret() {
+        let (metrics, _) = wrapped_metrics();
+        assert_eq!(metrics.line_of(0), 0);
+        assert_eq!(metrics.line_of(2), 0);
+        // the end of a wrapped line is the start of the next one
+        assert_eq!(metrics.line_of(3), 1);
+        // before the line break
+        assert_eq!(metrics.line_of(5), 1);
+        assert_eq!(metrics.line_of(6), 2);
+        assert_eq!(metrics.line_of(8), 2);
+        assert_eq!(metrics.line_of(100), 2);
+    }
+
+    #[test]
+    fn places_the_caret() {
+        let (metrics, advance) = wrapped_metrics();
+        let caret = |byte_index| {
+            let rect = metrics.caret_rect(byte_index);
+            assert_eq!((rect.width, rect.height), (0.0, 20.0));
+            (rect.left, rect.top)
+        };
+        assert_eq!(caret(0), (0.0, 0.0));
+        assert_eq!(caret(2), (advance * 2.0, 0.0));
+        assert_eq!(caret(3), (0.0, 20.0));
+        assert_eq!(caret(5), (advance * 2.0, 20.0));
+        assert_eq!(caret(6), (0.0, 40.0));
+        assert_eq!(caret(8), (advance * 2.0, 40.0));
+        assert_eq!(caret(100), (advance * 2.0, 40.0));
+    }
+
+    #[test]
+    fn hit_tests_points() {
+        let (metrics, advance) = wrapped_metrics();
+        assert_eq!(metrics.hit_test((-5.0, -5.0)), 0);
+        assert_eq!(metrics.hit_test((advance * 0.4, 5.0)), 0);
+        assert_eq!(metrics.hit_test((advance * 0.6, 5.0)), 1);
+        // past a wrapped line's end, before the space it was broken at
+        assert_eq!(metrics.hit_test((100.0, 5.0)), 2);
+        // past the end of a line that ends at a line break
+        assert_eq!(metrics.hit_test((100.0, 25.0)), 5);
+        assert_eq!(metrics.hit_test((advance * 0.6, 25.0)), 4);
+        // past the end of the text
+        assert_eq!(metrics.hit_test((100.0, 100.0)), 8);
+        assert_eq!(metrics.hit_test((advance * 1.6, 45.0)), 8);
+    }
+}
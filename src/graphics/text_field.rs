@@ -0,0 +1,648 @@
+// a single line of editable screen space text, drawn by RendererGlyph; IME input arrives
+// composed, winit 0.24 has no events for the text being composed, and the clipboard is whatever
+// implements Clipboard, like SystemClipboard with the `system-clipboard` feature
+use crate::graphics::renderers::RendererGlyph;
+use crate::graphics::text::{TextMetrics, TextStyle};
+use crate::graphics::{Rect, Text, TextSpace};
+use std::ops::Range;
+use wgpu_glyph::{HorizontalAlign, VerticalAlign};
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent,
+};
+
+// where cut and copied text goes and pasted text comes from; a String keeps it inside the app
+pub trait Clipboard {
+    fn get(&mut self) -> Option<String>;
+    fn set(&mut self, text: String);
+}
+
+impl Clipboard for String {
+    fn get(&mut self) -> Option<String> {
+        Some(self.clone())
+    }
+
+    fn set(&mut self, text: String) {
+        *self = text;
+    }
+}
+
+// the platform's clipboard, shared with other apps
+#[cfg(feature = "system-clipboard")]
+pub struct SystemClipboard(arboard::Clipboard);
+
+#[cfg(feature = "system-clipboard")]
+impl SystemClipboard {
+    pub fn new() -> Result<Self, arboard::Error> {
+        Ok(SystemClipboard(arboard::Clipboard::new()?))
+    }
+}
+
+// empty or non-text contents paste nothing, and failing to copy is only logged
+#[cfg(feature = "system-clipboard")]
+impl Clipboard for SystemClipboard {
+    fn get(&mut self) -> Option<String> {
+        self.0.get_text().ok()
+    }
+
+    fn set(&mut self, text: String) {
+        if let Err(e) = self.0.set_text(text) {
+            log::warn!("couldn't copy to the clipboard: {}", e);
+        }
+    }
+}
+
+pub struct TextField {
+    // in window pixels
+    pub rect: Rect,
+    // how the string is drawn; its string, position, alignment and clip are the field's, and its
+    // styles move along with the text typed before them
+    pub text: Text,
+    // between the rect's left and right edges and the text
+    pub padding: f32,
+    // the selected text is drawn in it, over a rectangle of the highlight color
+    pub selection_color: [f32; 4],
+    pub highlight_color: [f32; 4],
+    pub caret_color: [f32; 4],
+    // typing goes to the focused field, a click inside focuses it and one outside unfocuses it
+    pub focused: bool,
+    // what's been typed
+    string: String,
+    // byte indices into the string, the selection is between them
+    caret: usize,
+    anchor: usize,
+    // how far the text is moved left to keep the caret inside the field
+    scroll: f32,
+    modifiers: ModifiersState,
+    cursor: (f32, f32),
+    dragging: bool,
+}
+
+// new
+impl TextField {
+    pub fn new(rect: Rect) -> Self {
+        TextField {
+            rect,
+            text: Text::new(String::new()),
+            string: String::new(),
+            padding: 4.0,
+            selection_color: [1.0, 1.0, 1.0, 1.0],
+            highlight_color: [0.2, 0.4, 0.8, 1.0],
+            caret_color: [1.0, 1.0, 1.0, 1.0],
+            focused: false,
+            caret: 0,
+            anchor: 0,
+            scroll: 0.0,
+            modifiers: ModifiersState::empty(),
+            cursor: (0.0, 0.0),
+            dragging: false,
+        }
+    }
+}
+
+// accessors
+impl TextField {
+    pub fn string(&self) -> &str {
+        &self.string
+    }
+
+    // with the caret at its end, and without the text's styles
+    pub fn set_string<S: Into<String>>(&mut self, string: S) {
+        self.string = string.into();
+        self.text.styles.clear();
+        self.caret = self.string.len();
+        self.anchor = self.caret;
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    pub fn selection(&self) -> Range<usize> {
+        self.caret.min(self.anchor)..self.caret.max(self.anchor)
+    }
+
+    pub fn selected_text(&self) -> &str {
+        &self.string[self.selection()]
+    }
+
+    // from `anchor` to `caret`, both moved back to character boundaries
+    pub fn select(&mut self, anchor: usize, caret: usize) {
+        self.anchor = self.floor_boundary(anchor);
+        self.caret = self.floor_boundary(caret);
+    }
+
+    // where the IME's candidate window goes, right below the caret, for
+    // Window::set_ime_position; winit 0.24 only reports the composed text, as ReceivedCharacter,
+    // so there's no composition to show in the field while it's going on
+    pub fn ime_position(&self, renderer_glyph: &RendererGlyph) -> (f32, f32) {
+        let caret = renderer_glyph
+            .metrics(&self.positioned_text())
+            .caret_rect(self.caret);
+        (caret.left, caret.bottom())
+    }
+}
+
+// edit
+impl TextField {
+    // replaces the selection, without the characters a single line can't have
+    pub fn insert(&mut self, text: &str) {
+        let selection = self.selection();
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+        self.string.replace_range(selection.clone(), &text);
+        self.caret = selection.start + text.len();
+        self.anchor = self.caret;
+
+        // styles after the selection move with the text, the ones in it are cut
+        let moved = |index: usize| {
+            if index <= selection.start {
+                index
+            } else if index >= selection.end {
+                index - selection.len() + text.len()
+            } else {
+                selection.start
+            }
+        };
+        for style in &mut self.text.styles {
+            style.range = moved(style.range.start)..moved(style.range.end);
+        }
+        self.text.styles.retain(|style| !style.range.is_empty());
+    }
+
+    pub fn copy(&self, clipboard: &mut dyn Clipboard) {
+        if !self.selection().is_empty() {
+            clipboard.set(self.selected_text().to_string());
+        }
+    }
+
+    pub fn cut(&mut self, clipboard: &mut dyn Clipboard) {
+        self.copy(clipboard);
+        self.insert("");
+    }
+
+    pub fn paste(&mut self, clipboard: &mut dyn Clipboard) {
+        if let Some(text) = clipboard.get() {
+            self.insert(&text);
+        }
+    }
+}
+
+// events
+impl TextField {
+    // true when the event was for the field, and shouldn't go anywhere else
+    pub fn handle_event(
+        &mut self,
+        event: &WindowEvent,
+        renderer_glyph: &RendererGlyph,
+        clipboard: &mut dyn Clipboard,
+    ) -> bool {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x as f32, position.y as f32);
+                if self.dragging {
+                    self.caret = self.hit_test(renderer_glyph);
+                }
+                self.dragging
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.focused = self.rect.contains(self.cursor);
+                    if self.focused {
+                        self.caret = self.hit_test(renderer_glyph);
+                        if !self.modifiers.shift() {
+                            self.anchor = self.caret;
+                        }
+                        self.dragging = true;
+                    }
+                    self.focused
+                }
+                ElementState::Released => std::mem::replace(&mut self.dragging, false),
+            },
+            // control characters, like backspace, come as key presses too
+            WindowEvent::ReceivedCharacter(c) if self.focused && !c.is_control() => {
+                self.insert(c.encode_utf8(&mut [0; 4]));
+                true
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } if self.focused => {
+                self.handle_key(*key, clipboard);
+                true
+            }
+            WindowEvent::KeyboardInput { .. } | WindowEvent::ReceivedCharacter(_) => self.focused,
+            _ => false,
+        }
+    }
+
+    fn handle_key(&mut self, key: VirtualKeyCode, clipboard: &mut dyn Clipboard) {
+        let (ctrl, shift) = (self.modifiers.ctrl(), self.modifiers.shift());
+        match key {
+            VirtualKeyCode::Left | VirtualKeyCode::Right => {
+                let left = key == VirtualKeyCode::Left;
+                let selection = self.selection();
+                self.caret = match (left, ctrl) {
+                    // an arrow without shift leaves the selection on its side
+                    _ if !shift && !ctrl && !selection.is_empty() => {
+                        if left {
+                            selection.start
+                        } else {
+                            selection.end
+                        }
+                    }
+                    (true, false) => self.previous_boundary(self.caret),
+                    (false, false) => self.next_boundary(self.caret),
+                    (true, true) => self.word_start(self.caret),
+                    (false, true) => self.word_end(self.caret),
+                };
+                if !shift {
+                    self.anchor = self.caret;
+                }
+            }
+            VirtualKeyCode::Home | VirtualKeyCode::End => {
+                self.caret = match key {
+                    VirtualKeyCode::Home => 0,
+                    _ => self.string.len(),
+                };
+                if !shift {
+                    self.anchor = self.caret;
+                }
+            }
+            VirtualKeyCode::Back | VirtualKeyCode::Delete => {
+                // without a selection, the character or word next to the caret is selected
+                if self.selection().is_empty() {
+                    self.caret = match (key, ctrl) {
+                        (VirtualKeyCode::Back, false) => self.previous_boundary(self.caret),
+                        (VirtualKeyCode::Back, true) => self.word_start(self.caret),
+                        (_, false) => self.next_boundary(self.caret),
+                        (_, true) => self.word_end(self.caret),
+                    };
+                }
+                self.insert("");
+            }
+            VirtualKeyCode::A if ctrl => self.select(0, self.string.len()),
+            VirtualKeyCode::C if ctrl => self.copy(clipboard),
+            VirtualKeyCode::X if ctrl => self.cut(clipboard),
+            VirtualKeyCode::V if ctrl => self.paste(clipboard),
+            _ => {}
+        }
+    }
+
+    fn hit_test(&self, renderer_glyph: &RendererGlyph) -> usize {
+        renderer_glyph
+            .metrics(&self.positioned_text())
+            .hit_test(self.cursor)
+    }
+}
+
+// draw
+impl TextField {
+    // scrolls to the caret first, so it's always inside the field
+    pub fn queue(&mut self, renderer_glyph: &mut RendererGlyph) {
+        let metrics = renderer_glyph.metrics(&self.positioned_text());
+        let scrolled = self.scroll_to_caret(&metrics);
+        let text = self.positioned_text();
+
+        if self.focused {
+            if let Some(highlight) = selection_rect(&metrics, self.selection()) {
+                renderer_glyph.queue_rect(
+                    Rect {
+                        left: highlight.left - scrolled,
+                        ..highlight
+                    },
+                    self.highlight_color,
+                    TextSpace::Screen,
+                    text.clip,
+                );
+            }
+            let caret = metrics.caret_rect(self.caret);
+            // a bar in the text's font, centered on the caret
+            renderer_glyph.queue_text(&Text {
+                font: text.font,
+                size: text.size,
+                color: self.caret_color,
+                position: (caret.left - scrolled, caret.top + caret.height / 2.0),
+                horizontal_align: HorizontalAlign::Center,
+                vertical_align: VerticalAlign::Center,
+                clip: text.clip,
+                ..Text::new("|")
+            });
+        }
+        renderer_glyph.queue_text(&text);
+    }
+
+    // where the text is drawn, scrolled and cut off at the field's edges
+    fn positioned_text(&self) -> Text {
+        Text {
+            string: self.string.clone(),
+            space: TextSpace::Screen,
+            position: (
+                self.rect.left + self.padding - self.scroll,
+                self.rect.top + self.rect.height / 2.0,
+            ),
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Center,
+            wrap_width: None,
+            styles: self.styles(),
+            clip: Some(self.rect),
+            ..self.text.clone()
+        }
+    }
+
+    // the text's styles, with the selection's color over them
+    fn styles(&self) -> Vec<TextStyle> {
+        let selection = self.selection();
+        let mut styles = Vec::with_capacity(self.text.styles.len() + 2);
+        // where the selection isn't styled yet
+        let mut unstyled = selection.start;
+        let push_selected = |styles: &mut Vec<TextStyle>, range: Range<usize>| {
+            if !range.is_empty() {
+                styles.push(TextStyle {
+                    range,
+                    color: Some(self.selection_color),
+                    size: None,
+                    bold: false,
+                    italic: false,
+                });
+            }
+        };
+        for style in &self.text.styles {
+            let range = style.range.clone();
+            // set by hand, they might not fit the string
+            if range.end > self.string.len()
+                || !self.string.is_char_boundary(range.start)
+                || !self.string.is_char_boundary(range.end)
+            {
+                continue;
+            }
+            let inside = range.start.max(selection.start)..range.end.min(selection.end);
+            if inside.is_empty() {
+                if range.start >= selection.end {
+                    push_selected(&mut styles, unstyled..selection.end);
+                    unstyled = selection.end;
+                }
+                styles.push(style.clone());
+                continue;
+            }
+
+            push_selected(&mut styles, unstyled..inside.start);
+            let part = |range: Range<usize>| TextStyle {
+                range,
+                ..style.clone()
+            };
+            if range.start < inside.start {
+                styles.push(part(range.start..inside.start));
+            }
+            styles.push(TextStyle {
+                color: Some(self.selection_color),
+                ..part(inside.clone())
+            });
+            if inside.end < range.end {
+                styles.push(part(inside.end..range.end));
+            }
+            unstyled = inside.end;
+        }
+        push_selected(&mut styles, unstyled..selection.end);
+        styles
+    }
+
+    // how far the text moved left
+    fn scroll_to_caret(&mut self, metrics: &TextMetrics) -> f32 {
+        let caret = metrics.caret_rect(self.caret).left;
+        let left = self.rect.left + self.padding;
+        let right = self.rect.right() - self.padding;
+        let scroll = self.scroll;
+        if caret < left {
+            self.scroll -= left - caret;
+        } else if caret > right {
+            self.scroll += caret - right;
+        }
+        // no empty space after the end of the text while some of it is scrolled out on the left
+        let overflow = (metrics.bounds.width - (right - left)).max(0.0);
+        self.scroll = self.scroll.clamp(0.0, overflow);
+        self.scroll - scroll
+    }
+}
+
+// around the selected glyphs, as high as the line; None when nothing is selected
+fn selection_rect(metrics: &TextMetrics, selection: Range<usize>) -> Option<Rect> {
+    let (left, right) = metrics
+        .glyphs
+        .iter()
+        .filter(|glyph| selection.contains(&glyph.byte_index))
+        .fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(left, right), glyph| (left.min(glyph.rect.left), right.max(glyph.rect.right())),
+        );
+    let line = metrics.lines.first()?;
+    if left >= right {
+        return None;
+    }
+    Some(Rect::new(
+        left,
+        line.rect.top,
+        right - left,
+        line.rect.height,
+    ))
+}
+
+// characters
+impl TextField {
+    fn floor_boundary(&self, index: usize) -> usize {
+        let mut index = index.min(self.string.len());
+        while !self.string.is_char_boundary(index) {
+            index -= 1;
+        }
+        index
+    }
+
+    fn previous_boundary(&self, index: usize) -> usize {
+        self.string[..index]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, index: usize) -> usize {
+        self.string[index..]
+            .chars()
+            .next()
+            .map_or(index, |c| index + c.len_utf8())
+    }
+
+    // the start of the word before `index`, past the whitespace in between
+    fn word_start(&self, index: usize) -> usize {
+        let before = self.string[..index].trim_end();
+        before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8())
+    }
+
+    // the end of the word after `index`, past the whitespace in between
+    fn word_end(&self, index: usize) -> usize {
+        let after = &self.string[index..];
+        let word = after.trim_start();
+        let start = index + after.len() - word.len();
+        start + word.find(char::is_whitespace).unwrap_or(word.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::text::FontVariants;
+    use wgpu_glyph::ab_glyph;
+
+    fn field(string: &str) -> TextField {
+        let mut field = TextField::new(Rect::new(0.0, 0.0, 200.0, 30.0));
+        field.set_string(string);
+        field
+    }
+
+    fn bold(range: Range<usize>) -> TextStyle {
+        TextStyle {
+            range,
+            color: None,
+            size: None,
+            bold: true,
+            italic: false,
+        }
+    }
+
+    fn press(field: &mut TextField, key: VirtualKeyCode, modifiers: ModifiersState) {
+        field.modifiers = modifiers;
+        field.handle_key(key, &mut String::new());
+    }
+
+    #[test]
+    fn inserts_over_the_selection() {
+        let mut field = field("hello world");
+        field.select(0, 5);
+        field.insert("bye\n");
+        assert_eq!(field.string(), "bye world");
+        assert_eq!(field.caret(), 3);
+        assert!(field.selection().is_empty());
+
+        let mut clipboard = String::new();
+        field.select(4, 9);
+        field.cut(&mut clipboard);
+        assert_eq!((field.string(), clipboard.as_str()), ("bye ", "world"));
+        field.select(0, 0);
+        field.paste(&mut clipboard);
+        assert_eq!(field.string(), "worldbye ");
+    }
+
+    #[test]
+    fn deletes_characters_and_words() {
+        let mut field = field("héllo big world");
+        press(&mut field, VirtualKeyCode::Back, ModifiersState::empty());
+        assert_eq!(field.string(), "héllo big worl");
+        press(&mut field, VirtualKeyCode::Back, ModifiersState::CTRL);
+        assert_eq!(field.string(), "héllo big ");
+
+        field.select(0, 0);
+        press(&mut field, VirtualKeyCode::Right, ModifiersState::empty());
+        press(&mut field, VirtualKeyCode::Delete, ModifiersState::empty());
+        assert_eq!(field.string(), "hllo big ");
+        press(&mut field, VirtualKeyCode::Delete, ModifiersState::CTRL);
+        assert_eq!(field.string(), "h big ");
+        assert_eq!(field.caret(), 1);
+    }
+
+    #[test]
+    fn moves_by_words() {
+        let mut field = field("one  two three");
+        assert_eq!(field.word_start(14), 9);
+        assert_eq!(field.word_start(9), 5);
+        assert_eq!(field.word_start(4), 0);
+        assert_eq!(field.word_end(0), 3);
+        assert_eq!(field.word_end(3), 8);
+        assert_eq!(field.word_end(14), 14);
+
+        field.select(0, 0);
+        press(&mut field, VirtualKeyCode::Right, ModifiersState::CTRL);
+        press(
+            &mut field,
+            VirtualKeyCode::Right,
+            ModifiersState::CTRL | ModifiersState::SHIFT,
+        );
+        assert_eq!(field.selection(), 3..8);
+        assert_eq!(field.selected_text(), "  two");
+        // without shift, the caret goes to the selection's side
+        press(&mut field, VirtualKeyCode::Left, ModifiersState::empty());
+        assert_eq!(field.selection(), 3..3);
+    }
+
+    #[test]
+    fn moves_and_cuts_styles() {
+        let mut field = field("abcdefgh");
+        field.text.styles = vec![bold(0..2), bold(3..5), bold(6..8)];
+        field.select(4, 7);
+        field.insert("XY");
+        let ranges: Vec<Range<usize>> = field.text.styles.iter().map(|s| s.range.clone()).collect();
+        assert_eq!(ranges, vec![0..2, 3..4, 4..7]);
+
+        // cut entirely
+        field.select(3, 4);
+        field.insert("");
+        let ranges: Vec<Range<usize>> = field.text.styles.iter().map(|s| s.range.clone()).collect();
+        assert_eq!(ranges, vec![0..2, 3..6]);
+    }
+
+    #[test]
+    fn colors_the_selection_over_styles() {
+        let mut field = field("abcdefgh");
+        field.text.styles = vec![bold(2..4), bold(6..8)];
+        field.select(1, 7);
+        let styles: Vec<(Range<usize>, bool, bool)> = field
+            .styles()
+            .into_iter()
+            .map(|s| (s.range, s.bold, s.color == Some(field.selection_color)))
+            .collect();
+        assert_eq!(
+            styles,
+            vec![
+                (1..2, false, true),
+                (2..4, true, true),
+                (4..6, false, true),
+                (6..7, true, true),
+                (7..8, true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_the_selected_glyphs() {
+        let fonts =
+            [
+                ab_glyph::FontArc::try_from_slice(include_bytes!("../Inconsolata-Regular.ttf"))
+                    .unwrap(),
+            ];
+        let mut field = field("ab cd");
+        field.text.size = 20.0;
+        let text = field.positioned_text();
+        let metrics = text.metrics(&fonts, FontVariants::default(), &text.layout());
+        let advance = metrics.glyphs[0].rect.width;
+        let line = &metrics.lines[0].rect;
+
+        let rect = selection_rect(&metrics, 1..4).unwrap();
+        assert!((rect.left - metrics.glyphs[1].rect.left).abs() < 0.01);
+        assert!((rect.width - 3.0 * advance).abs() < 0.01);
+        assert_eq!((rect.top, rect.height), (line.top, line.height));
+        assert!(selection_rect(&metrics, 2..2).is_none());
+    }
+}
@@ -0,0 +1,235 @@
+// [color=#ff0]..[/color], [size=24]..[/size], [b]..[/b] and [i]..[/i], nested in any order;
+// "[[" is a literal '[', and anything that isn't a known tag is kept as text
+use crate::graphics::text::TextStyle;
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Tag {
+    Bold,
+    Italic,
+    Color([f32; 4]),
+    Size(f32),
+}
+
+impl Tag {
+    fn parse(tag: &str) -> Option<Tag> {
+        let (name, value) = match tag.find('=') {
+            Some(i) => (&tag[..i], Some(&tag[i + 1..])),
+            None => (tag, None),
+        };
+        match (name, value) {
+            ("b", None) => Some(Tag::Bold),
+            ("i", None) => Some(Tag::Italic),
+            ("color", Some(value)) => parse_color(value).map(Tag::Color),
+            ("size", Some(value)) => value
+                .parse()
+                .ok()
+                .filter(|size: &f32| size.is_finite() && *size > 0.0)
+                .map(Tag::Size),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Tag::Bold => "b",
+            Tag::Italic => "i",
+            Tag::Color(_) => "color",
+            Tag::Size(_) => "size",
+        }
+    }
+}
+
+// #rgb, #rgba, #rrggbb or #rrggbbaa
+fn parse_color(value: &str) -> Option<[f32; 4]> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits: Vec<u8> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).unwrap() as u8 * 17)
+            .collect(),
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect(),
+        _ => return None,
+    };
+    let mut color = [1.0; 4];
+    for (channel, digit) in color.iter_mut().zip(digits) {
+        *channel = digit as f32 / 255.0;
+    }
+    Some(color)
+}
+
+// the text without the markup, and the styles of its tagged parts
+pub(crate) fn parse(markup: &str) -> (String, Vec<TextStyle>) {
+    let mut string = String::with_capacity(markup.len());
+    let mut styles = Vec::new();
+    // innermost last
+    let mut open: Vec<Tag> = Vec::new();
+    let mut run_start = 0;
+
+    let mut rest = markup;
+    while let Some(bracket) = rest.find('[') {
+        string.push_str(&rest[..bracket]);
+        rest = &rest[bracket..];
+
+        if let Some(after) = rest.strip_prefix("[[") {
+            string.push('[');
+            rest = after;
+            continue;
+        }
+        let end = match rest.find(']') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[1..end];
+        if tag.contains('[') {
+            string.push('[');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let change = match tag.strip_prefix('/') {
+            Some(name) => open
+                .iter()
+                .rposition(|open| open.name() == name)
+                .map(|i| (Some(i), None)),
+            None => Tag::parse(tag).map(|tag| (None, Some(tag))),
+        };
+        match change {
+            Some((close, push)) => {
+                if string.len() > run_start && !open.is_empty() {
+                    styles.push(style(run_start..string.len(), &open));
+                }
+                run_start = string.len();
+                if let Some(i) = close {
+                    open.remove(i);
+                }
+                open.extend(push);
+            }
+            None => string.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    string.push_str(rest);
+
+    // unclosed tags run to the end
+    if string.len() > run_start && !open.is_empty() {
+        styles.push(style(run_start..string.len(), &open));
+    }
+    (string, styles)
+}
+
+fn style(range: Range<usize>, open: &[Tag]) -> TextStyle {
+    let mut style = TextStyle {
+        range,
+        color: None,
+        size: None,
+        bold: false,
+        italic: false,
+    };
+    for tag in open {
+        match *tag {
+            Tag::Bold => style.bold = true,
+            Tag::Italic => style.italic = true,
+            Tag::Color(color) => style.color = Some(color),
+            Tag::Size(size) => style.size = Some(size),
+        }
+    }
+    style
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled(range: Range<usize>, bold: bool, italic: bool) -> TextStyle {
+        TextStyle {
+            range,
+            color: None,
+            size: None,
+            bold,
+            italic,
+        }
+    }
+
+    #[test]
+    fn escapes_brackets() {
+        assert_eq!(parse("a[[b]"), ("a[b]".to_owned(), vec![]));
+        assert_eq!(parse("[[b]x"), ("[b]x".to_owned(), vec![]));
+    }
+
+    #[test]
+    fn unclosed_tags_run_to_the_end() {
+        assert_eq!(
+            parse("a[b]bc"),
+            ("abc".to_owned(), vec![styled(1..3, true, false)])
+        );
+        // a bracket that's never closed is text
+        assert_eq!(parse("a[b"), ("a[b".to_owned(), vec![]));
+    }
+
+    #[test]
+    fn keeps_unknown_tags_as_text() {
+        assert_eq!(
+            parse("[foo]x[/foo][/b]"),
+            ("[foo]x[/foo][/b]".to_owned(), vec![])
+        );
+    }
+
+    #[test]
+    fn nests_tags() {
+        assert_eq!(
+            parse("[b]a[i]b[/i]c[/b]d"),
+            (
+                "abcd".to_owned(),
+                vec![
+                    styled(0..1, true, false),
+                    styled(1..2, true, true),
+                    styled(2..3, true, false),
+                ]
+            )
+        );
+        // closed out of order
+        assert_eq!(
+            parse("[b]a[i]b[/b]c[/i]"),
+            (
+                "abc".to_owned(),
+                vec![
+                    styled(0..1, true, false),
+                    styled(1..2, true, true),
+                    styled(2..3, false, true),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn parses_colors_and_sizes() {
+        let (string, styles) = parse("[color=#f008]a[/color][size=24]b");
+        assert_eq!(string, "ab");
+        assert_eq!(styles[0].color, Some([1.0, 0.0, 0.0, 136.0 / 255.0]));
+        assert_eq!(styles[1].size, Some(24.0));
+        assert_eq!(parse_color("#00ff00"), Some([0.0, 1.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn keeps_bad_values_as_text() {
+        for markup in &[
+            "[color=#ggg]x",
+            "[color=#12345]x",
+            "[color=red]x",
+            "[size=0]x",
+            "[size=-2]x",
+            "[size=inf]x",
+            "[size=NaN]x",
+            "[b=1]x",
+        ] {
+            assert_eq!(parse(markup), (markup.to_string(), vec![]), "{}", markup);
+        }
+    }
+}
@@ -0,0 +1,517 @@
+// complex script shaping with rustybuzz, and bidirectional text reordered line by line,
+// for the `shaping` feature; glyphs come out like the built-in layout's, so they're drawn
+// and measured the same way
+use crate::graphics::text::LaidOutGlyph;
+use glyph_brush::ToSectionText;
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use std::ops::Range;
+use std::sync::Arc;
+use unicode_bidi::{BidiInfo, ParagraphInfo};
+use wgpu_glyph::ab_glyph::{point, Font, Glyph, GlyphId, PxScale, ScaleFont};
+use wgpu_glyph::{
+    FontId, HorizontalAlign, SectionGeometry, SectionGlyph, SectionText, VerticalAlign,
+};
+
+// the fonts parsed for rustybuzz, by FontId; fonts added as a FontArc don't have any data,
+// their glyphs are placed by their advances and kerning instead
+#[derive(Clone, Default)]
+pub(crate) struct Shaper {
+    faces: Arc<Vec<Option<Arc<OwnedFace>>>>,
+}
+
+// a face and the data it borrows, parsed once instead of on every layout
+struct OwnedFace {
+    // declared first, so it's dropped before the data
+    face: Face<'static>,
+    _data: Arc<[u8]>,
+}
+
+impl OwnedFace {
+    fn parse(data: Arc<[u8]>) -> Option<Self> {
+        // SAFETY: the bytes are the Arc's heap allocation, which doesn't move when the Arc
+        // does and lives as long as `_data` holds it. `face` is dropped before `_data`, as
+        // fields drop in declaration order, and the `'static` face never leaves the struct:
+        // `face()` only lends it out for as long as the OwnedFace is borrowed.
+        let bytes: &'static [u8] = unsafe { &*(&*data as *const [u8]) };
+        let face = Face::from_slice(bytes, 0)?;
+        Some(OwnedFace { face, _data: data })
+    }
+
+    fn face(&self) -> &Face<'_> {
+        &self.face
+    }
+}
+
+impl std::fmt::Debug for Shaper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shaper")
+            .field("faces", &self.faces.len())
+            .finish()
+    }
+}
+
+// the fonts are only ever added to, layouts that share them are the same
+impl PartialEq for Shaper {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.faces, &other.faces)
+    }
+}
+
+// a part of a paragraph in one section, font and direction, shaped on its own
+struct Run {
+    // in the hard line
+    range: Range<usize>,
+    section_index: usize,
+    font_id: FontId,
+    scale: PxScale,
+    rtl: bool,
+    // in visual order
+    glyphs: Vec<RunGlyph>,
+}
+
+struct RunGlyph {
+    id: GlyphId,
+    // in the run
+    cluster: usize,
+    advance: f32,
+    offset: (f32, f32),
+}
+
+// a visual line, its glyphs are positioned relative to the start of its baseline until the end
+struct Line {
+    glyphs: Range<usize>,
+    width: f32,
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+}
+
+impl Line {
+    fn new(first_glyph: usize) -> Self {
+        Line {
+            glyphs: first_glyph..first_glyph,
+            width: 0.0,
+            ascent: 0.0,
+            descent: 0.0,
+            line_gap: 0.0,
+        }
+    }
+
+    fn fit_font<F: Font>(&mut self, font: &F, scale: PxScale) {
+        let font = font.as_scaled(scale);
+        self.ascent = self.ascent.max(font.ascent());
+        self.descent = self.descent.min(font.descent());
+        self.line_gap = self.line_gap.max(font.line_gap());
+    }
+}
+
+// new
+impl Shaper {
+    // in the order of the FontIds; parsed once, data rustybuzz can't read is placed like a
+    // FontArc's
+    pub(crate) fn push_font(&mut self, data: Option<Arc<[u8]>>) {
+        let face = data.and_then(OwnedFace::parse).map(Arc::new);
+        Arc::make_mut(&mut self.faces).push(face);
+    }
+}
+
+// layout
+impl Shaper {
+    // `font_for` picks the font of each character, from its section's font and the fallbacks
+    pub(crate) fn calculate_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+        align: (HorizontalAlign, VerticalAlign),
+        font_for: impl Fn(FontId, char) -> FontId,
+    ) -> Vec<LaidOutGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        let sections: Vec<SectionText> = sections.iter().map(|s| s.to_section_text()).collect();
+        if sections.is_empty() {
+            return vec![];
+        }
+
+        // the sections joined, and where each of them starts
+        let mut string = String::new();
+        let mut section_starts = Vec::with_capacity(sections.len());
+        for section in &sections {
+            section_starts.push(string.len());
+            string.push_str(section.text);
+        }
+        let section_at =
+            |byte_index: usize| section_starts.partition_point(|&start| start <= byte_index) - 1;
+
+        let mut glyphs = Vec::new();
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for hard_line in string.split('\n') {
+            let bidi = BidiInfo::new(hard_line, None);
+            if bidi.paragraphs.is_empty() {
+                let section = &sections[section_at(start)];
+                let mut line = Line::new(glyphs.len());
+                line.fit_font(&fonts[section.font_id.0], section.scale);
+                lines.push(line);
+            }
+            for paragraph in &bidi.paragraphs {
+                let runs = self.shape_paragraph(
+                    fonts,
+                    &bidi,
+                    paragraph,
+                    |byte_index| {
+                        let section_index = section_at(start + byte_index);
+                        (section_index, &sections[section_index])
+                    },
+                    &font_for,
+                );
+
+                // wrapped in logical order, by the advances of the glyphs of each character
+                let mut advances = vec![0.0; hard_line.len()];
+                for run in &runs {
+                    for glyph in &run.glyphs {
+                        advances[run.range.start + glyph.cluster] += glyph.advance;
+                    }
+                }
+                for line_range in break_lines(
+                    hard_line,
+                    paragraph.range.clone(),
+                    &advances,
+                    geometry.bounds.0,
+                ) {
+                    let mut line = Line::new(glyphs.len());
+                    let section = &sections[section_at(start + line_range.start)];
+                    // trailing whitespace isn't drawn, and doesn't count for the alignment
+                    let visible =
+                        line_range.start..line_range.start + hard_line[line_range].trim_end().len();
+                    if !visible.is_empty() {
+                        let (levels, visual_runs) = bidi.visual_runs(paragraph, visible.clone());
+                        for visual_run in visual_runs {
+                            let mut parts: Vec<&Run> = runs
+                                .iter()
+                                .filter(|run| {
+                                    run.range.start < visual_run.end
+                                        && visual_run.start < run.range.end
+                                })
+                                .collect();
+                            if levels[visual_run.start].is_rtl() {
+                                parts.reverse();
+                            }
+                            for run in parts {
+                                line.fit_font(&fonts[run.font_id.0], run.scale);
+                                for glyph in &run.glyphs {
+                                    let byte_index = start + run.range.start + glyph.cluster;
+                                    if !visual_run.contains(&(byte_index - start)) {
+                                        continue;
+                                    }
+                                    glyphs.push(LaidOutGlyph {
+                                        glyph: SectionGlyph {
+                                            section_index: run.section_index,
+                                            byte_index: byte_index
+                                                - section_starts[run.section_index],
+                                            font_id: run.font_id,
+                                            glyph: Glyph {
+                                                id: glyph.id,
+                                                scale: run.scale,
+                                                position: point(
+                                                    line.width + glyph.offset.0,
+                                                    glyph.offset.1,
+                                                ),
+                                            },
+                                        },
+                                        advance: glyph.advance,
+                                        rtl: run.rtl,
+                                    });
+                                    line.width += glyph.advance;
+                                }
+                            }
+                        }
+                    }
+                    line.glyphs.end = glyphs.len();
+                    // lines without glyphs are as high as their section's font
+                    if line.glyphs.is_empty() {
+                        line.fit_font(&fonts[section.font_id.0], section.scale);
+                    }
+                    lines.push(line);
+                }
+            }
+            start += hard_line.len() + 1;
+        }
+
+        // lines are stacked like the built-in layout stacks them, then aligned
+        let (h_align, v_align) = align;
+        let height: f32 = lines
+            .iter()
+            .map(|line| line.ascent - line.descent + line.line_gap)
+            .sum();
+        let (x, y) = geometry.screen_position;
+        let mut top = match v_align {
+            VerticalAlign::Top => y,
+            VerticalAlign::Center => y - height / 2.0,
+            VerticalAlign::Bottom => y - height,
+        };
+        for line in &lines {
+            let baseline = top + line.ascent;
+            let left = match h_align {
+                HorizontalAlign::Left => x,
+                HorizontalAlign::Center => x - line.width / 2.0,
+                HorizontalAlign::Right => x - line.width,
+            };
+            let line_glyphs = &mut glyphs[line.glyphs.clone()];
+            for glyph in line_glyphs.iter_mut() {
+                glyph.glyph.glyph.position.x += left;
+                glyph.glyph.glyph.position.y += baseline;
+            }
+            // in logical order within the line, like the built-in layout's
+            line_glyphs.sort_by_key(|glyph| (glyph.glyph.section_index, glyph.glyph.byte_index));
+            top += line.ascent - line.descent + line.line_gap;
+        }
+        glyphs
+    }
+
+    // split into runs by section, font and direction, each shaped in its direction
+    fn shape_paragraph<'a, F: Font>(
+        &self,
+        fonts: &[F],
+        bidi: &BidiInfo,
+        paragraph: &ParagraphInfo,
+        section_at: impl Fn(usize) -> (usize, &'a SectionText<'a>),
+        font_for: impl Fn(FontId, char) -> FontId,
+    ) -> Vec<Run> {
+        let mut runs: Vec<Run> = Vec::new();
+        let text = &bidi.text[paragraph.range.clone()];
+        for (i, c) in text.char_indices() {
+            let byte_index = paragraph.range.start + i;
+            let (section_index, section) = section_at(byte_index);
+            let font_id = font_for(section.font_id, c);
+            let rtl = bidi.levels[byte_index].is_rtl();
+            match runs.last_mut() {
+                Some(run)
+                    if run.section_index == section_index
+                        && run.font_id == font_id
+                        && run.rtl == rtl =>
+                {
+                    run.range.end = byte_index + c.len_utf8()
+                }
+                _ => runs.push(Run {
+                    range: byte_index..byte_index + c.len_utf8(),
+                    section_index,
+                    font_id,
+                    scale: section.scale,
+                    rtl,
+                    glyphs: vec![],
+                }),
+            }
+        }
+
+        for run in &mut runs {
+            let text = &bidi.text[run.range.clone()];
+            let font = fonts[run.font_id.0].as_scaled(run.scale);
+            let mut glyphs = match self.faces.get(run.font_id.0).and_then(Option::as_ref) {
+                Some(face) => {
+                    let mut buffer = UnicodeBuffer::new();
+                    buffer.push_str(text);
+                    buffer.set_direction(if run.rtl {
+                        Direction::RightToLeft
+                    } else {
+                        Direction::LeftToRight
+                    });
+                    let output = rustybuzz::shape(face.face(), &[], buffer);
+
+                    // from font units, y up, to the run's scale, y down
+                    let (h_scale, v_scale) = (font.h_scale_factor(), font.v_scale_factor());
+                    output
+                        .glyph_infos()
+                        .iter()
+                        .zip(output.glyph_positions())
+                        .map(|(info, position)| RunGlyph {
+                            id: GlyphId(info.codepoint as u16),
+                            cluster: info.cluster as usize,
+                            advance: position.x_advance as f32 * h_scale,
+                            offset: (
+                                position.x_offset as f32 * h_scale,
+                                -position.y_offset as f32 * v_scale,
+                            ),
+                        })
+                        .collect()
+                }
+                None => {
+                    let mut glyphs: Vec<RunGlyph> = Vec::with_capacity(text.len());
+                    for (cluster, c) in text.char_indices() {
+                        let id = font.glyph_id(c);
+                        if let Some(last) = glyphs.last_mut() {
+                            last.advance += font.kern(last.id, id);
+                        }
+                        glyphs.push(RunGlyph {
+                            id,
+                            cluster,
+                            advance: font.h_advance(id),
+                            offset: (0.0, 0.0),
+                        });
+                    }
+                    if run.rtl {
+                        glyphs.reverse();
+                    }
+                    glyphs
+                }
+            };
+            // like '\r', the built-in layout skips them too
+            glyphs.retain(|glyph| !text[glyph.cluster..].starts_with(char::is_control));
+            run.glyphs = glyphs;
+        }
+        runs
+    }
+}
+
+// greedily between words, a word that doesn't fit on a line of its own overflows it
+fn break_lines(
+    text: &str,
+    range: Range<usize>,
+    advances: &[f32],
+    wrap_width: f32,
+) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut line_start = range.start;
+    let mut line_width = 0.0;
+    let mut word = |word: Range<usize>, lines: &mut Vec<Range<usize>>| {
+        let visible = word.start..word.start + text[word.clone()].trim_end().len();
+        let visible_width: f32 = advances[visible].iter().sum();
+        if line_start < word.start && line_width + visible_width > wrap_width {
+            lines.push(line_start..word.start);
+            line_start = word.start;
+            line_width = 0.0;
+        }
+        line_width += advances[word].iter().sum::<f32>();
+    };
+
+    let mut word_start = range.start;
+    let mut after_whitespace = false;
+    for (i, c) in text[range.clone()].char_indices() {
+        let i = range.start + i;
+        if after_whitespace && !c.is_whitespace() {
+            word(word_start..i, &mut lines);
+            word_start = i;
+        }
+        after_whitespace = c.is_whitespace();
+    }
+    word(word_start..range.end, &mut lines);
+    lines.push(line_start..range.end);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu_glyph::ab_glyph::FontArc;
+
+    const INCONSOLATA: &[u8] = include_bytes!("../Inconsolata-Regular.ttf");
+
+    fn layout(text: &str, wrap_width: f32) -> Vec<LaidOutGlyph> {
+        let fonts = [FontArc::try_from_slice(INCONSOLATA).unwrap()];
+        let mut shaper = Shaper::default();
+        shaper.push_font(Some(Arc::from(INCONSOLATA)));
+        let geometry = SectionGeometry {
+            screen_position: (0.0, 0.0),
+            bounds: (wrap_width, f32::INFINITY),
+        };
+        let sections = [SectionText {
+            text,
+            scale: PxScale::from(20.0),
+            font_id: FontId(0),
+        }];
+        shaper.calculate_glyphs(
+            &fonts,
+            &geometry,
+            &sections,
+            (HorizontalAlign::Left, VerticalAlign::Top),
+            |font_id, _| font_id,
+        )
+    }
+
+    // byte indices from left to right, and whether they're right-to-left
+    fn visual_order(glyphs: &[LaidOutGlyph]) -> Vec<(usize, bool)> {
+        let mut glyphs: Vec<&LaidOutGlyph> = glyphs.iter().collect();
+        glyphs.sort_by(|a, b| {
+            let (a, b) = (a.glyph.glyph.position, b.glyph.glyph.position);
+            (a.y, a.x).partial_cmp(&(b.y, b.x)).unwrap()
+        });
+        glyphs
+            .iter()
+            .map(|glyph| (glyph.glyph.byte_index, glyph.rtl))
+            .collect()
+    }
+
+    #[test]
+    fn reorders_right_to_left_runs() {
+        // "ab", two hebrew letters of two bytes each, "cd"
+        let glyphs = layout("ab \u{5d0}\u{5d1} cd", f32::INFINITY);
+        assert_eq!(
+            visual_order(&glyphs),
+            vec![
+                (0, false),
+                (1, false),
+                (2, false),
+                (5, true),
+                (3, true),
+                (7, false),
+                (8, false),
+                (9, false),
+            ]
+        );
+        // logical order in the output, on one baseline
+        let byte_indices: Vec<usize> = glyphs.iter().map(|glyph| glyph.glyph.byte_index).collect();
+        assert_eq!(byte_indices, vec![0, 1, 2, 3, 5, 7, 8, 9]);
+        assert!(glyphs
+            .iter()
+            .all(|glyph| glyph.glyph.glyph.position.y == glyphs[0].glyph.glyph.position.y));
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        let glyphs = layout("ab cd ef", 50.0);
+        let lines: Vec<(usize, f32, f32)> = glyphs
+            .iter()
+            .map(|glyph| {
+                let position = glyph.glyph.glyph.position;
+                (glyph.glyph.byte_index, position.x, position.y)
+            })
+            .collect();
+        let advance = glyphs[0].advance;
+        let (first, second) = (lines[0].2, lines[5].2);
+        assert!(second > first);
+        // the space the line was broken at isn't drawn
+        assert_eq!(
+            lines,
+            vec![
+                (0, 0.0, first),
+                (1, advance, first),
+                (2, advance * 2.0, first),
+                (3, advance * 3.0, first),
+                (4, advance * 4.0, first),
+                (6, 0.0, second),
+                (7, advance, second),
+            ]
+        );
+    }
+
+    #[test]
+    fn breaks_lines_after_whitespace() {
+        let text = "ab cd ef";
+        let advances = vec![1.0; text.len()];
+        // the trailing space of "cd " doesn't count for fitting it
+        assert_eq!(
+            break_lines(text, 0..text.len(), &advances, 5.0),
+            vec![0..6, 6..8]
+        );
+        assert_eq!(
+            break_lines(text, 0..text.len(), &advances, 1.0),
+            vec![0..3, 3..6, 6..8]
+        );
+        assert_eq!(
+            break_lines(text, 3..text.len(), &advances, f32::INFINITY),
+            vec![3..8]
+        );
+    }
+}
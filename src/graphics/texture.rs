@@ -0,0 +1,462 @@
+use crate::graphics::mipmaps::mip_level_count;
+use crate::graphics::GraphicsState;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_TEXTURE_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureOptions {
+    // nearest for pixel art, linear for everything else
+    pub filter: wgpu::FilterMode,
+    // clamp to edge, repeat or mirror repeat
+    pub address_mode: wgpu::AddressMode,
+    // color data is usually srgb, data textures (normal maps, lookup tables) are linear
+    pub srgb: bool,
+    // builds the full mip chain on the gpu and samples it trilinearly,
+    // so minified textures don't shimmer
+    pub mipmaps: bool,
+    // 1 turns it off, only used with mipmaps and linear filtering;
+    // wgpu ignores it when the device doesn't support anisotropic filtering
+    pub max_anisotropy: u8,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        TextureOptions {
+            filter: wgpu::FilterMode::Linear,
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            srgb: true,
+            mipmaps: false,
+            max_anisotropy: 16,
+        }
+    }
+}
+
+pub struct Texture {
+    // unique per texture, renderers batch draws by it
+    pub(crate) id: u64,
+    pub(crate) texture: wgpu::Texture,
+    // bound with GraphicsState::texture_bind_group_layout
+    pub(crate) bind_group: wgpu::BindGroup,
+    // bound with GraphicsState::texture_array_bind_group_layout, always layer 0
+    pub(crate) array_bind_group: wgpu::BindGroup,
+    // bumped by every update, so copies of the texture know they're stale
+    version: Cell<u64>,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+    options: TextureOptions,
+}
+
+// new
+impl Texture {
+    // wgpu can't create textures without pixels, empty images are an error
+    pub(super) fn new(
+        graphics_state: &GraphicsState,
+        label: &str,
+        size: (u32, u32),
+        rgba: &[u8],
+        options: TextureOptions,
+    ) -> Result<Self, image::ImageError> {
+        if size.0 == 0 || size.1 == 0 {
+            return Err(parameter_error(
+                image::error::ParameterErrorKind::DimensionMismatch,
+            ));
+        }
+
+        let format = if options.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+
+        let mip_level_count = if options.mipmaps {
+            mip_level_count(size)
+        } else {
+            1
+        };
+        // copy source for the sprite renderer's texture arrays
+        let mut usage = wgpu::TextureUsage::SAMPLED
+            | wgpu::TextureUsage::COPY_SRC
+            | wgpu::TextureUsage::COPY_DST;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsage::RENDER_ATTACHMENT;
+        }
+
+        let texture = graphics_state
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth: 1,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+            });
+
+        let texture = Texture::from_wgpu_texture(
+            graphics_state,
+            label,
+            texture,
+            size,
+            format,
+            mip_level_count,
+            options,
+        );
+        texture.update_region(graphics_state, 0, 0, size.0, size.1, rgba)?;
+        Ok(texture)
+    }
+
+    // magenta and black squares, drawn in place of textures that failed to load
+    pub(super) fn placeholder(graphics_state: &GraphicsState) -> Self {
+        const SIZE: u32 = 64;
+        let rgba = placeholder_rgba((SIZE, SIZE));
+
+        // repeats, so it also fills texture rects that were meant for a bigger image
+        Texture::new(
+            graphics_state,
+            "placeholder texture",
+            (SIZE, SIZE),
+            &rgba,
+            TextureOptions {
+                filter: wgpu::FilterMode::Nearest,
+                address_mode: wgpu::AddressMode::Repeat,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    // uploads ready made mip levels, starting with the full size; works for block compressed
+    // formats too, as long as the device supports them
+    pub(super) fn from_levels(
+        graphics_state: &GraphicsState,
+        label: &str,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+        levels: &[Vec<u8>],
+        options: TextureOptions,
+    ) -> Self {
+        let info = format.describe();
+        let (block_width, block_height) = (
+            info.block_dimensions.0 as u32,
+            info.block_dimensions.1 as u32,
+        );
+        let mip_level_count = levels.len() as u32;
+
+        let texture = graphics_state
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth: 1,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_SRC
+                    | wgpu::TextureUsage::COPY_DST,
+            });
+
+        for (level, data) in levels.iter().enumerate() {
+            let width = (size.0 >> level).max(1);
+            let height = (size.1 >> level).max(1);
+            let blocks_wide = (width + block_width - 1) / block_width;
+            let blocks_high = (height + block_height - 1) / block_height;
+            // copies cover whole blocks, even where they hang over the edge of small levels
+            graphics_state.queue.write_texture(
+                wgpu::TextureCopyView {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: blocks_wide * info.block_size as u32,
+                    rows_per_image: blocks_high * block_height,
+                },
+                wgpu::Extent3d {
+                    width: blocks_wide * block_width,
+                    height: blocks_high * block_height,
+                    depth: 1,
+                },
+            );
+        }
+
+        Texture::from_wgpu_texture(
+            graphics_state,
+            label,
+            texture,
+            size,
+            format,
+            mip_level_count,
+            options,
+        )
+    }
+
+    // creates the bind groups, they keep the views and the sampler alive
+    fn from_wgpu_texture(
+        graphics_state: &GraphicsState,
+        label: &str,
+        texture: wgpu::Texture,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        options: TextureOptions,
+    ) -> Self {
+        let device = &graphics_state.device;
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = Texture::create_sampler(device, label, options, mip_level_count);
+        let bind_group = Texture::create_bind_group(
+            device,
+            &graphics_state.texture_bind_group_layout,
+            label,
+            &view,
+            &sampler,
+        );
+
+        // the same texture as a one layer array, for shaders that sample texture arrays
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let array_bind_group = Texture::create_bind_group(
+            device,
+            &graphics_state.texture_array_bind_group_layout,
+            label,
+            &array_view,
+            &sampler,
+        );
+
+        Texture {
+            id: NEXT_TEXTURE_ID.fetch_add(1, Ordering::Relaxed),
+            texture,
+            bind_group,
+            array_bind_group,
+            version: Cell::new(0),
+            size,
+            format,
+            mip_level_count,
+            options,
+        }
+    }
+
+    pub(super) fn create_sampler(
+        device: &wgpu::Device,
+        label: &str,
+        options: TextureOptions,
+        mip_level_count: u32,
+    ) -> wgpu::Sampler {
+        let (mipmap_filter, anisotropy_clamp) =
+            if mip_level_count > 1 && options.filter == wgpu::FilterMode::Linear {
+                // valid clamps are powers of two up to 16
+                let anisotropy = options.max_anisotropy.clamp(1, 16).next_power_of_two();
+                (
+                    wgpu::FilterMode::Linear,
+                    std::num::NonZeroU8::new(anisotropy).filter(|&a| a.get() > 1),
+                )
+            } else {
+                (wgpu::FilterMode::Nearest, None)
+            };
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: options.address_mode,
+            address_mode_v: options.address_mode,
+            address_mode_w: options.address_mode,
+            mag_filter: options.filter,
+            min_filter: options.filter,
+            mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+
+    pub(super) fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        label: &str,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    // D2 for GraphicsState::texture_bind_group_layout,
+    // D2Array for GraphicsState::texture_array_bind_group_layout
+    pub(super) fn bind_group_layout(
+        device: &wgpu::Device,
+        label: &str,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+}
+
+// other
+impl Texture {
+    // uploads tightly packed rgba8 pixels into the given rectangle, and rebuilds the mip chain;
+    // fails for regions outside of the texture, compressed textures and a wrong amount of pixels
+    pub fn update_region(
+        &self,
+        graphics_state: &GraphicsState,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<(), image::ImageError> {
+        let inside = |start: u32, length: u32, size: u32| {
+            start.checked_add(length).map_or(false, |end| end <= size)
+        };
+        if !inside(x, width, self.size.0) || !inside(y, height, self.size.1) {
+            return Err(parameter_error(
+                image::error::ParameterErrorKind::DimensionMismatch,
+            ));
+        }
+        if self.is_compressed() {
+            return Err(parameter_error(image::error::ParameterErrorKind::Generic(
+                "compressed textures can't be updated".to_owned(),
+            )));
+        }
+        let expected_len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4));
+        if expected_len != Some(rgba.len()) {
+            return Err(parameter_error(
+                image::error::ParameterErrorKind::DimensionMismatch,
+            ));
+        }
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        graphics_state.queue.write_texture(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+            },
+            rgba,
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: 4 * width,
+                rows_per_image: height,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+
+        self.version.set(self.version.get() + 1);
+
+        if self.mip_level_count > 1 {
+            graphics_state.mipmap_generator.generate(
+                &graphics_state.device,
+                &graphics_state.queue,
+                &graphics_state.texture_bind_group_layout,
+                &self.texture,
+                self.format,
+                self.mip_level_count,
+            );
+        }
+        Ok(())
+    }
+}
+
+// accessors
+impl Texture {
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.format.describe().block_dimensions != (1, 1)
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.mip_level_count
+    }
+
+    pub fn options(&self) -> TextureOptions {
+        self.options
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.version.get()
+    }
+}
+
+// the placeholder's squares, at any size
+pub(super) fn placeholder_rgba(size: (u32, u32)) -> Vec<u8> {
+    const SQUARE: u32 = 8;
+    let mut rgba = Vec::with_capacity((size.0 * size.1 * 4) as usize);
+    for y in 0..size.1 {
+        for x in 0..size.0 {
+            if (x / SQUARE + y / SQUARE) % 2 == 0 {
+                rgba.extend_from_slice(&[255, 0, 255, 255]);
+            } else {
+                rgba.extend_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+    }
+    rgba
+}
+
+fn parameter_error(kind: image::error::ParameterErrorKind) -> image::ImageError {
+    image::ImageError::Parameter(image::error::ParameterError::from_kind(kind))
+}
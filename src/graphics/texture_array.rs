@@ -0,0 +1,241 @@
+// copies of same-sized textures in the layers of D2Array textures, so sprites using any of them
+// can share a batch; RendererSprite keeps one of these
+use crate::graphics::{GraphicsState, Texture, TextureOptions};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+// every wgpu backend supports at least this many layers
+const MAX_LAYERS: u32 = 256;
+
+// textures can only share an array when they're sampled the same way
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct ArrayKey {
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+    options: TextureOptions,
+}
+
+impl ArrayKey {
+    fn of(texture: &Texture) -> Self {
+        ArrayKey {
+            size: texture.size(),
+            format: texture.format(),
+            mip_level_count: texture.mip_level_count(),
+            options: texture.options(),
+        }
+    }
+}
+
+struct Layer {
+    texture: Weak<Texture>,
+    texture_id: u64,
+    // of the texture when it was copied
+    version: u64,
+    // has to be copied before the next draw
+    dirty: bool,
+    // pushed since the last release(), kept alive so the layer isn't reused before the draw
+    pushed: Option<Rc<Texture>>,
+}
+
+struct GpuArray {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    capacity: u32,
+}
+
+struct TextureArray {
+    key: ArrayKey,
+    // layers of dropped textures are reused
+    layers: Vec<Option<Layer>>,
+    // created on the first draw, and recreated bigger when the layers outgrow it
+    gpu: Option<GpuArray>,
+}
+
+#[derive(Default)]
+pub(crate) struct TextureArrays {
+    arrays: Vec<TextureArray>,
+    // texture id to array index and layer
+    slots: HashMap<u64, (usize, u32)>,
+}
+
+impl TextureArrays {
+    // compressed textures aren't copied, they keep their own bind group
+    pub fn supports(texture: &Texture) -> bool {
+        !texture.is_compressed()
+    }
+
+    // the array and layer `texture` will be in when drawn
+    pub fn slot(&mut self, texture: &Rc<Texture>) -> (usize, u32) {
+        if let Some(&(array_index, layer_index)) = self.slots.get(&texture.id) {
+            let layer = self.arrays[array_index].layers[layer_index as usize]
+                .as_mut()
+                .unwrap();
+            if layer.version != texture.version() {
+                layer.version = texture.version();
+                layer.dirty = true;
+            }
+            layer.pushed = Some(texture.clone());
+            return (array_index, layer_index);
+        }
+
+        let key = ArrayKey::of(texture);
+        let (array_index, layer_index) = self.free_layer(key);
+        self.arrays[array_index].layers[layer_index as usize] = Some(Layer {
+            texture: Rc::downgrade(texture),
+            texture_id: texture.id,
+            version: texture.version(),
+            dirty: true,
+            pushed: Some(texture.clone()),
+        });
+        self.slots.insert(texture.id, (array_index, layer_index));
+        (array_index, layer_index)
+    }
+
+    fn free_layer(&mut self, key: ArrayKey) -> (usize, u32) {
+        for (array_index, array) in self.arrays.iter_mut().enumerate() {
+            if array.key != key {
+                continue;
+            }
+            for (layer_index, layer) in array.layers.iter_mut().enumerate() {
+                let free = match layer {
+                    Some(layer) => layer.texture.strong_count() == 0,
+                    None => true,
+                };
+                if free {
+                    if let Some(layer) = layer.take() {
+                        self.slots.remove(&layer.texture_id);
+                    }
+                    return (array_index, layer_index as u32);
+                }
+            }
+            if array.layers.len() < MAX_LAYERS as usize {
+                array.layers.push(None);
+                return (array_index, array.layers.len() as u32 - 1);
+            }
+        }
+
+        self.arrays.push(TextureArray {
+            key,
+            layers: vec![None],
+            gpu: None,
+        });
+        (self.arrays.len() - 1, 0)
+    }
+
+    // the layers of textures dropped since can be reused again
+    pub fn release(&mut self) {
+        for array in &mut self.arrays {
+            for layer in array.layers.iter_mut().flatten() {
+                layer.pushed = None;
+            }
+        }
+    }
+
+    // creates or grows the arrays and copies the new and updated textures into them
+    pub fn prepare(&mut self, graphics_state: &GraphicsState, encoder: &mut wgpu::CommandEncoder) {
+        for array in &mut self.arrays {
+            let layer_count = array.layers.len() as u32;
+            if array
+                .gpu
+                .as_ref()
+                .map_or(true, |gpu| gpu.capacity < layer_count)
+            {
+                // room to grow, without reallocating for every new texture
+                let capacity = layer_count.next_power_of_two().min(MAX_LAYERS);
+                log::debug!(
+                    "creating a {:?} texture array with {} layers",
+                    array.key.size,
+                    capacity
+                );
+                array.gpu = Some(create_gpu_array(graphics_state, array.key, capacity));
+                for layer in array.layers.iter_mut().flatten() {
+                    layer.dirty = true;
+                }
+            }
+
+            let gpu = array.gpu.as_ref().unwrap();
+            for (layer_index, slot) in array.layers.iter_mut().enumerate() {
+                let layer = match slot {
+                    Some(layer) if layer.dirty => layer,
+                    _ => continue,
+                };
+                let texture = match layer.texture.upgrade() {
+                    Some(texture) => texture,
+                    None => {
+                        self.slots.remove(&layer.texture_id);
+                        *slot = None;
+                        continue;
+                    }
+                };
+
+                for mip_level in 0..array.key.mip_level_count {
+                    encoder.copy_texture_to_texture(
+                        wgpu::TextureCopyView {
+                            texture: &texture.texture,
+                            mip_level,
+                            origin: wgpu::Origin3d::ZERO,
+                        },
+                        wgpu::TextureCopyView {
+                            texture: &gpu.texture,
+                            mip_level,
+                            origin: wgpu::Origin3d {
+                                x: 0,
+                                y: 0,
+                                z: layer_index as u32,
+                            },
+                        },
+                        wgpu::Extent3d {
+                            width: (array.key.size.0 >> mip_level).max(1),
+                            height: (array.key.size.1 >> mip_level).max(1),
+                            depth: 1,
+                        },
+                    );
+                }
+                layer.dirty = false;
+            }
+        }
+    }
+
+    // only valid after prepare()
+    pub fn bind_group(&self, array_index: usize) -> &wgpu::BindGroup {
+        &self.arrays[array_index].gpu.as_ref().unwrap().bind_group
+    }
+}
+
+fn create_gpu_array(graphics_state: &GraphicsState, key: ArrayKey, capacity: u32) -> GpuArray {
+    let device = &graphics_state.device;
+    let label = "Sprite Texture Array";
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: key.size.0,
+            height: key.size.1,
+            depth: capacity,
+        },
+        mip_level_count: key.mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: key.format,
+        usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some(label),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let sampler = Texture::create_sampler(device, label, key.options, key.mip_level_count);
+    let bind_group = Texture::create_bind_group(
+        device,
+        &graphics_state.texture_array_bind_group_layout,
+        label,
+        &view,
+        &sampler,
+    );
+
+    GpuArray {
+        texture,
+        bind_group,
+        capacity,
+    }
+}
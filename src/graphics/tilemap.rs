@@ -0,0 +1,235 @@
+// tile grids, drawn by RendererTilemap; see tilemap_tiled.rs for loading Tiled maps
+use crate::graphics::{Rect, Texture};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+// the top bits of a gid flip the tile, like in Tiled
+pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const FLIP_FLAGS: u32 = FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY;
+
+#[derive(Debug)]
+pub enum TilemapError {
+    // more tiles than fit in memory
+    LayerTooLarge(String, (u32, u32)),
+}
+
+impl std::fmt::Display for TilemapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TilemapError::LayerTooLarge(name, size) => {
+                write!(f, "layer {} is too large: {}x{}", name, size.0, size.1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TilemapError {}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TileFrame {
+    // local to the tileset
+    pub tile_id: u32,
+    pub duration: Duration,
+}
+
+#[derive(Clone)]
+pub struct Tileset {
+    // the gid of its first tile, gid 0 is always "no tile"
+    pub first_gid: u32,
+    pub texture: Rc<Texture>,
+    pub tile_size: (u32, u32),
+    pub columns: u32,
+    pub tile_count: u32,
+    // in pixels, around the whole image and between tiles
+    pub margin: u32,
+    pub spacing: u32,
+    // keyed by local tile id
+    pub animations: HashMap<u32, Vec<TileFrame>>,
+}
+
+impl Tileset {
+    pub fn contains(&self, gid: u32) -> bool {
+        let gid = gid & !FLIP_FLAGS;
+        gid >= self.first_gid && gid - self.first_gid < self.tile_count
+    }
+
+    // in pixels
+    pub fn texture_rect(&self, tile_id: u32) -> Rect {
+        let columns = self.columns.max(1);
+        let (width, height) = self.tile_size;
+        Rect::new(
+            (self.margin + (tile_id % columns) * (width + self.spacing)) as f32,
+            (self.margin + (tile_id / columns) * (height + self.spacing)) as f32,
+            width as f32,
+            height as f32,
+        )
+    }
+
+    // the tile shown at `time` by an animated tile, `tile_id` itself if it isn't animated
+    pub fn animated_tile_id(&self, tile_id: u32, time: Duration) -> u32 {
+        let frames = match self.animations.get(&tile_id) {
+            Some(frames) if !frames.is_empty() => frames,
+            _ => return tile_id,
+        };
+        let total: Duration = frames.iter().map(|frame| frame.duration).sum();
+        if total == Duration::default() {
+            return frames[0].tile_id;
+        }
+
+        let mut t = time.as_nanos() % total.as_nanos();
+        for frame in frames {
+            let duration = frame.duration.as_nanos();
+            if t < duration {
+                return frame.tile_id;
+            }
+            t -= duration;
+        }
+        frames[frames.len() - 1].tile_id
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TileLayer {
+    pub name: String,
+    // in tiles
+    pub size: (u32, u32),
+    // row by row, 0 is empty
+    pub tiles: Vec<u32>,
+    pub visible: bool,
+    pub opacity: f32,
+    // in pixels
+    pub offset: (f32, f32),
+}
+
+impl TileLayer {
+    pub fn new(name: &str, size: (u32, u32)) -> Result<Self, TilemapError> {
+        let tile_count =
+            tile_count(size).ok_or_else(|| TilemapError::LayerTooLarge(name.to_owned(), size))?;
+        Ok(TileLayer {
+            name: name.to_owned(),
+            size,
+            tiles: vec![0; tile_count],
+            visible: true,
+            opacity: 1.0,
+            offset: (0.0, 0.0),
+        })
+    }
+
+    pub fn tile(&self, x: u32, y: u32) -> u32 {
+        if x >= self.size.0 || y >= self.size.1 {
+            return 0;
+        }
+        self.tiles[y as usize * self.size.0 as usize + x as usize]
+    }
+}
+
+#[derive(Clone)]
+pub struct Tilemap {
+    // in pixels, the grid cell size, tiles can be bigger
+    pub tile_size: (u32, u32),
+    pub tilesets: Vec<Tileset>,
+    // bottom to top
+    pub layers: Vec<TileLayer>,
+}
+
+impl Tilemap {
+    pub fn new(tile_size: (u32, u32)) -> Self {
+        Tilemap {
+            tile_size,
+            tilesets: vec![],
+            layers: vec![],
+        }
+    }
+
+    // the tileset a gid belongs to, ignoring its flip flags
+    pub fn tileset_index(&self, gid: u32) -> Option<usize> {
+        if gid & !FLIP_FLAGS == 0 {
+            return None;
+        }
+        self.tilesets
+            .iter()
+            .position(|tileset| tileset.contains(gid))
+    }
+
+    pub fn layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.name == name)
+    }
+}
+
+// a tile as two triangles, for RendererTilemap
+pub(crate) fn tile_vertices(
+    tileset: &Tileset,
+    tile_id: u32,
+    flags: u32,
+    // the grid cell, tiles bigger than it stick out at the top and right, like in Tiled
+    cell: Rect,
+    color: [f32; 4],
+) -> [crate::vertex::TexturedVertex; 6] {
+    use crate::vertex::TexturedVertex;
+
+    let (texture_width, texture_height) = tileset.texture.size();
+    let rect = tileset.texture_rect(tile_id);
+    let u0 = rect.left / texture_width as f32;
+    let v0 = rect.top / texture_height as f32;
+    let u1 = rect.right() / texture_width as f32;
+    let v1 = rect.bottom() / texture_height as f32;
+
+    // uvs of the top left, top right, bottom right and bottom left corners
+    let mut uvs = [[u0, v0], [u1, v0], [u1, v1], [u0, v1]];
+    if flags & FLIPPED_DIAGONALLY != 0 {
+        uvs.swap(1, 3);
+    }
+    if flags & FLIPPED_HORIZONTALLY != 0 {
+        uvs.swap(0, 1);
+        uvs.swap(2, 3);
+    }
+    if flags & FLIPPED_VERTICALLY != 0 {
+        uvs.swap(0, 3);
+        uvs.swap(1, 2);
+    }
+
+    let (width, height) = flipped_size((rect.width, rect.height), flags);
+    let left = cell.left;
+    let bottom = cell.bottom();
+    let right = left + width;
+    let top = bottom - height;
+    let corner = |x: f32, y: f32, uv: [f32; 2]| TexturedVertex {
+        position: [x, y],
+        uv,
+        color,
+    };
+    let top_left = corner(left, top, uvs[0]);
+    let top_right = corner(right, top, uvs[1]);
+    let bottom_right = corner(right, bottom, uvs[2]);
+    let bottom_left = corner(left, bottom, uvs[3]);
+
+    [
+        top_left,
+        top_right,
+        bottom_right,
+        top_left,
+        bottom_right,
+        bottom_left,
+    ]
+}
+
+// a diagonal flip turns the tile by a quarter, so its width and height swap
+pub(crate) fn flipped_size(size: (f32, f32), flags: u32) -> (f32, f32) {
+    if flags & FLIPPED_DIAGONALLY != 0 {
+        (size.1, size.0)
+    } else {
+        size
+    }
+}
+
+// width * height, None when it overflows
+pub(crate) fn tile_count(size: (u32, u32)) -> Option<usize> {
+    (size.0 as usize).checked_mul(size.1 as usize)
+}
+
+pub(crate) fn split_gid(gid: u32) -> (u32, u32) {
+    (gid & !FLIP_FLAGS, gid & FLIP_FLAGS)
+}
@@ -0,0 +1,706 @@
+// loading maps made with Tiled <https://www.mapeditor.org/>, saved as json (.tmj/.json) or xml (.tmx),
+// with inline or external tilesets; only finite orthogonal maps, object and image layers are skipped
+use crate::graphics::tilemap::{tile_count, TileFrame, TileLayer, Tilemap, Tileset};
+use crate::graphics::{GraphicsState, TextureOptions};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum TiledError {
+    // something this loader doesn't handle, like infinite maps or zstd compression
+    Unsupported(String),
+    Invalid(String),
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Json(serde_json::Error),
+    Xml(roxmltree::Error),
+    Base64(base64::DecodeError),
+}
+
+impl std::fmt::Display for TiledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TiledError::Unsupported(what) => write!(f, "unsupported tiled map: {}", what),
+            TiledError::Invalid(what) => write!(f, "invalid tiled map: {}", what),
+            TiledError::Io(e) => write!(f, "{}", e),
+            TiledError::Image(e) => write!(f, "{}", e),
+            TiledError::Json(e) => write!(f, "{}", e),
+            TiledError::Xml(e) => write!(f, "{}", e),
+            TiledError::Base64(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+impl From<std::io::Error> for TiledError {
+    fn from(e: std::io::Error) -> Self {
+        TiledError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for TiledError {
+    fn from(e: image::ImageError) -> Self {
+        TiledError::Image(e)
+    }
+}
+
+impl From<serde_json::Error> for TiledError {
+    fn from(e: serde_json::Error) -> Self {
+        TiledError::Json(e)
+    }
+}
+
+impl From<roxmltree::Error> for TiledError {
+    fn from(e: roxmltree::Error) -> Self {
+        TiledError::Xml(e)
+    }
+}
+
+impl From<base64::DecodeError> for TiledError {
+    fn from(e: base64::DecodeError) -> Self {
+        TiledError::Base64(e)
+    }
+}
+
+impl Tilemap {
+    // .tmx files are read as xml, everything else as json;
+    // tileset images are loaded relative to the file that references them
+    pub fn load_tiled<P: AsRef<Path>>(
+        graphics_state: &GraphicsState,
+        path: P,
+        texture_options: TextureOptions,
+    ) -> Result<Tilemap, TiledError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let map = if is_xml(path) {
+            xml::map(&text)?
+        } else {
+            serde_json::from_str(&text)?
+        };
+        map.load(graphics_state, path, texture_options)
+    }
+}
+
+fn is_xml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("tmx") | Some("tsx")
+    )
+}
+
+// the json format, the xml format is read into the same structs
+
+#[derive(serde::Deserialize)]
+struct TiledMap {
+    #[serde(default)]
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    layers: Vec<TiledLayer>,
+    #[serde(default)]
+    tilesets: Vec<TiledTileset>,
+}
+
+#[derive(serde::Deserialize)]
+struct TiledLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    data: Option<TiledData>,
+    #[serde(default)]
+    encoding: String,
+    #[serde(default)]
+    compression: String,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default = "default_one")]
+    opacity: f32,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    // group layers
+    #[serde(default)]
+    layers: Vec<TiledLayer>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum TiledData {
+    Gids(Vec<u32>),
+    // base64, maybe compressed
+    Encoded(String),
+    // csv, only in xml
+    #[serde(skip)]
+    Csv(String),
+}
+
+#[derive(serde::Deserialize)]
+struct TiledTileset {
+    #[serde(default = "default_one_u32")]
+    firstgid: u32,
+    // external tileset, relative to the map
+    source: Option<String>,
+    // relative to the file the tileset is in
+    image: Option<String>,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    tilecount: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(serde::Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    animation: Vec<TiledFrame>,
+}
+
+#[derive(serde::Deserialize)]
+struct TiledFrame {
+    tileid: u32,
+    // in milliseconds
+    duration: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_one_u32() -> u32 {
+    1
+}
+
+impl TiledMap {
+    fn load(
+        self,
+        graphics_state: &GraphicsState,
+        path: &Path,
+        texture_options: TextureOptions,
+    ) -> Result<Tilemap, TiledError> {
+        if !self.orientation.is_empty() && self.orientation != "orthogonal" {
+            return Err(TiledError::Unsupported(format!(
+                "{} orientation",
+                self.orientation
+            )));
+        }
+        if self.infinite {
+            return Err(TiledError::Unsupported("infinite map".to_owned()));
+        }
+
+        let mut tilemap = Tilemap::new((self.tilewidth, self.tileheight));
+
+        for tileset in self.tilesets {
+            let first_gid = tileset.firstgid;
+            let (tileset, tileset_path) = match &tileset.source {
+                Some(source) => {
+                    let tileset_path = path.with_file_name(source);
+                    let text = std::fs::read_to_string(&tileset_path)?;
+                    let external = if is_xml(&tileset_path) {
+                        xml::tileset(&roxmltree::Document::parse(&text)?.root_element())?
+                    } else {
+                        serde_json::from_str(&text)?
+                    };
+                    (external, tileset_path)
+                }
+                None => (tileset, path.to_owned()),
+            };
+            tilemap.tilesets.push(tileset.load(
+                graphics_state,
+                &tileset_path,
+                first_gid,
+                texture_options,
+            )?);
+        }
+
+        let mut layers = vec![];
+        flatten_layers(self.layers, (0.0, 0.0), 1.0, true, &mut layers);
+        for layer in layers {
+            tilemap.layers.push(layer.load()?);
+        }
+
+        Ok(tilemap)
+    }
+}
+
+// group layers are dissolved into their children, which inherit their offset, opacity and visibility
+fn flatten_layers(
+    layers: Vec<TiledLayer>,
+    offset: (f32, f32),
+    opacity: f32,
+    visible: bool,
+    out: &mut Vec<TiledLayer>,
+) {
+    for mut layer in layers {
+        layer.offsetx += offset.0;
+        layer.offsety += offset.1;
+        layer.opacity *= opacity;
+        layer.visible &= visible;
+        match layer.kind.as_str() {
+            "tilelayer" => out.push(layer),
+            "group" => {
+                let children = std::mem::take(&mut layer.layers);
+                flatten_layers(
+                    children,
+                    (layer.offsetx, layer.offsety),
+                    layer.opacity,
+                    layer.visible,
+                    out,
+                );
+            }
+            _ => log::debug!("skipping tiled {} layer {}", layer.kind, layer.name),
+        }
+    }
+}
+
+impl TiledTileset {
+    fn load(
+        self,
+        graphics_state: &GraphicsState,
+        path: &Path,
+        first_gid: u32,
+        texture_options: TextureOptions,
+    ) -> Result<Tileset, TiledError> {
+        let image = self
+            .image
+            .ok_or_else(|| TiledError::Unsupported("image collection tilesets".to_owned()))?;
+        // a missing image shouldn't stop the rest of the map from loading
+        let texture =
+            graphics_state.load_texture_or_placeholder(path.with_file_name(image), texture_options);
+
+        let animations = self
+            .tiles
+            .into_iter()
+            .filter(|tile| !tile.animation.is_empty())
+            .map(|tile| {
+                let frames = tile
+                    .animation
+                    .iter()
+                    .map(|frame| TileFrame {
+                        tile_id: frame.tileid,
+                        duration: Duration::from_millis(frame.duration),
+                    })
+                    .collect();
+                (tile.id, frames)
+            })
+            .collect::<HashMap<_, _>>();
+
+        Ok(Tileset {
+            first_gid,
+            texture,
+            tile_size: (self.tilewidth, self.tileheight),
+            columns: self.columns,
+            tile_count: self.tilecount,
+            margin: self.margin,
+            spacing: self.spacing,
+            animations,
+        })
+    }
+}
+
+impl TiledLayer {
+    fn load(self) -> Result<TileLayer, TiledError> {
+        let tiles = match self.data {
+            Some(TiledData::Gids(gids)) => gids,
+            Some(TiledData::Csv(csv)) => csv
+                .split(',')
+                .map(str::trim)
+                .filter(|gid| !gid.is_empty())
+                .map(|gid| {
+                    gid.parse()
+                        .map_err(|_| TiledError::Invalid(format!("gid {}", gid)))
+                })
+                .collect::<Result<_, _>>()?,
+            Some(TiledData::Encoded(encoded)) => {
+                if self.encoding != "base64" {
+                    return Err(TiledError::Unsupported(format!(
+                        "{} encoding",
+                        self.encoding
+                    )));
+                }
+                decode_base64(&encoded, &self.compression)?
+            }
+            None => {
+                return Err(TiledError::Invalid(format!(
+                    "layer {} has no data",
+                    self.name
+                )))
+            }
+        };
+
+        if tile_count((self.width, self.height)) != Some(tiles.len()) {
+            return Err(TiledError::Invalid(format!(
+                "layer {} has {} tiles instead of {}x{}",
+                self.name,
+                tiles.len(),
+                self.width,
+                self.height
+            )));
+        }
+
+        Ok(TileLayer {
+            name: self.name,
+            size: (self.width, self.height),
+            tiles,
+            visible: self.visible,
+            opacity: self.opacity,
+            offset: (self.offsetx, self.offsety),
+        })
+    }
+}
+
+// little endian u32 gids
+fn decode_base64(encoded: &str, compression: &str) -> Result<Vec<u32>, TiledError> {
+    let bytes = base64::decode(encoded.trim())?;
+    let bytes = match compression {
+        "" => bytes,
+        "zlib" => {
+            let mut decompressed = vec![];
+            flate2::read::ZlibDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+            decompressed
+        }
+        "gzip" => {
+            let mut decompressed = vec![];
+            flate2::read::GzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+            decompressed
+        }
+        _ => {
+            return Err(TiledError::Unsupported(format!(
+                "{} compression",
+                compression
+            )))
+        }
+    };
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+        .collect())
+}
+
+mod xml {
+    use super::{TiledData, TiledError, TiledFrame, TiledLayer, TiledMap, TiledTile, TiledTileset};
+
+    pub(super) fn map(text: &str) -> Result<TiledMap, TiledError> {
+        let document = roxmltree::Document::parse(text)?;
+        let root = document.root_element();
+        if !root.has_tag_name("map") {
+            return Err(TiledError::Invalid("root element isn't <map>".to_owned()));
+        }
+
+        let mut tilesets = vec![];
+        for node in root.children().filter(|node| node.has_tag_name("tileset")) {
+            tilesets.push(tileset(&node)?);
+        }
+
+        Ok(TiledMap {
+            orientation: root.attribute("orientation").unwrap_or_default().to_owned(),
+            infinite: parse_or(&root, "infinite", 0u32)? != 0,
+            tilewidth: parse(&root, "tilewidth")?,
+            tileheight: parse(&root, "tileheight")?,
+            layers: layers(&root)?,
+            tilesets,
+        })
+    }
+
+    pub(super) fn tileset(node: &roxmltree::Node) -> Result<TiledTileset, TiledError> {
+        let image = node
+            .children()
+            .find(|child| child.has_tag_name("image"))
+            .and_then(|image| image.attribute("source"))
+            .map(str::to_owned);
+
+        let mut tiles = vec![];
+        for tile in node.children().filter(|child| child.has_tag_name("tile")) {
+            let mut animation = vec![];
+            for frame in tile
+                .children()
+                .filter(|child| child.has_tag_name("animation"))
+                .flat_map(|animation| animation.children())
+                .filter(|child| child.has_tag_name("frame"))
+            {
+                animation.push(TiledFrame {
+                    tileid: parse(&frame, "tileid")?,
+                    duration: parse(&frame, "duration")?,
+                });
+            }
+            tiles.push(TiledTile {
+                id: parse(&tile, "id")?,
+                animation,
+            });
+        }
+
+        Ok(TiledTileset {
+            firstgid: parse_or(node, "firstgid", 1)?,
+            source: node.attribute("source").map(str::to_owned),
+            image,
+            tilewidth: parse_or(node, "tilewidth", 0)?,
+            tileheight: parse_or(node, "tileheight", 0)?,
+            columns: parse_or(node, "columns", 0)?,
+            tilecount: parse_or(node, "tilecount", 0)?,
+            margin: parse_or(node, "margin", 0)?,
+            spacing: parse_or(node, "spacing", 0)?,
+            tiles,
+        })
+    }
+
+    fn layers(parent: &roxmltree::Node) -> Result<Vec<TiledLayer>, TiledError> {
+        let mut result = vec![];
+        for node in parent.children().filter(roxmltree::Node::is_element) {
+            let kind = match node.tag_name().name() {
+                "layer" => "tilelayer",
+                "group" => "group",
+                "objectgroup" => "objectgroup",
+                "imagelayer" => "imagelayer",
+                _ => continue,
+            };
+
+            let data_node = node.children().find(|child| child.has_tag_name("data"));
+            let encoding = data_node
+                .and_then(|data| data.attribute("encoding"))
+                .unwrap_or_default();
+            let data = match data_node {
+                None => None,
+                Some(data) if data.children().any(|child| child.has_tag_name("chunk")) => {
+                    return Err(TiledError::Unsupported("infinite map".to_owned()));
+                }
+                Some(data) => match encoding {
+                    "csv" => Some(TiledData::Csv(data.text().unwrap_or_default().to_owned())),
+                    "" => {
+                        let mut gids = vec![];
+                        for tile in data.children().filter(|child| child.has_tag_name("tile")) {
+                            gids.push(parse_or(&tile, "gid", 0)?);
+                        }
+                        Some(TiledData::Gids(gids))
+                    }
+                    _ => Some(TiledData::Encoded(
+                        data.text().unwrap_or_default().to_owned(),
+                    )),
+                },
+            };
+
+            result.push(TiledLayer {
+                kind: kind.to_owned(),
+                name: node.attribute("name").unwrap_or_default().to_owned(),
+                width: parse_or(&node, "width", 0)?,
+                height: parse_or(&node, "height", 0)?,
+                data,
+                encoding: encoding.to_owned(),
+                compression: data_node
+                    .and_then(|data| data.attribute("compression"))
+                    .unwrap_or_default()
+                    .to_owned(),
+                visible: parse_or(&node, "visible", 1u32)? != 0,
+                opacity: parse_or(&node, "opacity", 1.0)?,
+                offsetx: parse_or(&node, "offsetx", 0.0)?,
+                offsety: parse_or(&node, "offsety", 0.0)?,
+                layers: if kind == "group" {
+                    layers(&node)?
+                } else {
+                    vec![]
+                },
+            });
+        }
+        Ok(result)
+    }
+
+    fn parse<T: std::str::FromStr>(node: &roxmltree::Node, name: &str) -> Result<T, TiledError> {
+        let value = node.attribute(name).ok_or_else(|| {
+            TiledError::Invalid(format!(
+                "<{}> has no {} attribute",
+                node.tag_name().name(),
+                name
+            ))
+        })?;
+        value.parse().map_err(|_| {
+            TiledError::Invalid(format!(
+                "<{}> has an invalid {} attribute: {}",
+                node.tag_name().name(),
+                name,
+                value
+            ))
+        })
+    }
+
+    fn parse_or<T: std::str::FromStr>(
+        node: &roxmltree::Node,
+        name: &str,
+        default: T,
+    ) -> Result<T, TiledError> {
+        if node.attribute(name).is_none() {
+            return Ok(default);
+        }
+        parse(node, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::tilemap::{
+        split_gid, FLIPPED_DIAGONALLY, FLIPPED_HORIZONTALLY, FLIPPED_VERTICALLY,
+    };
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="8" infinite="0">
+ <tileset firstgid="1" name="a" tilewidth="16" tileheight="8" tilecount="4" columns="2">
+  <image source="a.png" width="32" height="16"/>
+  <tile id="1">
+   <animation>
+    <frame tileid="1" duration="100"/>
+    <frame tileid="3" duration="250"/>
+   </animation>
+  </tile>
+ </tileset>
+ <tileset firstgid="5" source="b.tsx"/>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,0,
+2147483653,5,3221225478
+</data>
+ </layer>
+ <objectgroup id="2" name="objects"/>
+ <group id="3" name="group" offsetx="4" opacity="0.5">
+  <layer id="4" name="inner" width="1" height="1" offsety="2" visible="0">
+   <data><tile gid="7"/></data>
+  </layer>
+ </group>
+</map>"#;
+
+    const JSON: &str = r#"{
+        "orientation": "orthogonal",
+        "tilewidth": 16,
+        "tileheight": 16,
+        "tilesets": [
+            { "firstgid": 1, "source": "a.tsj" },
+            { "firstgid": 65, "image": "b.png", "tilewidth": 16, "tileheight": 16, "tilecount": 8, "columns": 4 }
+        ],
+        "layers": [
+            { "type": "tilelayer", "name": "gids", "width": 2, "height": 1, "data": [66, 1073741890] },
+            { "type": "tilelayer", "name": "base64", "width": 2, "height": 2, "encoding": "base64",
+              "data": "AQAAAAIAAIADAAAgAAAAAA==" }
+        ]
+    }"#;
+
+    fn load_layers(map: TiledMap) -> Vec<TileLayer> {
+        let mut layers = vec![];
+        flatten_layers(map.layers, (0.0, 0.0), 1.0, true, &mut layers);
+        layers
+            .into_iter()
+            .map(|layer| layer.load().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parses_tmx_maps() {
+        let map = xml::map(TMX).unwrap();
+        assert_eq!((map.tilewidth, map.tileheight), (16, 8));
+        assert!(!map.infinite);
+
+        let firstgids: Vec<u32> = map
+            .tilesets
+            .iter()
+            .map(|tileset| tileset.firstgid)
+            .collect();
+        assert_eq!(firstgids, vec![1, 5]);
+        let inline = &map.tilesets[0];
+        assert_eq!(inline.image.as_deref(), Some("a.png"));
+        assert_eq!((inline.columns, inline.tilecount), (2, 4));
+        assert_eq!(inline.tiles[0].id, 1);
+        let frames: Vec<(u32, u64)> = inline.tiles[0]
+            .animation
+            .iter()
+            .map(|frame| (frame.tileid, frame.duration))
+            .collect();
+        assert_eq!(frames, vec![(1, 100), (3, 250)]);
+        assert_eq!(map.tilesets[1].source.as_deref(), Some("b.tsx"));
+
+        // the object layer is skipped, the group's layer inherits its offset and opacity
+        let layers = load_layers(map);
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].name, "ground");
+        assert_eq!(layers[0].size, (3, 2));
+        assert_eq!(layers[0].tiles, vec![1, 2, 0, 0x8000_0005, 5, 0xc000_0006]);
+        assert_eq!(layers[1].tiles, vec![7]);
+        assert_eq!(layers[1].offset, (4.0, 2.0));
+        assert_eq!(layers[1].opacity, 0.5);
+        assert!(!layers[1].visible);
+    }
+
+    #[test]
+    fn keeps_flip_flags_apart_from_gids() {
+        let layers = load_layers(xml::map(TMX).unwrap());
+        assert_eq!(split_gid(layers[0].tiles[3]), (5, FLIPPED_HORIZONTALLY));
+        assert_eq!(
+            split_gid(layers[0].tiles[5]),
+            (6, FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY)
+        );
+        assert_eq!(split_gid(layers[0].tiles[1]), (2, 0));
+    }
+
+    #[test]
+    fn parses_json_maps() {
+        let map: TiledMap = serde_json::from_str(JSON).unwrap();
+        let firstgids: Vec<u32> = map
+            .tilesets
+            .iter()
+            .map(|tileset| tileset.firstgid)
+            .collect();
+        assert_eq!(firstgids, vec![1, 65]);
+        assert_eq!(map.tilesets[1].columns, 4);
+
+        let layers = load_layers(map);
+        // the second tileset's second tile, then flipped vertically
+        assert_eq!(split_gid(layers[0].tiles[0]), (66, 0));
+        assert_eq!(split_gid(layers[0].tiles[1]), (66, FLIPPED_VERTICALLY));
+        assert_eq!(
+            layers[1].tiles,
+            vec![1, 2 | FLIPPED_HORIZONTALLY, 3 | FLIPPED_DIAGONALLY, 0]
+        );
+    }
+
+    #[test]
+    fn rejects_layers_of_the_wrong_size() {
+        let mut map = xml::map(TMX).unwrap();
+        map.layers[0].width = 4;
+        assert!(matches!(
+            map.layers.remove(0).load(),
+            Err(TiledError::Invalid(_))
+        ));
+
+        // 65536 * 65536 wraps around to 0 in a u32
+        let mut map: TiledMap = serde_json::from_str(JSON).unwrap();
+        map.layers[0].width = 65536;
+        map.layers[0].height = 65536;
+        map.layers[0].data = Some(TiledData::Gids(vec![]));
+        assert!(matches!(
+            map.layers.remove(0).load(),
+            Err(TiledError::Invalid(_))
+        ));
+    }
+}
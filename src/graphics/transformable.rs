@@ -0,0 +1,78 @@
+// reimplementation of <https://www.sfml-dev.org/documentation/2.5.1/classsf_1_1Transformable.php>
+use crate::vertex::{Instance, Vertex};
+use cgmath::Transform;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transformable {
+    pub position: (f32, f32),
+    pub rotation: f32,
+    pub scale: (f32, f32),
+    pub origin: (f32, f32),
+}
+
+impl Default for Transformable {
+    fn default() -> Self {
+        Transformable {
+            position: (0.0, 0.0),
+            rotation: 0.0,
+            scale: (1.0, 1.0),
+            origin: (0.0, 0.0),
+        }
+    }
+}
+
+impl Transformable {
+    pub fn get_matrix4(&self) -> cgmath::Matrix4<f32> {
+        // https://github.com/SFML/SFML/blob/master/src/SFML/Graphics/Transformable.cpp#L186
+
+        use cgmath::Angle;
+
+        let position = self.position;
+        let scale = self.scale;
+        let origin = self.origin;
+
+        let angle = -self.rotation * std::f32::consts::PI / 180f32; // in radians now
+        let cosine = cgmath::Rad::cos(cgmath::Rad(angle));
+        let sine = cgmath::Rad::sin(cgmath::Rad(angle));
+        let sxc = scale.0 * cosine;
+        let syc = scale.1 * cosine;
+        let sxs = scale.0 * sine;
+        let sys = scale.1 * sine;
+        let tx = -origin.0 * sxc - origin.1 * sys + position.0;
+        let ty = origin.0 * sxs - origin.1 * syc + position.1;
+
+        // note: SFML matrices are row-major, but cgmath is column-major
+        #[rustfmt::skip]
+        let m4 = cgmath::Matrix4::new(
+            sxc,            -sxs,           0f32,        0f32,
+            sys,             syc,           0f32,        0f32,
+            0f32,            0f32,          1f32,        0f32,
+            tx,              ty,            0f32,        1f32,
+        );
+
+        m4
+    }
+
+    pub fn transform_point(&self, point: (f32, f32)) -> (f32, f32) {
+        let p = self
+            .get_matrix4()
+            .transform_point(cgmath::Point3::new(point.0, point.1, 0.0));
+        (p.x, p.y)
+    }
+
+    // cpu path, for batching transformed geometry into a single draw
+    pub fn transform_vertices(&self, vertices: &mut [Vertex]) {
+        let m = self.get_matrix4();
+        for vertex in vertices {
+            let p = m.transform_point(cgmath::Point3::from(vertex.position));
+            vertex.position = p.into();
+        }
+    }
+
+    // gpu path, one instance per transformable
+    pub fn instance(&self) -> Instance {
+        Instance {
+            model: self.get_matrix4().into(),
+        }
+    }
+}
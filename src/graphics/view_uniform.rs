@@ -0,0 +1,64 @@
+use crate::graphics::SfView;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Uniforms {
+    m: [[f32; 4]; 4],
+}
+
+// the SfView matrix as a uniform at set 0, binding 0, like in shader_vert_with_view.glsl
+pub struct ViewUniform {
+    buffer: wgpu::Buffer,
+    pub(super) bind_group_layout: wgpu::BindGroupLayout,
+    pub(super) bind_group: wgpu::BindGroup,
+}
+
+impl ViewUniform {
+    pub fn new(device: &wgpu::Device) -> Self {
+        use wgpu::util::DeviceExt;
+
+        let uniforms = Uniforms { m: [[0.0; 4]; 4] };
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("uniform_bind_group"),
+        });
+
+        ViewUniform {
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue, view: &SfView) {
+        let m = view.get_matrix4();
+        let m = m * crate::graphics::sf_view::OPENGL_TO_WGPU_MATRIX4;
+        let u = Uniforms { m: m.into() };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[u]));
+    }
+}
@@ -0,0 +1,9 @@
+// the graphics library; main.rs is a demo of it
+
+// #[derive(VertexLayout)] refers to the crate as ::wgpu_tiny_graphics, here too
+extern crate self as wgpu_tiny_graphics;
+
+pub mod frame_counter;
+pub mod graphics;
+mod shader_compilation;
+pub mod vertex;
@@ -1,14 +1,12 @@
 #![allow(clippy::collapsible_match)]
 #![allow(clippy::single_match)]
 
+use rand::Rng;
 use std::cell::RefCell;
+use std::rc::Rc;
 
-mod frame_counter;
-mod graphics;
-mod shader_compilation;
-mod vertex;
-
-use graphics::GraphicsState;
+use wgpu_tiny_graphics::graphics::{self, GraphicsState};
+use wgpu_tiny_graphics::vertex;
 
 fn main() {
     {
@@ -56,10 +54,158 @@ fn main() {
         size: (1000.0, 1000.0),
         rotation: 0.0,
     };
+    let happy_tree = Rc::new(
+        graphics_state
+            .create_texture_from_bytes(
+                include_bytes!("happy-tree.png"),
+                // zooming out (LShift) stays smooth
+                graphics::TextureOptions {
+                    mipmaps: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+    );
+    let atlas: graphics::Atlas = {
+        let tree = image::load_from_memory(include_bytes!("happy-tree.png")).unwrap();
+        graphics::AtlasBuilder::new(graphics::AtlasOptions {
+            page_size: (512, 512),
+            ..Default::default()
+        })
+        .add("tree", &tree)
+        .add("tree_small", &tree.thumbnail(64, 64))
+        .build(&graphics_state)
+        .unwrap()
+    };
+
+    // the tree split into four 128x128 cells
+    let mut animated_tree =
+        graphics::AnimatedSprite::new(Rc::new(graphics::AnimationClip::from_grid(
+            &happy_tree,
+            (128, 128),
+            vec![0, 1, 3, 2],
+            std::time::Duration::from_millis(250),
+            graphics::PlayMode::PingPong,
+        )));
+    animated_tree.sprite.transformable.position = (800.0, 850.0);
+
     let mut renderer_with_view = graphics::renderers::RendererWithView::new(&mut graphics_state);
+    let mut renderer_sprite = graphics::renderers::RendererSprite::new(&mut graphics_state);
+    renderer_sprite.set_texture_arrays(true);
+    // a big map left of the demo, made of 32x32 pieces of the tree, the first one animated
+    let mut renderer_tilemap = {
+        let mut tilemap = graphics::Tilemap::new((32, 32));
+        tilemap.tilesets.push(graphics::Tileset {
+            first_gid: 1,
+            texture: happy_tree.clone(),
+            tile_size: (32, 32),
+            columns: 8,
+            tile_count: 64,
+            margin: 0,
+            spacing: 0,
+            animations: std::iter::once((
+                0,
+                (0..8)
+                    .map(|tile_id| graphics::tilemap::TileFrame {
+                        tile_id,
+                        duration: std::time::Duration::from_millis(100),
+                    })
+                    .collect(),
+            ))
+            .collect(),
+        });
+        let mut layer = graphics::TileLayer::new("ground", (200, 200)).unwrap();
+        layer.offset = (-6400.0, 0.0);
+        for y in 0..200 {
+            for x in 0..200 {
+                layer.tiles[(y * 200 + x) as usize] = if (x + y) % 10 == 0 {
+                    1
+                } else {
+                    2 + (x * 7 + y * 3) % 63
+                };
+            }
+        }
+        tilemap.layers.push(layer);
+        graphics::renderers::RendererTilemap::new(&mut graphics_state, tilemap)
+    };
     let mut renderer_simple_triangle =
         graphics::renderers::RendererSimpleTriangle::new(&mut graphics_state);
-    let mut renderer_glyph = graphics::renderers::RendererGlyph::new(&graphics_state);
+    renderer_simple_triangle.set_blend_mode(graphics::BlendMode::Add);
+    let mut renderer_glyph = graphics::renderers::RendererGlyph::new(&mut graphics_state);
+    renderer_glyph.retain(graphics::Text {
+        color: [0.0, 1.0, 0.0, 1.0],
+        size: 40.0,
+        position: (30.0, 30.0),
+        ..graphics::Text::new("Hello wgpu_glyph!")
+    });
+    // wrapped, centered and spaced out, in the bottom right corner
+    renderer_glyph.retain(graphics::Text {
+        size: 20.0,
+        position: (1000.0, 980.0),
+        horizontal_align: wgpu_glyph::HorizontalAlign::Right,
+        vertical_align: wgpu_glyph::VerticalAlign::Bottom,
+        wrap_width: Some(300.0),
+        line_spacing: 1.5,
+        ..graphics::Text::new(
+            "Text can be queued every frame, or retained by the renderer until it's released.",
+        )
+    });
+    renderer_glyph.retain(graphics::Text {
+        size: 24.0,
+        position: (30.0, 150.0),
+        ..graphics::Text::from_markup(
+            "[color=#ff0]Warning:[/color] [b]markup[/b] can mix [size=32]sizes[/size] and \
+             [color=#4af]colors[/color] in one text",
+        )
+    });
+    // moves, zooms and rotates with the view
+    renderer_glyph.retain(graphics::Text {
+        color: [1.0, 0.8, 0.2, 1.0],
+        size: 32.0,
+        space: graphics::TextSpace::World,
+        position: (300.0, 560.0),
+        horizontal_align: wgpu_glyph::HorizontalAlign::Center,
+        vertical_align: wgpu_glyph::VerticalAlign::Bottom,
+        ..graphics::Text::new("World space text")
+    });
+    // a distance field stays sharp however far the view zooms in, and tilted around its anchor
+    renderer_glyph.retain(graphics::Text {
+        color: [1.0, 1.0, 1.0, 1.0],
+        size: 48.0,
+        space: graphics::TextSpace::World,
+        position: (500.0, 380.0),
+        transform: graphics::Transformable {
+            position: (500.0, 380.0),
+            rotation: -8.0,
+            origin: (500.0, 380.0),
+            ..Default::default()
+        },
+        horizontal_align: wgpu_glyph::HorizontalAlign::Center,
+        rendering: graphics::TextRendering::Sdf(graphics::SdfEffects {
+            outline_width: 2.0,
+            outline_color: [0.1, 0.1, 0.4, 1.0],
+            shadow_offset: (3.0, 3.0),
+            shadow_softness: 2.0,
+            shadow_color: [0.0, 0.0, 0.0, 0.6],
+            glow_width: 4.0,
+            glow_color: [0.4, 0.8, 1.0, 0.5],
+        }),
+        ..graphics::Text::new("SDF text")
+    });
+    // click it to type, in the top right corner
+    let mut text_field = graphics::TextField::new(graphics::Rect::new(650.0, 300.0, 300.0, 32.0));
+    text_field.text.size = 24.0;
+    text_field.set_string("Click to edit this text field");
+    // copy and paste through the system clipboard with the `system-clipboard` feature, otherwise
+    // inside the app
+    let mut clipboard = new_clipboard();
+    // a chat panel below the text field, scrolled with the mouse wheel over it
+    let mut chat = graphics::ScrollRegion::new(graphics::Rect::new(650.0, 350.0, 300.0, 120.0));
+    let chat_lines: Vec<String> = (1..=20)
+        .map(|i| format!("Chat message number {}", i))
+        .collect();
+    chat.content_size = (300.0, chat_lines.len() as f32 * chat.line_height);
+    chat.scroll_to_end();
     let mut renderer_imgui =
         graphics::renderers::RendererImgui::new(&graphics_state, imgui_context, imgui_platform);
 
@@ -71,11 +217,19 @@ fn main() {
                 ref event,
                 window_id,
             } if window_id == graphics_state.window.id() => {
+                let consumed = text_field.handle_event(event, &renderer_glyph, clipboard.as_mut());
+                chat.handle_event(event);
+                if text_field.focused {
+                    let (x, y) = text_field.ime_position(&renderer_glyph);
+                    graphics_state
+                        .window
+                        .set_ime_position(winit::dpi::PhysicalPosition::new(x, y));
+                }
                 match event {
                     WindowEvent::CloseRequested => {
                         *control_flow = winit::event_loop::ControlFlow::Exit
                     }
-                    WindowEvent::KeyboardInput { input, .. } => match input {
+                    WindowEvent::KeyboardInput { input, .. } if !consumed => match input {
                         KeyboardInput {
                             state: ElementState::Pressed,
                             virtual_keycode: Some(keycode),
@@ -132,9 +286,175 @@ fn main() {
                 Err(e) => log::warn!("{:?}", e),
 
                 Ok(mut current_frame) => {
+                    renderer_with_view.clear();
+                    renderer_with_view.push(&[
+                        vertex::Vertex {
+                            position: [100.0, 100.0, 0.0],
+                            color: [0.0, 0.1, 1.0, 1.0],
+                        },
+                        vertex::Vertex {
+                            position: [900.0, 900.0, 0.0],
+                            color: [0.0, 0.2, 1.0, 1.0],
+                        },
+                        vertex::Vertex {
+                            position: [900.0, 100.0, 0.0],
+                            color: [0.0, 0.3, 1.0, 1.0],
+                        },
+                    ]);
+                    renderer_with_view.push(
+                        &graphics::Polyline {
+                            points: vec![
+                                (150.0, 850.0),
+                                (300.0, 600.0),
+                                (450.0, 850.0),
+                                (600.0, 600.0),
+                            ],
+                            width: 20.0,
+                            color: [1.0, 0.8, 0.2, 1.0],
+                            join: graphics::LineJoin::Round,
+                            cap: graphics::LineCap::Round,
+                            ..Default::default()
+                        }
+                        .vertices(),
+                    );
+                    renderer_with_view.push(
+                        &graphics::Polyline {
+                            points: vec![
+                                (150.0, 150.0),
+                                (400.0, 150.0),
+                                (400.0, 400.0),
+                                (150.0, 400.0),
+                            ],
+                            width: 8.0,
+                            color: [1.0, 0.3, 0.3, 1.0],
+                            closed: true,
+                            ..Default::default()
+                        }
+                        .vertices(),
+                    );
+                    // two waves, strips aren't batched so they stay apart
+                    renderer_with_view.set_topology(wgpu::PrimitiveTopology::LineStrip);
+                    for row in 0..2 {
+                        let wave = (0..=20)
+                            .map(|i| vertex::Vertex {
+                                position: [
+                                    150.0 + 12.5 * i as f32,
+                                    430.0 + 25.0 * row as f32 + 8.0 * (0.8 * i as f32).sin(),
+                                    0.0,
+                                ],
+                                color: [1.0, 1.0, 1.0, 1.0],
+                            })
+                            .collect::<Vec<_>>();
+                        renderer_with_view.push(&wave);
+                    }
+                    renderer_with_view.set_topology(wgpu::PrimitiveTopology::TriangleList);
+                    {
+                        let path = graphics::Path::new()
+                            .move_to(700.0, 450.0)
+                            .cubic_to(850.0, 300.0, 950.0, 550.0, 700.0, 750.0)
+                            .cubic_to(450.0, 550.0, 550.0, 300.0, 700.0, 450.0)
+                            .close()
+                            .circle(700.0, 560.0, 50.0);
+                        renderer_with_view
+                            .push(&path.fill(graphics::FillRule::EvenOdd, [0.9, 0.2, 0.5, 1.0]));
+                        renderer_with_view.push(&path.stroke(&graphics::Polyline {
+                            width: 4.0,
+                            color: [1.0, 1.0, 1.0, 1.0],
+                            join: graphics::LineJoin::Round,
+                            ..Default::default()
+                        }));
+                    }
+                    {
+                        let square = graphics::Path::new()
+                            .rect(0.0, 0.0, 60.0, 60.0)
+                            .fill(graphics::FillRule::NonZero, [0.3, 0.9, 0.4, 1.0]);
+                        let transformables = (0..4)
+                            .map(|i| graphics::Transformable {
+                                position: (150.0 + 100.0 * i as f32, 500.0),
+                                rotation: 15.0 * i as f32,
+                                origin: (30.0, 30.0),
+                                ..Default::default()
+                            })
+                            .collect::<Vec<_>>();
+                        renderer_with_view.set_blend_mode(graphics::BlendMode::Add);
+                        renderer_with_view.push_instanced(&square, &transformables);
+                        renderer_with_view.set_blend_mode(graphics::BlendMode::Alpha);
+                        renderer_with_view.push_transformed(
+                            &square,
+                            &graphics::Transformable {
+                                position: (600.0, 150.0),
+                                scale: (2.0, 0.5),
+                                ..Default::default()
+                            },
+                        );
+                    }
                     renderer_with_view.draw(&mut current_frame, &view);
+
+                    renderer_tilemap.draw(&mut current_frame, &view);
+
+                    renderer_sprite.clear();
+                    for i in 0..3 {
+                        let mut sprite = graphics::Sprite::new(happy_tree.clone());
+                        sprite.transformable.position = (250.0 + 200.0 * i as f32, 250.0);
+                        sprite.transformable.origin = (128.0, 128.0);
+                        sprite.transformable.rotation = 20.0 * i as f32;
+                        sprite.transformable.scale = (0.5, 0.5);
+                        renderer_sprite.push(&sprite);
+                    }
+                    {
+                        // just the top half, tinted
+                        let mut sprite = graphics::Sprite::new(happy_tree.clone());
+                        sprite.texture_rect = Some(graphics::Rect::new(0.0, 0.0, 256.0, 128.0));
+                        sprite.color = [1.0, 0.5, 0.5, 0.8];
+                        sprite.transformable.position = (700.0, 750.0);
+                        renderer_sprite.push(&sprite);
+                    }
+                    for i in 0..4 {
+                        // both from the same atlas page, so a single batch
+                        let name = if i % 2 == 0 { "tree_small" } else { "tree" };
+                        let mut sprite = atlas.sprite(name).unwrap();
+                        let bounds = sprite.local_bounds();
+                        sprite.transformable.position = (100.0 + 80.0 * i as f32, 850.0);
+                        sprite.transformable.scale = (64.0 / bounds.width, 64.0 / bounds.height);
+                        renderer_sprite.push(&sprite);
+                    }
+                    renderer_sprite.push(&animated_tree.sprite);
+                    {
+                        // a panel with the tree's 48px borders, stretched and tiled
+                        let mut panel = graphics::NineSlice::new(
+                            happy_tree.clone(),
+                            graphics::Insets::uniform(48.0),
+                            (300.0, 120.0),
+                        );
+                        panel.transformable.position = (650.0, 20.0);
+                        renderer_sprite.push_nine_slice(&panel);
+                        panel.edge_fill = graphics::SliceFill::Tile;
+                        panel.center_fill = graphics::SliceFill::Tile;
+                        panel.color = [0.6, 1.0, 0.6, 0.9];
+                        panel.transformable.position = (650.0, 160.0);
+                        renderer_sprite.push_nine_slice(&panel);
+                    }
+                    renderer_sprite.draw(&mut current_frame, &view);
                     renderer_simple_triangle.draw(&mut current_frame);
-                    renderer_glyph.draw(&mut current_frame);
+                    renderer_glyph.queue_text(&graphics::Text {
+                        color: [1.0, 1.0, 1.0, 1.0],
+                        size: 40.0,
+                        position: (30.0, 90.0),
+                        ..graphics::Text::new(format!(
+                            "Hello wgpu_glyph! Random number: {}",
+                            rand::thread_rng().gen_range(0..100)
+                        ))
+                    });
+                    text_field.queue(&mut renderer_glyph);
+                    for (i, line) in chat_lines.iter().enumerate() {
+                        renderer_glyph.queue_text(&chat.place(&graphics::Text {
+                            color: [1.0, 1.0, 1.0, 1.0],
+                            size: 18.0,
+                            position: (4.0, i as f32 * chat.line_height),
+                            ..graphics::Text::new(line.as_str())
+                        }));
+                    }
+                    renderer_glyph.draw(&mut current_frame, &view);
                     renderer_imgui.draw(&mut current_frame);
                     current_frame.finish_and_present();
                 }
@@ -142,6 +462,8 @@ fn main() {
             Event::MainEventsCleared => {
                 // incoming networking here
                 // updating + physics here
+                animated_tree.advance(graphics_state.frame_counter());
+                renderer_tilemap.advance(graphics_state.frame_counter());
                 // outgoing networking again here?
                 // draw:
                 graphics_state.window.request_redraw();
@@ -158,3 +480,19 @@ fn main() {
         );
     });
 }
+
+#[cfg(feature = "system-clipboard")]
+fn new_clipboard() -> Box<dyn graphics::text_field::Clipboard> {
+    match graphics::text_field::SystemClipboard::new() {
+        Ok(clipboard) => Box::new(clipboard),
+        Err(e) => {
+            log::warn!("no system clipboard, copying inside the app: {}", e);
+            Box::new(String::new())
+        }
+    }
+}
+
+#[cfg(not(feature = "system-clipboard"))]
+fn new_clipboard() -> Box<dyn graphics::text_field::Clipboard> {
+    Box::new(String::new())
+}
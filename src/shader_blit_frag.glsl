@@ -0,0 +1,12 @@
+// shader_blit.frag
+#version 450
+
+layout(location=0) in vec2 v_uv; // from the vertex shader
+layout(location=0) out vec4 f_color; // to the mip level being generated
+
+layout(set=0, binding=0) uniform texture2D t_texture;
+layout(set=0, binding=1) uniform sampler s_texture;
+
+void main() {
+    f_color = texture(sampler2D(t_texture, s_texture), v_uv);
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+// shader_blit.vert
+#version 450
+
+layout(location=0) out vec2 v_uv; // to the fragment shader
+
+// a single triangle covering the whole target, no vertex buffer needed
+void main() {
+    vec2 position = vec2(gl_VertexIndex == 1 ? 3.0 : -1.0, gl_VertexIndex == 2 ? 3.0 : -1.0);
+    v_uv = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+
+    gl_Position = vec4(position, 0.0, 1.0);
+}
\ No newline at end of file
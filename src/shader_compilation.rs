@@ -8,7 +8,10 @@ pub fn module(
 ) -> Result<wgpu::ShaderModule, shaderc::Error> {
     let spirv = compiler
         .compile_into_spirv(src_str, shader_kind, src_filename, "main", None)
-        .unwrap();
+        .map_err(|e| {
+            log::error!("{} failed to compile: {}", src_filename, e);
+            e
+        })?;
 
     let data = wgpu::util::make_spirv(spirv.as_binary_u8());
 
@@ -54,3 +57,70 @@ pub fn vertex_module(
         label,
     )
 }
+
+pub struct ShaderSource<'a> {
+    pub src: &'a str,
+    pub filename: &'a str,
+    pub label: &'a str,
+}
+
+// what the error vertex shader does with the position at location 0
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ErrorVertex {
+    // transformed by the view uniform at set 0, binding 0, like the sprite shaders
+    View,
+    // already in clip space
+    Clip,
+}
+
+// a vertex and fragment shader pair, with the magenta and black error shaders standing in
+// for the ones that don't compile, so a broken shader shows up on screen instead of panicking;
+// a failed vertex shader replaces both, the fragment shader's inputs would be missing otherwise
+pub fn modules_or_error(
+    compiler: &mut shaderc::Compiler,
+    device: &wgpu::Device,
+    vertex: ShaderSource,
+    fragment: ShaderSource,
+    error_vertex: ErrorVertex,
+) -> (wgpu::ShaderModule, wgpu::ShaderModule) {
+    let vs_module = vertex_module(compiler, device, vertex.src, vertex.filename, vertex.label);
+    let fs_module = match vs_module {
+        Ok(_) => fragment_module(
+            compiler,
+            device,
+            fragment.src,
+            fragment.filename,
+            fragment.label,
+        )
+        .ok(),
+        Err(_) => None,
+    };
+
+    let vs_module = vs_module.unwrap_or_else(|_| {
+        log::warn!("using the error shaders in place of {}", vertex.filename);
+        let (src, filename) = match error_vertex {
+            ErrorVertex::View => (
+                include_str!("shader_error_vert.glsl"),
+                "shader_error_vert.glsl",
+            ),
+            ErrorVertex::Clip => (
+                include_str!("shader_error_clip_vert.glsl"),
+                "shader_error_clip_vert.glsl",
+            ),
+        };
+        vertex_module(compiler, device, src, filename, "Error Vertex Shader").unwrap()
+    });
+    let fs_module = fs_module.unwrap_or_else(|| {
+        log::warn!("using the error shader in place of {}", fragment.filename);
+        fragment_module(
+            compiler,
+            device,
+            include_str!("shader_error_frag.glsl"),
+            "shader_error_frag.glsl",
+            "Error Fragment Shader",
+        )
+        .unwrap()
+    });
+
+    (vs_module, fs_module)
+}
@@ -0,0 +1,8 @@
+// shader_error_clip.vert
+#version 450
+
+layout(location=0) in vec2 a_position; // from the vertex, the rest of it is ignored
+
+void main() {
+    gl_Position = vec4(a_position, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+// shader_error.frag
+#version 450
+
+layout(location=0) out vec4 f_color; // to the buffer 0 - current texture from the swapchain, the screen
+
+void main() {
+    // 8 pixel magenta and black squares, in screen space
+    ivec2 cell = ivec2(gl_FragCoord.xy) / 8;
+    f_color = (cell.x + cell.y) % 2 == 0 ? vec4(1.0, 0.0, 1.0, 1.0) : vec4(0.0, 0.0, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+// shader_error.vert
+#version 450
+
+layout(location=0) in vec2 a_position; // from the vertex, the rest of it is ignored
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_view;
+};
+
+void main() {
+    gl_Position = u_view * vec4(a_position, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,55 @@
+// shader_sdf_text.frag
+#version 450
+
+layout(location=0) in vec2 v_uv; // from the vertex shader, in atlas pixels
+layout(location=1) flat in vec4 v_uv_rect; // from the vertex shader
+layout(location=2) in vec4 v_color; // from the vertex shader
+layout(location=3) in vec4 v_outline_color; // from the vertex shader
+layout(location=4) in vec4 v_shadow_color; // from the vertex shader
+layout(location=5) in vec4 v_glow_color; // from the vertex shader
+layout(location=6) flat in vec3 v_widths; // from the vertex shader, outline, glow, shadow softness
+layout(location=7) flat in vec2 v_shadow_offset; // from the vertex shader
+layout(location=0) out vec4 f_color; // to the buffer 0 - current texture from the swapchain, the screen
+
+layout(set=1, binding=0) uniform texture2D t_sdf;
+layout(set=1, binding=1) uniform sampler s_sdf;
+
+// 0.5 on the outline, more inside
+float distance_at(vec2 uv) {
+    vec2 size = vec2(textureSize(sampler2D(t_sdf, s_sdf), 0));
+    uv = clamp(uv, v_uv_rect.xy, v_uv_rect.zw);
+    return texture(sampler2D(t_sdf, s_sdf), uv / size).r;
+}
+
+vec4 premultiplied(vec4 color, float coverage) {
+    float alpha = color.a * coverage;
+    return vec4(color.rgb * alpha, alpha);
+}
+
+vec4 over(vec4 top, vec4 bottom) {
+    return top + bottom * (1.0 - top.a);
+}
+
+void main() {
+    // quads without a rect in the atlas are solid, like a text field's selection
+    if (v_uv_rect.z <= v_uv_rect.x) {
+        f_color = v_color;
+        return;
+    }
+    float fill_distance = distance_at(v_uv);
+    // about a pixel on screen, for antialiasing
+    float smoothing = max(fwidth(fill_distance) * 0.5, 0.001);
+    float outline_edge = 0.5 - v_widths.x;
+
+    float fill = smoothstep(0.5 - smoothing, 0.5 + smoothing, fill_distance);
+    float outline = smoothstep(outline_edge - smoothing, outline_edge + smoothing, fill_distance);
+    float glow = smoothstep(outline_edge - v_widths.y - smoothing, outline_edge, fill_distance);
+    float shadow_distance = distance_at(v_uv - v_shadow_offset);
+    float shadow = smoothstep(outline_edge - v_widths.z - smoothing, outline_edge + smoothing, shadow_distance);
+
+    vec4 color = premultiplied(v_glow_color, glow);
+    color = over(premultiplied(v_shadow_color, shadow), color);
+    color = over(premultiplied(v_outline_color, outline), color);
+    color = over(premultiplied(v_color, fill), color);
+    f_color = color.a > 0.0 ? vec4(color.rgb / color.a, color.a) : vec4(0.0);
+}
\ No newline at end of file
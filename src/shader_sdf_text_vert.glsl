@@ -0,0 +1,39 @@
+// shader_sdf_text.vert
+#version 450
+
+layout(location=0) in vec2 a_position; // from the vertex
+layout(location=1) in vec2 a_uv; // from the vertex
+layout(location=2) in vec4 a_uv_rect; // from the vertex
+layout(location=3) in vec4 a_color; // from the vertex
+layout(location=4) in vec4 a_outline_color; // from the vertex
+layout(location=5) in vec4 a_shadow_color; // from the vertex
+layout(location=6) in vec4 a_glow_color; // from the vertex
+layout(location=7) in vec3 a_widths; // from the vertex
+layout(location=8) in vec2 a_shadow_offset; // from the vertex
+
+layout(location=0) out vec2 v_uv; // to the fragment shader
+layout(location=1) flat out vec4 v_uv_rect; // to the fragment shader
+layout(location=2) out vec4 v_color; // to the fragment shader
+layout(location=3) out vec4 v_outline_color; // to the fragment shader
+layout(location=4) out vec4 v_shadow_color; // to the fragment shader
+layout(location=5) out vec4 v_glow_color; // to the fragment shader
+layout(location=6) flat out vec3 v_widths; // to the fragment shader
+layout(location=7) flat out vec2 v_shadow_offset; // to the fragment shader
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_view;
+};
+
+void main() {
+    v_uv = a_uv;
+    v_uv_rect = a_uv_rect;
+    v_color = a_color;
+    v_outline_color = a_outline_color;
+    v_shadow_color = a_shadow_color;
+    v_glow_color = a_glow_color;
+    v_widths = a_widths;
+    v_shadow_offset = a_shadow_offset;
+
+    gl_Position = u_view * vec4(a_position, 0.0, 1.0);
+}
\ No newline at end of file
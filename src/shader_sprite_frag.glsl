@@ -0,0 +1,13 @@
+// shader_sprite.frag
+#version 450
+
+layout(location=0) in vec2 v_uv; // from the vertex shader
+layout(location=1) in vec4 v_color; // from the vertex shader
+layout(location=0) out vec4 f_color; // to the buffer 0 - current texture from the swapchain, the screen
+
+layout(set=1, binding=0) uniform texture2D t_texture;
+layout(set=1, binding=1) uniform sampler s_texture;
+
+void main() {
+    f_color = texture(sampler2D(t_texture, s_texture), v_uv) * v_color;
+}
\ No newline at end of file
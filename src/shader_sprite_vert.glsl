@@ -0,0 +1,21 @@
+// shader_sprite.vert
+#version 450
+
+layout(location=0) in vec2 a_position; // from the vertex
+layout(location=1) in vec2 a_uv; // from the vertex
+layout(location=2) in vec4 a_color; // from the vertex
+
+layout(location=0) out vec2 v_uv; // to the fragment shader
+layout(location=1) out vec4 v_color; // to the fragment shader
+
+layout(set=0, binding=0)
+uniform Uniforms {
+    mat4 u_view;
+};
+
+void main() {
+    v_uv = a_uv;
+    v_color = a_color;
+
+    gl_Position = u_view * vec4(a_position, 0.0, 1.0);
+}
\ No newline at end of file
@@ -2,9 +2,10 @@
 #version 450
 
 layout(location=0) in vec3 a_position; // from the vertex
-layout(location=1) in vec3 a_color; // from the vertex
+layout(location=1) in vec4 a_color; // from the vertex
+layout(location=2) in mat4 i_model; // from the instance, takes up locations 2 to 5
 
-layout(location=0) out vec3 v_color; // to the fragment shader
+layout(location=0) out vec4 v_color; // to the fragment shader
 
 layout(set=0, binding=0)
 uniform Uniforms {
@@ -14,5 +15,5 @@ uniform Uniforms {
 void main() {
     v_color = a_color;
 
-    gl_Position = u_view * vec4(a_position, 1.0);
+    gl_Position = u_view * i_model * vec4(a_position, 1.0);
 }
\ No newline at end of file
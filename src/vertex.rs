@@ -1,29 +1,154 @@
+pub use wgpu_tiny_graphics_derive::VertexLayout;
+
+// implemented by #[derive(VertexLayout)], which fills in the attributes from the field types
+pub trait VertexLayout: bytemuck::Pod {
+    const STEP_MODE: wgpu::InputStepMode;
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+
+    fn descriptor<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: Self::STEP_MODE,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+// the vertex format a field type maps to
+pub trait VertexAttributeFormat {
+    const FORMAT: wgpu::VertexFormat;
+}
+
+macro_rules! vertex_attribute_formats {
+    ($($ty:ty => $format:ident,)*) => {
+        $(
+            impl VertexAttributeFormat for $ty {
+                const FORMAT: wgpu::VertexFormat = wgpu::VertexFormat::$format;
+            }
+        )*
+    };
+}
+
+vertex_attribute_formats! {
+    f32 => Float,
+    [f32; 2] => Float2,
+    [f32; 3] => Float3,
+    [f32; 4] => Float4,
+    u32 => Uint,
+    [u32; 2] => Uint2,
+    [u32; 3] => Uint3,
+    [u32; 4] => Uint4,
+    i32 => Int,
+    [i32; 2] => Int2,
+    [i32; 3] => Int3,
+    [i32; 4] => Int4,
+    [u8; 4] => Uchar4,
+    Uchar4Norm => Uchar4Norm,
+}
+
+// a color packed into 4 bytes, read as a vec4 in 0..1 by the shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Uchar4Norm(pub [u8; 4]);
+
+impl From<[f32; 4]> for Uchar4Norm {
+    fn from(color: [f32; 4]) -> Self {
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Uchar4Norm([
+            channel(color[0]),
+            channel(color[1]),
+            channel(color[2]),
+            channel(color[3]),
+        ])
+    }
+}
+
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
 pub struct Vertex {
     // wgpu::FrontFace::Ccw
-    pub(crate) position: [f32; 3],
-    pub(crate) color: [f32; 3],
+    pub position: [f32; 3],
+    pub color: [f32; 4],
 }
 
-// descriptor
-impl Vertex {
-    pub fn descriptor<'a>() -> wgpu::VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float3,
-                },
-                wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float3,
-                },
-            ],
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+pub struct TexturedVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+// a TexturedVertex that also picks the layer of a texture array
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+pub struct LayeredVertex {
+    pub(crate) position: [f32; 2],
+    pub(crate) uv: [f32; 2],
+    pub(crate) color: [f32; 4],
+    pub(crate) layer: u32,
+}
+
+impl LayeredVertex {
+    pub fn new(vertex: TexturedVertex, layer: u32) -> Self {
+        LayeredVertex {
+            position: vertex.position,
+            uv: vertex.uv,
+            color: vertex.color,
+            layer,
         }
     }
 }
+
+// a corner of a distance field glyph quad, with the text's effects
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+pub struct SdfVertex {
+    pub(crate) position: [f32; 2],
+    // in atlas pixels
+    pub(crate) uv: [f32; 2],
+    // the glyph's rect in the atlas, shadows aren't sampled outside of it
+    pub(crate) uv_rect: [f32; 4],
+    pub(crate) color: [f32; 4],
+    pub(crate) outline_color: [f32; 4],
+    pub(crate) shadow_color: [f32; 4],
+    pub(crate) glow_color: [f32; 4],
+    // outline width, glow width and shadow softness, in distance field units
+    pub(crate) widths: [f32; 3],
+    // in atlas pixels
+    pub(crate) shadow_offset: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+pub struct PackedColorVertex {
+    pub(crate) position: [f32; 3],
+    pub(crate) color: Uchar4Norm,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+pub struct Vertex2d {
+    pub(crate) position: [f32; 2],
+    pub(crate) color: Uchar4Norm,
+}
+
+// per-instance model matrix, see Transformable::instance
+// starts at location 2, right after Vertex's attributes
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+#[vertex(instance, location = 2)]
+pub struct Instance {
+    pub(crate) model: [[f32; 4]; 4],
+}
+
+impl Instance {
+    pub const IDENTITY: Instance = Instance {
+        model: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+}